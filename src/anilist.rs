@@ -0,0 +1,594 @@
+//! AniList GraphQL backend, mapped onto Kitsu's response types so the display
+//! code doesn't need to know which service answered.
+
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+    AgeRating, AnimeAttributes, AnimeData, AnimeResponse, ImageSet, Media, Page, PaginationLinks,
+    ResponseMeta, SearchOptions, SortKey, Subtype, Titles, http::HttpClient, source::AnimeSource,
+};
+
+const ANILIST_API_URL: &str = "https://graphql.anilist.co";
+/// The most results AniList serves per page.
+const ANILIST_MAX_PER_PAGE: u32 = 50;
+
+const SEARCH_QUERY: &str = "
+query ($search: String, $page: Int, $perPage: Int, $type: MediaType, $sort: [MediaSort],
+       $format: MediaFormat, $isAdult: Boolean, $season: MediaSeason, $seasonYear: Int,
+       $status: MediaStatus) {
+  Page(page: $page, perPage: $perPage) {
+    pageInfo { hasNextPage total }
+    media(search: $search, type: $type, sort: $sort, format: $format, isAdult: $isAdult,
+          season: $season, seasonYear: $seasonYear, status: $status) {
+      id
+      title { romaji english native }
+      description(asHtml: false)
+      averageScore
+      startDate { year month day }
+      endDate { year month day }
+      status
+      format
+      episodes
+      chapters
+      volumes
+      isAdult
+      genres
+      popularity
+      favourites
+      coverImage { medium large extraLarge }
+      bannerImage
+    }
+  }
+}";
+
+pub struct AniList {
+    client: HttpClient,
+}
+
+impl AniList {
+    pub fn new(client: HttpClient) -> AniList {
+        AniList { client }
+    }
+}
+
+impl AnimeSource for AniList {
+    fn name(&self) -> &'static str {
+        "anilist"
+    }
+
+    async fn search(&self, query: &str, options: &SearchOptions) -> Result<AnimeResponse> {
+        let plan = PagePlan::new(options.page);
+        let mut combined: Option<AniListPage> = None;
+
+        for page in plan.pages.clone() {
+            let body = search_body(query, options, page, plan.per_page);
+            let response = self
+                .client
+                .post_json_with_retry(ANILIST_API_URL, &body)
+                .await?;
+
+            let parsed = response
+                .json::<GraphQlResponse>()
+                .await
+                .context("Failed to parse AniList response")?;
+
+            if let Some(error) = parsed.errors.first() {
+                bail!("AniList returned an error: {}", error.message);
+            }
+
+            let chunk = parsed.data.context("AniList response had no data")?.page;
+            let exhausted = !chunk.page_info.has_next_page;
+            match &mut combined {
+                None => combined = Some(chunk),
+                Some(combined) => {
+                    combined.media.extend(chunk.media);
+                    // The last page says whether anything lies beyond it.
+                    combined.page_info = chunk.page_info;
+                }
+            }
+            if exhausted {
+                break;
+            }
+        }
+
+        let mut page = combined.context("AniList response had no data")?;
+        page.media = page
+            .media
+            .into_iter()
+            .skip(plan.skip)
+            .take(options.page.limit as usize)
+            .collect();
+        Ok(to_anime_response(page))
+    }
+
+    fn describe_search(&self, query: &str, options: &SearchOptions) -> Result<Vec<String>> {
+        let plan = PagePlan::new(options.page);
+        plan.pages
+            .map(|page| {
+                let body = search_body(query, options, page, plan.per_page);
+                self.client.describe_post(ANILIST_API_URL, &body)
+            })
+            .collect()
+    }
+}
+
+/// The AniList pages that cover a window of results. AniList pages start at
+/// multiples of their size, so a window within `ANILIST_MAX_PER_PAGE` is one
+/// page of its own size, and a larger one is fetched as full-size pages with
+/// the results outside it dropped.
+struct PagePlan {
+    /// 1-based page numbers.
+    pages: std::ops::RangeInclusive<u32>,
+    per_page: u32,
+    /// Results at the start of the first page that come before the window.
+    skip: usize,
+}
+
+impl PagePlan {
+    fn new(window: Page) -> PagePlan {
+        let limit = window.limit.max(1);
+        if limit <= ANILIST_MAX_PER_PAGE {
+            let page = window.offset / limit + 1;
+            return PagePlan {
+                pages: page..=page,
+                per_page: limit,
+                skip: 0,
+            };
+        }
+        let per_page = ANILIST_MAX_PER_PAGE;
+        let last = window.offset + limit - 1;
+        PagePlan {
+            pages: window.offset / per_page + 1..=last / per_page + 1,
+            per_page,
+            skip: (window.offset % per_page) as usize,
+        }
+    }
+}
+
+/// The GraphQL request for one page of a search.
+fn search_body(
+    query: &str,
+    options: &SearchOptions,
+    page: u32,
+    per_page: u32,
+) -> serde_json::Value {
+    let mut variables = json!({
+        "page": page,
+        "perPage": per_page,
+        "type": match options.media {
+            Media::Anime => "ANIME",
+            Media::Manga => "MANGA",
+        },
+    });
+
+    // Browses leave the search out rather than sending an empty string.
+    if !query.is_empty() {
+        variables["search"] = json!(query);
+    }
+    if let Some(sort) = options.sort {
+        variables["sort"] = json!([sort_value(sort)]);
+    }
+    if let Some(season) = options.season {
+        variables["season"] = json!(season.api_value().to_uppercase());
+    }
+    if let Some(year) = options.year {
+        variables["seasonYear"] = json!(year);
+    }
+    if let Some(subtype) = options.subtype {
+        variables["format"] = json!(format_value(subtype));
+    }
+    if options.airing {
+        variables["status"] = json!("RELEASING");
+    }
+    if options
+        .max_age_rating
+        .is_some_and(|max| max < AgeRating::R18)
+    {
+        variables["isAdult"] = json!(false);
+    }
+
+    json!({ "query": SEARCH_QUERY, "variables": variables })
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphQlResponse {
+    data: Option<PageData>,
+    #[serde(default)]
+    errors: Vec<GraphQlError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphQlError {
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct PageData {
+    #[serde(rename = "Page")]
+    page: AniListPage,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AniListPage {
+    page_info: PageInfo,
+    media: Vec<AniListMedia>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PageInfo {
+    has_next_page: bool,
+    total: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AniListMedia {
+    id: u64,
+    title: AniListTitle,
+    description: Option<String>,
+    average_score: Option<u32>,
+    start_date: Option<FuzzyDate>,
+    end_date: Option<FuzzyDate>,
+    status: Option<String>,
+    format: Option<String>,
+    episodes: Option<u16>,
+    chapters: Option<u32>,
+    volumes: Option<u32>,
+    is_adult: Option<bool>,
+    #[serde(default)]
+    genres: Vec<String>,
+    /// How many users have the title on their list.
+    popularity: Option<u64>,
+    favourites: Option<u64>,
+    cover_image: Option<AniListCoverImage>,
+    banner_image: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AniListTitle {
+    romaji: Option<String>,
+    english: Option<String>,
+    native: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FuzzyDate {
+    year: Option<u32>,
+    month: Option<u32>,
+    day: Option<u32>,
+}
+
+impl FuzzyDate {
+    /// Formats as Kitsu's `YYYY-MM-DD`, or `None` unless the date is complete.
+    fn to_iso(&self) -> Option<String> {
+        Some(format!(
+            "{:04}-{:02}-{:02}",
+            self.year?, self.month?, self.day?
+        ))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AniListCoverImage {
+    medium: Option<String>,
+    large: Option<String>,
+    extra_large: Option<String>,
+}
+
+fn to_anime_response(page: AniListPage) -> AnimeResponse {
+    let next = page
+        .page_info
+        .has_next_page
+        .then(|| ANILIST_API_URL.to_string());
+
+    AnimeResponse {
+        meta: Some(ResponseMeta {
+            count: page.page_info.total,
+        }),
+        skipped: 0,
+        data: page.media.into_iter().map(to_anime_data).collect(),
+        links: Some(PaginationLinks {
+            first: None,
+            prev: None,
+            next,
+            last: None,
+        }),
+    }
+}
+
+fn to_anime_data(media: AniListMedia) -> AnimeData {
+    let AniListTitle {
+        romaji,
+        english,
+        native,
+    } = media.title;
+    let title = romaji
+        .clone()
+        .or_else(|| english.clone())
+        .or_else(|| native.clone())
+        .unwrap_or_default();
+
+    AnimeData {
+        id: media.id.to_string(),
+        attributes: AnimeAttributes {
+            canonical_title: title,
+            titles: Titles {
+                en: english,
+                en_jp: romaji,
+                ja_jp: native,
+            },
+            abbreviated_titles: None,
+            synopsis: media.description.map(|d| strip_html(&d)),
+            average_rating: media.average_score.map(|score| score.to_string()),
+            start_date: media.start_date.and_then(|d| d.to_iso()),
+            end_date: media.end_date.and_then(|d| d.to_iso()),
+            status: media.status.as_deref().map(status_value),
+            episode_count: media.episodes,
+            chapter_count: media.chapters,
+            volume_count: media.volumes,
+            subtype: media.format.as_deref().map(subtype_value),
+            age_rating: media.is_adult.filter(|adult| *adult).map(|_| "R18".into()),
+            nsfw: media.is_adult,
+            poster_image: media.cover_image.map(|cover| ImageSet {
+                tiny: None,
+                small: None,
+                medium: cover.medium,
+                large: cover.large,
+                original: cover.extra_large,
+            }),
+            cover_image: media.banner_image.map(|banner| ImageSet {
+                tiny: None,
+                small: None,
+                medium: None,
+                large: None,
+                original: Some(banner),
+            }),
+            user_count: media.popularity,
+            favorites_count: media.favourites,
+            ..Default::default()
+        },
+        categories: media.genres,
+        streamers: Vec::new(),
+        source: None,
+    }
+}
+
+/// Maps AniList's `MediaStatus` onto Kitsu's status strings.
+fn status_value(status: &str) -> String {
+    match status {
+        "FINISHED" => "finished",
+        "RELEASING" => "current",
+        "NOT_YET_RELEASED" => "upcoming",
+        "CANCELLED" => "cancelled",
+        "HIATUS" => "hiatus",
+        other => return other.to_lowercase(),
+    }
+    .to_string()
+}
+
+/// Maps AniList's `MediaFormat` onto Kitsu's subtype strings.
+fn subtype_value(format: &str) -> String {
+    match format {
+        "TV" | "TV_SHORT" => "TV",
+        "MOVIE" => "movie",
+        "SPECIAL" => "special",
+        "OVA" => "OVA",
+        "ONA" => "ONA",
+        "MUSIC" => "music",
+        other => return other.to_lowercase(),
+    }
+    .to_string()
+}
+
+fn format_value(subtype: Subtype) -> &'static str {
+    match subtype {
+        Subtype::Tv => "TV",
+        Subtype::Movie => "MOVIE",
+        Subtype::Ova => "OVA",
+        Subtype::Ona => "ONA",
+        Subtype::Special => "SPECIAL",
+        Subtype::Music => "MUSIC",
+    }
+}
+
+fn sort_value(sort: SortKey) -> &'static str {
+    match sort {
+        SortKey::Rating => "SCORE_DESC",
+        SortKey::RatingAsc => "SCORE",
+        SortKey::Popularity => "POPULARITY_DESC",
+        SortKey::PopularityAsc => "POPULARITY",
+        SortKey::Date => "START_DATE_DESC",
+        SortKey::DateAsc => "START_DATE",
+    }
+}
+
+/// AniList descriptions carry `<br>` and `<i>` tags even with `asHtml: false`.
+fn strip_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut in_tag = false;
+    for c in text.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => out.push(c),
+            _ => {}
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Season;
+
+    const RESPONSE: &str = r#"{
+      "data": {
+        "Page": {
+          "pageInfo": { "hasNextPage": true, "total": 42 },
+          "media": [
+            {
+              "id": 1,
+              "title": { "romaji": "Cowboy Bebop", "english": "Cowboy Bebop", "native": "カウボーイビバップ" },
+              "description": "Enter a world in the distant future...<br><br>\n<i>(Source: Sunrise)</i>",
+              "averageScore": 86,
+              "startDate": { "year": 1998, "month": 4, "day": 3 },
+              "endDate": { "year": 1999, "month": 4, "day": 24 },
+              "status": "FINISHED",
+              "format": "TV",
+              "episodes": 26,
+              "chapters": null,
+              "volumes": null,
+              "isAdult": false,
+              "genres": ["Action", "Sci-Fi"],
+              "popularity": 350000,
+              "favourites": 41000,
+              "coverImage": { "medium": "m.jpg", "large": "l.jpg", "extraLarge": "xl.jpg" },
+              "bannerImage": "banner.jpg"
+            },
+            {
+              "id": 2,
+              "title": { "romaji": null, "english": null, "native": "未定" },
+              "description": null,
+              "averageScore": null,
+              "startDate": { "year": 2026, "month": null, "day": null },
+              "endDate": null,
+              "status": "NOT_YET_RELEASED",
+              "format": "TV_SHORT",
+              "episodes": null,
+              "chapters": null,
+              "volumes": null,
+              "isAdult": true,
+              "genres": [],
+              "popularity": null,
+              "favourites": null,
+              "coverImage": null,
+              "bannerImage": null
+            }
+          ]
+        }
+      }
+    }"#;
+
+    fn response() -> AnimeResponse {
+        let parsed: GraphQlResponse = serde_json::from_str(RESPONSE).unwrap();
+        to_anime_response(parsed.data.unwrap().page)
+    }
+
+    #[test]
+    fn maps_a_full_entry_onto_kitsu_fields() {
+        let response = response();
+        assert!(response.has_next_page());
+        assert_eq!(response.meta.as_ref().and_then(|meta| meta.count), Some(42));
+
+        let anime = &response.data[0];
+        let attrs = &anime.attributes;
+        assert_eq!(anime.id, "1");
+        assert_eq!(attrs.canonical_title, "Cowboy Bebop");
+        assert_eq!(attrs.titles.ja_jp.as_deref(), Some("カウボーイビバップ"));
+        assert_eq!(
+            attrs.synopsis.as_deref(),
+            Some("Enter a world in the distant future...\n(Source: Sunrise)")
+        );
+        assert_eq!(attrs.average_rating.as_deref(), Some("86"));
+        assert_eq!(attrs.start_date.as_deref(), Some("1998-04-03"));
+        assert_eq!(attrs.end_date.as_deref(), Some("1999-04-24"));
+        assert_eq!(attrs.status.as_deref(), Some("finished"));
+        assert_eq!(attrs.subtype.as_deref(), Some("TV"));
+        assert_eq!(attrs.episode_count, Some(26));
+        assert!(attrs.age_rating.is_none());
+        assert_eq!(attrs.user_count, Some(350_000));
+        assert_eq!(attrs.favorites_count, Some(41_000));
+        let poster = attrs.poster_image.as_ref().unwrap();
+        assert_eq!(poster.original.as_deref(), Some("xl.jpg"));
+        assert_eq!(anime.categories, ["Action", "Sci-Fi"]);
+    }
+
+    #[test]
+    fn maps_a_sparse_entry_without_inventing_values() {
+        let response = response();
+        let attrs = &response.data[1].attributes;
+        // Without a romaji or English title, the native one is canonical.
+        assert_eq!(attrs.canonical_title, "未定");
+        assert!(attrs.average_rating.is_none());
+        // A year alone isn't a Kitsu date.
+        assert!(attrs.start_date.is_none());
+        assert_eq!(attrs.status.as_deref(), Some("upcoming"));
+        assert_eq!(attrs.subtype.as_deref(), Some("TV"));
+        assert_eq!(attrs.age_rating.as_deref(), Some("R18"));
+        assert_eq!(attrs.nsfw, Some(true));
+        assert!(attrs.poster_image.is_none() && attrs.cover_image.is_none());
+    }
+
+    #[test]
+    fn strip_html_drops_tags_and_keeps_text() {
+        assert_eq!(strip_html("<b>Spike</b> &amp; Jet<br>"), "Spike &amp; Jet");
+        assert_eq!(strip_html("1 > 0"), "1 > 0");
+        assert_eq!(
+            strip_html("<i>カウボーイ</i>ビバップ"),
+            "カウボーイビバップ"
+        );
+    }
+
+    #[test]
+    fn search_body_sets_only_the_filters_given() {
+        let options = SearchOptions {
+            sort: Some(SortKey::Rating),
+            subtype: Some(Subtype::Movie),
+            max_age_rating: Some(AgeRating::R),
+            ..Default::default()
+        };
+        let body = search_body("akira", &options, 2, 10);
+        let variables = &body["variables"];
+        assert_eq!(variables["search"], "akira");
+        assert_eq!(variables["page"], 2);
+        assert_eq!(variables["perPage"], 10);
+        assert_eq!(variables["type"], "ANIME");
+        assert_eq!(variables["sort"], json!(["SCORE_DESC"]));
+        assert_eq!(variables["format"], "MOVIE");
+        assert_eq!(variables["isAdult"], false);
+        assert!(variables.get("season").is_none());
+        assert!(variables.get("status").is_none());
+
+        let browse = search_body("", &SearchOptions::default(), 1, 10);
+        assert!(browse["variables"].get("search").is_none());
+        assert!(browse["variables"].get("isAdult").is_none());
+    }
+
+    fn plan(limit: u32, offset: u32) -> (Vec<u32>, u32, usize) {
+        let plan = PagePlan::new(Page { limit, offset });
+        (plan.pages.collect(), plan.per_page, plan.skip)
+    }
+
+    #[test]
+    fn small_windows_are_one_page_of_their_size() {
+        assert_eq!(plan(10, 0), (vec![1], 10, 0));
+        assert_eq!(plan(10, 20), (vec![3], 10, 0));
+        assert_eq!(plan(50, 50), (vec![2], 50, 0));
+    }
+
+    #[test]
+    fn large_windows_are_split_into_full_pages() {
+        assert_eq!(plan(120, 0), (vec![1, 2, 3], 50, 0));
+        // --page 2 --limit 60 is results 61-120: pages 2 and 3, from 11th.
+        assert_eq!(plan(60, 60), (vec![2, 3], 50, 10));
+        assert_eq!(plan(51, 0), (vec![1, 2], 50, 0));
+    }
+
+    #[test]
+    fn season_and_year_become_variables() {
+        let options = SearchOptions {
+            season: Some(Season::Spring),
+            year: Some(2021),
+            ..Default::default()
+        };
+        let body = search_body("", &options, 1, 10);
+        assert_eq!(body["variables"]["season"], "SPRING");
+        assert_eq!(body["variables"]["seasonYear"], 2021);
+    }
+}
@@ -0,0 +1,322 @@
+//! On-disk caches of API responses, keyed by the normalized request.
+//!
+//! Searches are stored under a hash of their source, query, filters, sort
+//! and page, next to that key spelled out so `cache list` can show it and
+//! `cache clear --source` can pick entries out.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::{AnimeResponse, SearchOptions, paths};
+
+const SEARCH_CACHE_FILE: &str = "search-cache.json";
+const CATEGORIES_CACHE_FILE: &str = "categories-cache.json";
+const CACHE_FILES: [&str; 2] = [SEARCH_CACHE_FILE, CATEGORIES_CACHE_FILE];
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry<T> {
+    /// Seconds since the Unix epoch when the response was fetched.
+    fetched_at: u64,
+    response: T,
+}
+
+#[derive(Debug)]
+pub struct DiskCache<T> {
+    path: PathBuf,
+    entries: HashMap<String, CacheEntry<T>>,
+}
+
+/// Search responses keyed by `SearchKey::hash`.
+pub type SearchCache = DiskCache<CachedSearch>;
+
+/// Category titles keyed by anime id.
+pub type CategoriesCache = DiskCache<Vec<String>>;
+
+impl SearchCache {
+    pub fn load() -> Result<SearchCache> {
+        DiskCache::open(SEARCH_CACHE_FILE)
+    }
+}
+
+impl CategoriesCache {
+    pub fn load() -> Result<CategoriesCache> {
+        DiskCache::open(CATEGORIES_CACHE_FILE)
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> DiskCache<T> {
+    /// Loads a cache from disk. A missing or unreadable cache file is not an
+    /// error; it just means every lookup misses.
+    fn open(file: &str) -> Result<DiskCache<T>> {
+        let path = paths::cache_dir()?.join(file);
+        let entries = fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+
+        Ok(DiskCache { path, entries })
+    }
+
+    /// Returns the cached response for `key` if it is younger than `ttl`.
+    pub fn get(&self, key: &str, ttl: Duration) -> Option<&T> {
+        let entry = self.entries.get(key)?;
+        let age = now().saturating_sub(entry.fetched_at);
+        (age < ttl.as_secs()).then_some(&entry.response)
+    }
+
+    /// Every entry with when it was fetched, newest first.
+    pub fn entries(&self) -> Vec<(u64, &T)> {
+        let mut entries: Vec<(u64, &T)> = self
+            .entries
+            .values()
+            .map(|entry| (entry.fetched_at, &entry.response))
+            .collect();
+        entries.sort_by_key(|(fetched_at, _)| std::cmp::Reverse(*fetched_at));
+        entries
+    }
+
+    /// Drops the entries `keep` rejects, returning how many were dropped.
+    pub fn retain(&mut self, mut keep: impl FnMut(&T) -> bool) -> usize {
+        let before = self.entries.len();
+        self.entries.retain(|_, entry| keep(&entry.response));
+        before - self.entries.len()
+    }
+
+    pub fn insert(&mut self, key: String, response: T) {
+        let entry = CacheEntry {
+            fetched_at: now(),
+            response,
+        };
+        self.entries.insert(key, entry);
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+
+        let contents = serde_json::to_string(&self.entries)?;
+        fs::write(&self.path, contents)
+            .with_context(|| format!("Failed to write cache to {}", self.path.display()))
+    }
+}
+
+/// Deletes every cache file, if there are any.
+pub fn clear_all() -> Result<()> {
+    let dir = paths::cache_dir()?;
+    for file in CACHE_FILES {
+        let path = dir.join(file);
+        match fs::remove_file(&path) {
+            Ok(()) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => {
+                return Err(err).with_context(|| format!("Failed to remove {}", path.display()));
+            }
+        }
+    }
+    Ok(())
+}
+/// A cached search response together with the search it answers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedSearch {
+    pub key: SearchKey,
+    pub response: AnimeResponse,
+}
+
+/// Everything that can change a search's results, normalized so trivially
+/// different spellings of the same search share an entry.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchKey {
+    /// The backend's name, e.g. "kitsu".
+    pub source: String,
+    /// Lowercased, with its whitespace collapsed.
+    pub query: String,
+    /// The media, page and every filter and sort that was set, in a fixed
+    /// order, e.g. "media=anime limit=10 offset=0 sort=-averageRating".
+    pub filters: String,
+}
+
+impl SearchKey {
+    pub fn new(source: &str, query: &str, options: &SearchOptions) -> SearchKey {
+        let query = query
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase();
+
+        let mut filters = vec![
+            format!("media={}", options.media.path()),
+            format!("limit={}", options.page.limit),
+            format!("offset={}", options.page.offset),
+        ];
+        let optional = [
+            (
+                "subtype",
+                options.subtype.map(|s| s.api_value().to_string()),
+            ),
+            ("sort", options.sort.map(|s| s.api_value().to_string())),
+            ("season", options.season.map(|s| s.api_value().to_string())),
+            ("year", options.year.map(|y| y.to_string())),
+            ("airing", options.airing.then(|| "true".to_string())),
+            (
+                "max_age",
+                options.max_age_rating.map(|a| a.api_value().to_string()),
+            ),
+        ];
+        for (name, value) in optional {
+            if let Some(value) = value {
+                filters.push(format!("{}={}", name, value));
+            }
+        }
+
+        SearchKey {
+            source: source.to_string(),
+            query,
+            filters: filters.join(" "),
+        }
+    }
+
+    /// The key the entry is stored under: a 64-bit FNV-1a hash of the whole
+    /// key, which unlike `std`'s hasher is the same in every build.
+    pub fn hash(&self) -> String {
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for part in [&self.source, &self.query, &self.filters] {
+            // The separator keeps ("ab", "c") and ("a", "bc") apart.
+            for byte in part.bytes().chain([0]) {
+                hash ^= u64::from(byte);
+                hash = hash.wrapping_mul(0x0100_0000_01b3);
+            }
+        }
+        format!("{:016x}", hash)
+    }
+}
+
+/// How long ago an entry was fetched, in the largest whole unit, padded to
+/// line up in `cache list`: "  5m ago", " 3h ago".
+pub fn describe_age(seconds: u64) -> String {
+    let age = match seconds {
+        0..60 => format!("{}s", seconds),
+        60..3600 => format!("{}m", seconds / 60),
+        3600..86_400 => format!("{}h", seconds / 3600),
+        _ => format!("{}d", seconds / 86_400),
+    };
+    format!("{:>4} ago", age)
+}
+
+/// Seconds since the Unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    fn cache() -> DiskCache<String> {
+        DiskCache {
+            path: PathBuf::from("unused.json"),
+            entries: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn fresh_entries_hit() {
+        let mut cache = cache();
+        cache.insert("naruto".to_string(), "response".to_string());
+        assert_eq!(
+            cache.get("naruto", HOUR).map(String::as_str),
+            Some("response")
+        );
+    }
+
+    #[test]
+    fn unknown_keys_miss() {
+        let mut cache = cache();
+        cache.insert("naruto".to_string(), "response".to_string());
+        assert!(cache.get("bleach", HOUR).is_none());
+    }
+
+    #[test]
+    fn entries_older_than_the_ttl_expire() {
+        let mut cache = cache();
+        cache.entries.insert(
+            "naruto".to_string(),
+            CacheEntry {
+                fetched_at: now() - 2 * 3600,
+                response: "stale".to_string(),
+            },
+        );
+        assert!(cache.get("naruto", HOUR).is_none());
+        assert!(cache.get("naruto", 3 * HOUR).is_some());
+        // A TTL of zero never hits, even for what was just fetched.
+        cache.insert("bleach".to_string(), "fresh".to_string());
+        assert!(cache.get("bleach", Duration::ZERO).is_none());
+    }
+
+    fn key(source: &str, query: &str, options: &SearchOptions) -> String {
+        SearchKey::new(source, query, options).hash()
+    }
+
+    #[test]
+    fn searches_differing_only_by_sort_have_different_keys() {
+        let sorted = |sort| SearchOptions {
+            sort,
+            ..Default::default()
+        };
+        let keys = [
+            key("kitsu", "naruto", &sorted(None)),
+            key("kitsu", "naruto", &sorted(Some(crate::SortKey::Rating))),
+            key("kitsu", "naruto", &sorted(Some(crate::SortKey::RatingAsc))),
+            key("kitsu", "naruto", &sorted(Some(crate::SortKey::Date))),
+        ];
+        for (i, a) in keys.iter().enumerate() {
+            for b in &keys[i + 1..] {
+                assert_ne!(a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn source_and_page_are_part_of_the_key() {
+        let options = SearchOptions::default();
+        let second_page = SearchOptions {
+            page: crate::Page::numbered(2, options.page.limit),
+            ..Default::default()
+        };
+        assert_ne!(
+            key("kitsu", "naruto", &options),
+            key("anilist", "naruto", &options)
+        );
+        assert_ne!(
+            key("kitsu", "naruto", &options),
+            key("kitsu", "naruto", &second_page)
+        );
+    }
+
+    #[test]
+    fn query_spelling_is_normalized() {
+        let options = SearchOptions::default();
+        assert_eq!(
+            key("kitsu", "  Cowboy   BEBOP ", &options),
+            key("kitsu", "cowboy bebop", &options)
+        );
+        assert_ne!(
+            key("kitsu", "cowboy bebop", &options),
+            key("kitsu", "cowboybebop", &options)
+        );
+        assert_eq!(key("kitsu", "naruto", &options).len(), 16);
+    }
+}
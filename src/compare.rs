@@ -0,0 +1,193 @@
+//! Side-by-side comparison of two anime (`compare`, and 'C' in the TUI).
+
+use colored::Colorize;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::{
+    AnimeAttributes, AnimeData, TitleLanguage, dates, glyphs, locale, status_label, theme,
+};
+
+/// Which side comes out ahead on a row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Winner {
+    Left,
+    Right,
+    /// Both sides are equal, missing, or the row isn't a contest at all.
+    Neither,
+}
+
+pub struct Row {
+    pub label: &'static str,
+    pub left: String,
+    pub right: String,
+    pub winner: Winner,
+}
+
+/// Whether a bigger or a smaller number wins a row.
+#[derive(Clone, Copy)]
+enum Better {
+    Higher,
+    Lower,
+}
+
+/// The compared fields, in display order. Rating, episodes, users and
+/// favorites are won by the higher number, ranks by the lower one.
+pub fn rows(left: &AnimeData, right: &AnimeData, language: TitleLanguage) -> Vec<Row> {
+    let (a, b) = (&left.attributes, &right.attributes);
+    vec![
+        text_row(
+            "Title",
+            a.display_title(language),
+            b.display_title(language),
+        ),
+        text_row("ID", &left.id, &right.id),
+        number_row(
+            "Rating",
+            AnimeAttributes::rating_value,
+            |rating| format!("{}/100", rating),
+            Better::Higher,
+            a,
+            b,
+        ),
+        number_row(
+            "Episodes",
+            |attrs| attrs.episode_count.map(f64::from),
+            |count| count.to_string(),
+            Better::Higher,
+            a,
+            b,
+        ),
+        text_row(
+            "Status",
+            a.status.as_deref().map_or("?", status_label),
+            b.status.as_deref().map_or("?", status_label),
+        ),
+        text_row("Aired", &aired(a), &aired(b)),
+        number_row(
+            "Popularity",
+            |attrs| attrs.popularity_rank.map(f64::from),
+            |rank| format!("#{}", locale::count(rank as u64)),
+            Better::Lower,
+            a,
+            b,
+        ),
+        number_row(
+            "Rating rank",
+            |attrs| attrs.rating_rank.map(f64::from),
+            |rank| format!("#{}", locale::count(rank as u64)),
+            Better::Lower,
+            a,
+            b,
+        ),
+        number_row(
+            "Users",
+            |attrs| attrs.user_count.map(|count| count as f64),
+            |count| locale::count(count as u64),
+            Better::Higher,
+            a,
+            b,
+        ),
+        number_row(
+            "Favorites",
+            |attrs| attrs.favorites_count.map(|count| count as f64),
+            |count| locale::count(count as u64),
+            Better::Higher,
+            a,
+            b,
+        ),
+    ]
+}
+
+fn text_row(label: &'static str, left: &str, right: &str) -> Row {
+    Row {
+        label,
+        left: left.to_string(),
+        right: right.to_string(),
+        winner: Winner::Neither,
+    }
+}
+
+fn number_row(
+    label: &'static str,
+    value: impl Fn(&AnimeAttributes) -> Option<f64>,
+    format: impl Fn(f64) -> String,
+    better: Better,
+    a: &AnimeAttributes,
+    b: &AnimeAttributes,
+) -> Row {
+    let (left, right) = (value(a), value(b));
+    let winner = match (left, right, better) {
+        (Some(l), Some(r), Better::Higher) if l > r => Winner::Left,
+        (Some(l), Some(r), Better::Higher) if r > l => Winner::Right,
+        (Some(l), Some(r), Better::Lower) if l < r => Winner::Left,
+        (Some(l), Some(r), Better::Lower) if r < l => Winner::Right,
+        _ => Winner::Neither,
+    };
+    let shown = |value: Option<f64>| value.map_or_else(|| "?".to_string(), &format);
+    Row {
+        label,
+        left: shown(left),
+        right: shown(right),
+        winner,
+    }
+}
+
+fn aired(attrs: &AnimeAttributes) -> String {
+    match &attrs.start_date {
+        Some(start) => dates::airing_period(start, attrs.end_date.as_deref()),
+        None => "?".to_string(),
+    }
+}
+
+const LABEL_WIDTH: usize = 12;
+
+/// Prints the comparison as a label column and one column per anime, with
+/// each row's winner in bold and the theme's "high rating" color.
+pub fn display(left: &AnimeData, right: &AnimeData, language: TitleLanguage, width: usize) {
+    let column = width.saturating_sub(LABEL_WIDTH + 4).max(20) / 2;
+    let theme = theme::current();
+
+    outln!("{}", theme.rule_line(width));
+    for row in rows(left, right, language) {
+        let cell = |text: &str, wins: bool| {
+            let text = fit(text, column);
+            if wins {
+                text.color(theme.rating_high).bold().to_string()
+            } else {
+                text
+            }
+        };
+        outln!(
+            "{}  {}  {}",
+            fit(row.label, LABEL_WIDTH).bold(),
+            cell(&row.left, row.winner == Winner::Left),
+            cell(&row.right, row.winner == Winner::Right)
+        );
+    }
+    outln!("{}", theme.rule_line(width));
+}
+
+/// `text` cut or padded to exactly `width` columns, ending in an ellipsis if
+/// cut.
+pub fn fit(text: &str, width: usize) -> String {
+    let ellipsis = glyphs::current().ellipsis;
+    let mut out = String::new();
+    let mut used = 0;
+    if text.width() > width {
+        for c in text.chars() {
+            let w = c.width().unwrap_or(0);
+            if used + w + ellipsis.width() > width {
+                break;
+            }
+            out.push(c);
+            used += w;
+        }
+        out.push_str(ellipsis);
+        used += ellipsis.width();
+    } else {
+        out.push_str(text);
+        used = text.width();
+    }
+    out.push_str(&" ".repeat(width.saturating_sub(used)));
+    out
+}
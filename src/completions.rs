@@ -0,0 +1,345 @@
+//! Shell completion scripts generated from the clap command definition.
+//!
+//! Every shell gets the same data-driven script: walk the words typed so far
+//! to find which (sub)command is active, then offer either that command's
+//! subcommands and flags, or the possible values of the flag just typed.
+
+use std::io::{self, Write};
+
+use clap::{Command, ValueEnum};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+    Elvish,
+}
+
+/// Everything the scripts need to know, keyed by space-separated command
+/// paths such as `"sabikani cache"`.
+#[derive(Debug, Default)]
+struct Spec {
+    /// Paths that name a subcommand, i.e. where a word descends a level.
+    subcommands: Vec<String>,
+    /// Words to offer at each path: subcommand names and flags.
+    options: Vec<(String, Vec<String>)>,
+    /// Words to offer after `"<path> <flag>"` for flags that take a value.
+    values: Vec<(String, Vec<String>)>,
+}
+
+pub fn generate(shell: Shell, cmd: &mut Command, out: &mut impl Write) -> io::Result<()> {
+    cmd.build();
+    let name = cmd.get_name().to_string();
+
+    let mut spec = Spec::default();
+    walk(cmd, &name, &mut spec);
+
+    let script = match shell {
+        Shell::Bash => bash(&name, &spec),
+        Shell::Zsh => zsh(&name, &spec),
+        Shell::Fish => fish(&name, &spec),
+        Shell::Powershell => powershell(&name, &spec),
+        Shell::Elvish => elvish(&name, &spec),
+    };
+    out.write_all(script.as_bytes())
+}
+
+fn walk(cmd: &Command, path: &str, spec: &mut Spec) {
+    let mut words: Vec<String> = cmd
+        .get_subcommands()
+        .filter(|sub| !sub.is_hide_set())
+        .map(|sub| sub.get_name().to_string())
+        .collect();
+
+    for arg in cmd.get_arguments().filter(|arg| !arg.is_hide_set()) {
+        let flags: Vec<String> = arg
+            .get_long()
+            .map(|long| format!("--{}", long))
+            .into_iter()
+            .chain(arg.get_short().map(|short| format!("-{}", short)))
+            .collect();
+
+        if arg.get_action().takes_values() {
+            let possible: Vec<String> = arg
+                .get_possible_values()
+                .iter()
+                .filter(|value| !value.is_hide_set())
+                .map(|value| value.get_name().to_string())
+                .collect();
+            for flag in &flags {
+                spec.values
+                    .push((format!("{} {}", path, flag), possible.clone()));
+            }
+        }
+
+        words.extend(flags);
+    }
+
+    spec.options.push((path.to_string(), words));
+
+    for sub in cmd.get_subcommands() {
+        let sub_path = format!("{} {}", path, sub.get_name());
+        spec.subcommands.push(sub_path.clone());
+        walk(sub, &sub_path, spec);
+    }
+}
+
+/// Wraps `word` in single quotes, escaping embedded quotes POSIX-style.
+fn sh_quote(word: &str) -> String {
+    format!("'{}'", word.replace('\'', r"'\''"))
+}
+
+/// Single-quotes `word` for PowerShell and Elvish, which double the quote.
+fn doubled_quote(word: &str) -> String {
+    format!("'{}'", word.replace('\'', "''"))
+}
+
+fn words(list: &[String], quote: fn(&str) -> String) -> String {
+    list.iter()
+        .map(|word| quote(word))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn bash(name: &str, spec: &Spec) -> String {
+    let function = format!("_{}", name.replace('-', "_"));
+    let mut script = format!(
+        "{function}() {{
+    local cur prev cmdpath word i opts
+    cur=\"${{COMP_WORDS[COMP_CWORD]}}\"
+    prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\"
+    cmdpath={name}
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        word=\"${{COMP_WORDS[i]}}\"
+        case \"$cmdpath $word\" in
+"
+    );
+
+    for path in &spec.subcommands {
+        script.push_str(&format!(
+            "            {}) cmdpath=\"$cmdpath $word\" ;;\n",
+            sh_quote(path)
+        ));
+    }
+    script.push_str("        esac\n    done\n\n    case \"$cmdpath $prev\" in\n");
+    for (key, values) in &spec.values {
+        script.push_str(&format!(
+            "        {})\n            COMPREPLY=($(compgen -W {} -- \"$cur\"))\n            return ;;\n",
+            sh_quote(key),
+            sh_quote(&values.join(" "))
+        ));
+    }
+    script.push_str("    esac\n\n    case \"$cmdpath\" in\n");
+    for (path, options) in &spec.options {
+        script.push_str(&format!(
+            "        {}) opts={} ;;\n",
+            sh_quote(path),
+            sh_quote(&options.join(" "))
+        ));
+    }
+    script.push_str(&format!(
+        "    esac\n    COMPREPLY=($(compgen -W \"$opts\" -- \"$cur\"))\n}}\n\ncomplete -F {function} {name}\n"
+    ));
+    script
+}
+
+fn zsh(name: &str, spec: &Spec) -> String {
+    let function = format!("_{}", name.replace('-', "_"));
+    let mut script = format!(
+        "#compdef {name}
+
+{function}() {{
+    local cur prev cmdpath word i
+    local -a opts
+    cur=\"${{words[CURRENT]}}\"
+    prev=\"${{words[CURRENT-1]}}\"
+    cmdpath={name}
+    for ((i = 2; i < CURRENT; i++)); do
+        word=\"${{words[i]}}\"
+        case \"$cmdpath $word\" in
+"
+    );
+
+    for path in &spec.subcommands {
+        script.push_str(&format!(
+            "            ({}) cmdpath=\"$cmdpath $word\" ;;\n",
+            sh_quote(path)
+        ));
+    }
+    script.push_str("        esac\n    done\n\n    case \"$cmdpath $prev\" in\n");
+    for (key, values) in &spec.values {
+        script.push_str(&format!(
+            "        ({})\n            opts=({})\n            compadd -- $opts\n            return ;;\n",
+            sh_quote(key),
+            words(values, sh_quote)
+        ));
+    }
+    script.push_str("    esac\n\n    case \"$cmdpath\" in\n");
+    for (path, options) in &spec.options {
+        script.push_str(&format!(
+            "        ({}) opts=({}) ;;\n",
+            sh_quote(path),
+            words(options, sh_quote)
+        ));
+    }
+    script.push_str(&format!(
+        "    esac\n    compadd -- $opts\n}}\n\ncompdef {function} {name}\n"
+    ));
+    script
+}
+
+fn fish(name: &str, spec: &Spec) -> String {
+    let function = format!("__{}_complete", name.replace('-', "_"));
+    let mut script = format!(
+        "function {function}
+    set -l tokens (commandline -opc)
+    set -l args $tokens
+    set -e args[1]
+    set -l cmdpath {name}
+    for word in $args
+        switch \"$cmdpath $word\"
+"
+    );
+
+    if !spec.subcommands.is_empty() {
+        script.push_str(&format!(
+            "            case {}\n                set cmdpath \"$cmdpath $word\"\n",
+            words(&spec.subcommands, sh_quote)
+        ));
+    }
+    script.push_str("        end\n    end\n\n    switch \"$cmdpath $tokens[-1]\"\n");
+    for (key, values) in &spec.values {
+        script.push_str(&format!(
+            "        case {}\n            printf '%s\\n' {}\n            return\n",
+            sh_quote(key),
+            words(values, sh_quote)
+        ));
+    }
+    script.push_str("    end\n\n    switch $cmdpath\n");
+    for (path, options) in &spec.options {
+        script.push_str(&format!(
+            "        case {}\n            printf '%s\\n' {}\n",
+            sh_quote(path),
+            words(options, sh_quote)
+        ));
+    }
+    script.push_str(&format!(
+        "    end\nend\n\ncomplete -c {name} -f -a '({function})'\n"
+    ));
+    script
+}
+
+fn powershell_table(entries: &[(String, Vec<String>)]) -> String {
+    entries
+        .iter()
+        .map(|(key, list)| {
+            format!(
+                "        {} = @({})\n",
+                doubled_quote(key),
+                list.iter()
+                    .map(|word| doubled_quote(word))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        })
+        .collect()
+}
+
+fn powershell(name: &str, spec: &Spec) -> String {
+    let subcommands: String = spec
+        .subcommands
+        .iter()
+        .map(|path| format!("        {} = $true\n", doubled_quote(path)))
+        .collect();
+
+    format!(
+        "Register-ArgumentCompleter -Native -CommandName {quoted} -ScriptBlock {{
+    param($wordToComplete, $commandAst, $cursorPosition)
+
+    $subcommands = @{{
+{subcommands}    }}
+    $values = @{{
+{values}    }}
+    $options = @{{
+{options}    }}
+
+    $words = @($commandAst.CommandElements | ForEach-Object {{ $_.ToString() }})
+    if ($wordToComplete -ne '') {{
+        $words = @($words | Select-Object -SkipLast 1)
+    }}
+
+    $cmdpath = {quoted}
+    for ($i = 1; $i -lt $words.Count; $i++) {{
+        if ($subcommands.ContainsKey(\"$cmdpath $($words[$i])\")) {{
+            $cmdpath = \"$cmdpath $($words[$i])\"
+        }}
+    }}
+
+    $key = \"$cmdpath $($words[-1])\"
+    $candidates = if ($values.ContainsKey($key)) {{ $values[$key] }} else {{ $options[$cmdpath] }}
+    $candidates | Where-Object {{ $_ -like \"$wordToComplete*\" }} | ForEach-Object {{
+        [System.Management.Automation.CompletionResult]::new($_, $_, 'ParameterValue', $_)
+    }}
+}}
+",
+        quoted = doubled_quote(name),
+        values = powershell_table(&spec.values),
+        options = powershell_table(&spec.options),
+    )
+}
+
+fn elvish_map(entries: &[(String, Vec<String>)]) -> String {
+    if entries.is_empty() {
+        // `[]` would be an empty list; `[&]` is the empty map.
+        return "        &\n".to_string();
+    }
+    entries
+        .iter()
+        .map(|(key, list)| {
+            format!(
+                "        &{}=[{}]\n",
+                doubled_quote(key),
+                words(list, doubled_quote)
+            )
+        })
+        .collect()
+}
+
+fn elvish(name: &str, spec: &Spec) -> String {
+    let subcommands: String = spec
+        .subcommands
+        .iter()
+        .map(|path| format!("        &{}=$true\n", doubled_quote(path)))
+        .collect();
+
+    format!(
+        "set edit:completion:arg-completer[{quoted}] = {{|@words|
+    var subcommands = [
+{subcommands}    ]
+    var values = [
+{values}    ]
+    var options = [
+{options}    ]
+
+    var cmdpath = {quoted}
+    for word $words[1..-1] {{
+        if (has-key $subcommands $cmdpath' '$word) {{
+            set cmdpath = $cmdpath' '$word
+        }}
+    }}
+
+    var key = $cmdpath' '$words[-2]
+    if (has-key $values $key) {{
+        all $values[$key]
+    }} elif (has-key $options $cmdpath) {{
+        all $options[$cmdpath]
+    }}
+}}
+",
+        quoted = doubled_quote(name),
+        values = elvish_map(&spec.values),
+        options = elvish_map(&spec.options),
+    )
+}
@@ -0,0 +1,624 @@
+//! User defaults loaded from `config.toml` in the platform config directory.
+//!
+//! Precedence is: command-line flags, then this file, then built-in defaults.
+//! Only the subset of TOML the file needs is understood: `key = value` pairs
+//! with string, integer or boolean values (or one-line arrays of them),
+//! grouped under optional `[tables]`.
+
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+use anyhow::{Context, Result, anyhow, bail};
+use clap::ValueEnum;
+use reqwest::{
+    Url,
+    header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE, HeaderMap, HeaderName, HeaderValue},
+};
+
+use crate::{
+    ColorMode, OutputFormat, RatingBands, SortKey,
+    locale::Locale,
+    paths,
+    source::SourceKind,
+    theme::{Theme, ThemeName},
+};
+
+const CONFIG_FILE: &str = "config.toml";
+const PRECEDENCE: &str =
+    "command-line flags override config values, which override built-in defaults";
+
+#[derive(Debug, Default)]
+pub struct Config {
+    pub source: Option<SourceKind>,
+    pub format: Option<OutputFormat>,
+    pub color: Option<ColorMode>,
+    pub limit: Option<u32>,
+    pub sort: Option<SortKey>,
+    /// `[rating] low`: ratings below this are shown in red.
+    pub rating_low: Option<u32>,
+    /// `[rating] high`: ratings above this are shown in green.
+    pub rating_high: Option<u32>,
+    /// `[kitsu] token`: an OAuth access token, overridden by `SABIKANI_TOKEN`.
+    pub token: Option<Secret>,
+    /// `[kitsu] base_url`: a Kitsu mirror or proxy's API root, overridden by
+    /// `SABIKANI_BASE_URL`.
+    pub base_url: Option<String>,
+    pub theme: Option<ThemeName>,
+    pub locale: Option<Locale>,
+    /// Whether searches keep adult results without `--include-nsfw`.
+    pub include_nsfw: Option<bool>,
+    /// `[theme]`: per-setting overrides of the chosen theme, checked on load.
+    pub theme_overrides: BTreeMap<String, Value>,
+    /// `[headers]`: extra headers for every Kitsu request, e.g. for an API
+    /// gateway in front of a mirror.
+    pub headers: HeaderMap,
+}
+
+/// A credential that never shows up in `Debug` output.
+#[derive(Clone, PartialEq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Secret(..)")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Bool(bool),
+    Array(Vec<Value>),
+}
+
+/// A parsed file: `table -> key -> value`, with top-level keys under "".
+pub type Tables = BTreeMap<String, BTreeMap<String, Value>>;
+
+pub fn config_path() -> Result<PathBuf> {
+    Ok(paths::config_dir()?.join(CONFIG_FILE))
+}
+
+impl Config {
+    /// Loads the config file. A missing file means "all defaults".
+    pub fn load() -> Result<Config> {
+        let path = config_path()?;
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(err) => {
+                return Err(err).with_context(|| format!("Failed to read {}", path.display()));
+            }
+        };
+
+        let tables = parse(&contents)
+            .with_context(|| format!("Invalid config file {} ({})", path.display(), PRECEDENCE))?;
+        Config::from_tables(&tables)
+            .with_context(|| format!("Invalid config file {} ({})", path.display(), PRECEDENCE))
+    }
+
+    fn from_tables(tables: &Tables) -> Result<Config> {
+        let mut config = Config::default();
+
+        if let Some(name) = tables
+            .keys()
+            .find(|name| !matches!(name.as_str(), "" | "rating" | "kitsu" | "headers" | "theme"))
+        {
+            bail!("unknown table `[{}]`", name);
+        }
+
+        if let Some(rating) = tables.get("rating") {
+            for (key, value) in rating {
+                match key.as_str() {
+                    "low" => config.rating_low = Some(percentage(key, value)?),
+                    "high" => config.rating_high = Some(percentage(key, value)?),
+                    _ => bail!("unknown setting `rating.{}`", key),
+                }
+            }
+            if let (Some(low), Some(high)) = (config.rating_low, config.rating_high)
+                && low > high
+            {
+                bail!("`rating.low` ({}) is above `rating.high` ({})", low, high);
+            }
+        }
+
+        if let Some(kitsu) = tables.get("kitsu") {
+            for (key, value) in kitsu {
+                match (key.as_str(), value) {
+                    ("token", Value::String(token)) if !token.trim().is_empty() => {
+                        config.token = Some(Secret(token.trim().to_string()));
+                    }
+                    ("token", _) => bail!("`kitsu.token` must be a non-empty string"),
+                    ("base_url", Value::String(url)) => {
+                        config.base_url =
+                            Some(base_url(url).context("`kitsu.base_url` is not usable")?);
+                    }
+                    ("base_url", _) => bail!("`kitsu.base_url` must be a string"),
+                    _ => bail!("unknown setting `kitsu.{}`", key),
+                }
+            }
+        }
+
+        if let Some(headers) = tables.get("headers") {
+            for (key, value) in headers {
+                let (name, value) = header(key, value)?;
+                config.headers.insert(name, value);
+            }
+        }
+
+        if let Some(overrides) = tables.get("theme") {
+            Theme::default().customize(overrides)?;
+            config.theme_overrides = overrides.clone();
+        }
+
+        let Some(top) = tables.get("") else {
+            return Ok(config);
+        };
+
+        for (key, value) in top {
+            match key.as_str() {
+                "source" => config.source = Some(enum_value(key, value)?),
+                "format" => config.format = Some(enum_value(key, value)?),
+                "color" => config.color = Some(enum_value(key, value)?),
+                "sort" => config.sort = Some(enum_value(key, value)?),
+                "theme" => config.theme = Some(enum_value(key, value)?),
+                "locale" => config.locale = Some(enum_value(key, value)?),
+                "limit" => config.limit = Some(positive_int(key, value)?),
+                "include_nsfw" => config.include_nsfw = Some(boolean(key, value)?),
+                _ => bail!("unknown setting `{}`", key),
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// The Kitsu access token to send, if any: `SABIKANI_TOKEN` when set,
+    /// then `[kitsu] token`.
+    pub fn token(&self) -> Option<Secret> {
+        std::env::var("SABIKANI_TOKEN")
+            .ok()
+            .map(|token| token.trim().to_string())
+            .filter(|token| !token.is_empty())
+            .map(Secret)
+            .or_else(|| self.token.clone())
+    }
+
+    /// The Kitsu API root to use, if not the default: `SABIKANI_BASE_URL`
+    /// when set, then `[kitsu] base_url`.
+    pub fn base_url(&self) -> Result<Option<String>> {
+        match std::env::var("SABIKANI_BASE_URL") {
+            Ok(url) if !url.trim().is_empty() => base_url(&url)
+                .context("SABIKANI_BASE_URL is not usable")
+                .map(Some),
+            _ => Ok(self.base_url.clone()),
+        }
+    }
+
+    /// The theme to draw with: `flag`, else config `theme`, else the
+    /// default, with the `[theme]` overrides applied.
+    pub fn theme(&self, flag: Option<ThemeName>) -> Result<Theme> {
+        let mut theme = Theme::builtin(flag.or(self.theme).unwrap_or_default());
+        theme.customize(&self.theme_overrides)?;
+        Ok(theme)
+    }
+
+    /// Rating color thresholds, with the built-in default for any unset end.
+    pub fn rating_bands(&self) -> RatingBands {
+        let defaults = RatingBands::default();
+        RatingBands {
+            low: self.rating_low.map_or(defaults.low, |low| low as f32),
+            high: self.rating_high.map_or(defaults.high, |high| high as f32),
+        }
+    }
+}
+
+/// Checks that `text` is an http(s) URL and drops any trailing slash, since
+/// request paths are appended as `{base_url}/anime`.
+fn base_url(text: &str) -> Result<String> {
+    let text = text.trim();
+    let url = Url::parse(text).with_context(|| format!("\"{}\" is not a URL", text))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        bail!("\"{}\" must start with http:// or https://", text);
+    }
+    Ok(text.trim_end_matches('/').to_string())
+}
+
+/// One `[headers]` entry, as a valid header. The JSON-API `Accept` and
+/// `Content-Type` that Kitsu needs, and the `Authorization` that carries
+/// `kitsu.token`, are always sent as they are, so they can't be set here.
+fn header(key: &str, value: &Value) -> Result<(HeaderName, HeaderValue)> {
+    let name = HeaderName::from_bytes(key.as_bytes())
+        .map_err(|_| anyhow!("`headers.{}` is not a valid header name", key))?;
+    if [ACCEPT, CONTENT_TYPE].contains(&name) {
+        bail!(
+            "`headers.{}` can't be changed; Kitsu needs its JSON-API type",
+            key
+        );
+    }
+    if name == AUTHORIZATION {
+        bail!("`headers.{}` can't be set here; use `kitsu.token`", key);
+    }
+    let Value::String(text) = value else {
+        bail!("`headers.{}` must be a string", key);
+    };
+    let value = HeaderValue::from_str(text.trim())
+        .map_err(|_| anyhow!("`headers.{}` contains characters a header can't", key))?;
+    Ok((name, value))
+}
+
+fn enum_value<T: ValueEnum>(key: &str, value: &Value) -> Result<T> {
+    let Value::String(text) = value else {
+        bail!("`{}` must be a string", key);
+    };
+
+    T::from_str(text, true).map_err(|_| {
+        let allowed: Vec<String> = T::value_variants()
+            .iter()
+            .filter_map(|v| v.to_possible_value())
+            .map(|v| v.get_name().to_string())
+            .collect();
+        anyhow!(
+            "`{}` is \"{}\", expected one of: {}",
+            key,
+            text,
+            allowed.join(", ")
+        )
+    })
+}
+
+fn positive_int(key: &str, value: &Value) -> Result<u32> {
+    match value {
+        Value::Integer(n) if *n > 0 => {
+            u32::try_from(*n).with_context(|| format!("`{}` is too large", key))
+        }
+        _ => bail!("`{}` must be a positive integer", key),
+    }
+}
+
+fn boolean(key: &str, value: &Value) -> Result<bool> {
+    match value {
+        Value::Bool(flag) => Ok(*flag),
+        _ => bail!("`{}` must be true or false", key),
+    }
+}
+
+fn percentage(key: &str, value: &Value) -> Result<u32> {
+    match value {
+        Value::Integer(n @ 0..=100) => Ok(*n as u32),
+        _ => bail!("`{}` must be an integer from 0 to 100", key),
+    }
+}
+
+/// Parses the supported TOML subset.
+pub fn parse(contents: &str) -> Result<Tables> {
+    let mut tables = Tables::new();
+    let mut current = String::new();
+
+    for (number, raw) in contents.lines().enumerate() {
+        let line = strip_comment(raw).trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let context = || format!("line {}", number + 1);
+
+        if let Some(name) = line.strip_prefix('[') {
+            let name = name
+                .strip_suffix(']')
+                .ok_or_else(|| anyhow!("unterminated table header"))
+                .with_context(context)?;
+            current = name.trim().to_string();
+            tables.entry(current.clone()).or_default();
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| anyhow!("expected `key = value`"))
+            .with_context(context)?;
+        let key = unquote_key(key.trim()).with_context(context)?;
+        let value = parse_value(value.trim()).with_context(context)?;
+
+        tables
+            .entry(current.clone())
+            .or_default()
+            .insert(key, value);
+    }
+
+    Ok(tables)
+}
+
+/// Drops a trailing `# comment`, ignoring `#` inside quoted strings.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if c == q && !escaped => quote = None,
+            (None, '#') => return &line[..i],
+            _ => {}
+        }
+        escaped = false;
+    }
+    line
+}
+
+fn unquote_key(key: &str) -> Result<String> {
+    if key.is_empty() {
+        bail!("missing key");
+    }
+    match parse_value(key) {
+        Ok(Value::String(s)) if key.starts_with(['"', '\'']) => Ok(s),
+        _ => Ok(key.to_string()),
+    }
+}
+
+fn parse_value(value: &str) -> Result<Value> {
+    if let Some(inner) = value.strip_prefix('[') {
+        let inner = inner
+            .strip_suffix(']')
+            .ok_or_else(|| anyhow!("unterminated array"))?;
+        return split_array(inner)
+            .into_iter()
+            .map(|item| parse_value(item.trim()))
+            .collect::<Result<_>>()
+            .map(Value::Array);
+    }
+    if let Some(inner) = value.strip_prefix('"') {
+        let inner = inner
+            .strip_suffix('"')
+            .ok_or_else(|| anyhow!("unterminated string"))?;
+        return Ok(Value::String(unescape(inner)?));
+    }
+    if let Some(inner) = value.strip_prefix('\'') {
+        let inner = inner
+            .strip_suffix('\'')
+            .ok_or_else(|| anyhow!("unterminated string"))?;
+        return Ok(Value::String(inner.to_string()));
+    }
+
+    match value {
+        "true" => Ok(Value::Bool(true)),
+        "false" => Ok(Value::Bool(false)),
+        _ => value
+            .replace('_', "")
+            .parse()
+            .map(Value::Integer)
+            .map_err(|_| anyhow!("unsupported value `{}` (quote strings)", value)),
+    }
+}
+
+/// The items of an array's contents, split on commas outside quotes. A
+/// trailing comma is allowed.
+fn split_array(inner: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut quote = None;
+    let mut escaped = false;
+    let mut start = 0;
+    for (i, c) in inner.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if c == q && !escaped => quote = None,
+            (None, ',') => {
+                items.push(&inner[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+        escaped = false;
+    }
+    items.push(&inner[start..]);
+    if items.last().is_some_and(|item| item.trim().is_empty()) {
+        items.pop();
+    }
+    items
+}
+
+/// Quotes `text` as a TOML basic string, the inverse of `unescape`.
+pub fn quote(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            _ => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn unescape(text: &str) -> Result<String> {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('"') => out.push('"'),
+            Some('\\') => out.push('\\'),
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            other => bail!("unsupported escape `\\{}`", other.unwrap_or(' ')),
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(contents: &str) -> Result<Config> {
+        Config::from_tables(&parse(contents)?)
+    }
+
+    #[test]
+    fn parses_keys_tables_and_values() {
+        let tables = parse(
+            r#"
+            # Defaults for every search.
+            source = "kitsu"   # trailing comment
+            limit = 1_000
+            include_nsfw = false
+            fields = ["title", 'rating', "tag # not a comment",]
+
+            [rating]
+            low = 55
+            "#,
+        )
+        .unwrap();
+
+        let top = &tables[""];
+        assert_eq!(top["source"], Value::String("kitsu".into()));
+        assert_eq!(top["limit"], Value::Integer(1000));
+        assert_eq!(top["include_nsfw"], Value::Bool(false));
+        assert_eq!(
+            top["fields"],
+            Value::Array(vec![
+                Value::String("title".into()),
+                Value::String("rating".into()),
+                Value::String("tag # not a comment".into()),
+            ])
+        );
+        assert_eq!(tables["rating"]["low"], Value::Integer(55));
+    }
+
+    #[test]
+    fn strings_unescape_and_quote_back() {
+        let tables = parse(r#"query = "say \"hi\"\tthen\\leave""#).unwrap();
+        let Value::String(text) = &tables[""]["query"] else {
+            panic!("expected a string");
+        };
+        assert_eq!(text, "say \"hi\"\tthen\\leave");
+        assert_eq!(quote(text), r#""say \"hi\"\tthen\\leave""#);
+    }
+
+    #[test]
+    fn malformed_lines_name_their_line() {
+        for (contents, expected) in [
+            ("limit = 5\n[rating", "line 2"),
+            ("source", "line 1"),
+            ("source = kitsu", "line 1"),
+            ("source = \"kitsu", "line 1"),
+            ("key = \"\\q\"", "line 1"),
+        ] {
+            let err = parse(contents).unwrap_err();
+            assert_eq!(err.to_string(), expected, "{:?}", contents);
+        }
+    }
+
+    #[test]
+    fn settings_are_checked_on_load() {
+        let loaded = config("sort = \"-rating\"\nlimit = 25\n[rating]\nhigh = 90").unwrap();
+        assert_eq!(loaded.sort, Some(SortKey::RatingAsc));
+        assert_eq!(loaded.limit, Some(25));
+        assert_eq!(loaded.rating_high, Some(90));
+
+        let err = config("format = \"xml\"").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "`format` is \"xml\", expected one of: text, json, csv, table"
+        );
+        assert!(config("limit = 0").is_err());
+        assert!(config("colour = \"never\"").is_err());
+        let err = config("[ratings]\nlow = 40").unwrap_err();
+        assert_eq!(err.to_string(), "unknown table `[ratings]`");
+        assert!(config("[kitsu.extra]").is_err());
+        assert!(config("[rating]\nlow = 80\nhigh = 60").is_err());
+        assert!(config("").unwrap().source.is_none());
+    }
+
+    #[test]
+    fn rating_bands_fill_unset_ends_with_the_defaults() {
+        let bands = config("[rating]\nlow = 40\n").unwrap().rating_bands();
+        assert_eq!(
+            bands,
+            RatingBands {
+                low: 40.0,
+                high: 75.0
+            }
+        );
+        assert_eq!(bands.band(39.9), crate::RatingBand::Low);
+        assert_eq!(bands.band(40.0), crate::RatingBand::Mid);
+
+        assert_eq!(config("").unwrap().rating_bands(), RatingBands::default());
+        assert!(config("[rating]\nlow = 80\nhigh = 70\n").is_err());
+        assert!(config("[rating]\nhigh = 101\n").is_err());
+    }
+
+    #[test]
+    fn base_url_must_be_http_and_loses_its_trailing_slash() {
+        let loaded = config("[kitsu]\nbase_url = \"http://localhost:8080/api/edge/\"\n").unwrap();
+        assert_eq!(
+            loaded.base_url.as_deref(),
+            Some("http://localhost:8080/api/edge")
+        );
+        assert_eq!(
+            base_url(" https://kitsu.example/api ").unwrap(),
+            "https://kitsu.example/api"
+        );
+
+        for contents in [
+            "[kitsu]\nbase_url = \"ftp://kitsu.example\"\n",
+            "[kitsu]\nbase_url = \"kitsu.example/api\"\n",
+            "[kitsu]\nbase_url = 8080\n",
+        ] {
+            assert!(config(contents).is_err(), "{:?}", contents);
+        }
+    }
+
+    #[test]
+    fn headers_are_validated_at_load() {
+        let loaded =
+            config("[headers]\nX-Api-Gateway = \"abc123\"\nUser-Agent = \"me/1.0\"\n").unwrap();
+        assert_eq!(loaded.headers["x-api-gateway"], "abc123");
+        assert_eq!(loaded.headers["user-agent"], "me/1.0");
+
+        for (contents, complaint) in [
+            (
+                "[headers]\nAccept = \"text/html\"\n",
+                "Kitsu needs its JSON-API type",
+            ),
+            (
+                "[headers]\ncontent-type = \"x\"\n",
+                "Kitsu needs its JSON-API type",
+            ),
+            (
+                "[headers]\nAuthorization = \"Bearer x\"\n",
+                "use `kitsu.token`",
+            ),
+            (
+                "[headers]\n\"bad name\" = \"x\"\n",
+                "not a valid header name",
+            ),
+            ("[headers]\nX-Count = 3\n", "must be a string"),
+            (
+                "[headers]\nX-Line = \"a\\nb\"\n",
+                "characters a header can't",
+            ),
+        ] {
+            let err = format!("{:#}", config(contents).unwrap_err());
+            assert!(err.contains(complaint), "{:?}: {}", contents, err);
+        }
+    }
+}
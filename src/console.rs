@@ -0,0 +1,32 @@
+//! Console setup that only matters on Windows, where `cmd.exe` and older
+//! PowerShell windows print ANSI escape codes as text unless asked not to.
+
+/// Turns on ANSI escape processing for stdout, and says whether the console
+/// now understands escape codes. Legacy consoles (conhost before Windows 10)
+/// can't, and neither can a console whose mode can't be changed. Always true
+/// elsewhere.
+pub fn enable_ansi() -> bool {
+    #[cfg(windows)]
+    {
+        // Also true when TERM says so, for terminals like Git Bash's mintty
+        // that handle escape codes themselves.
+        crossterm::ansi_support::supports_ansi()
+    }
+    #[cfg(not(windows))]
+    {
+        true
+    }
+}
+
+/// Whether we are running in Windows Terminal, which draws Unicode and
+/// colors properly, as opposed to a conhost window whose default font may
+/// lack the stars and spinner glyphs.
+#[cfg(windows)]
+pub fn windows_terminal() -> bool {
+    std::env::var_os("WT_SESSION").is_some()
+}
+
+/// Why the TUI won't start in this console.
+pub const LEGACY_CONSOLE: &str = "This console doesn't understand ANSI escape codes, which the TUI \
+needs. On Windows, use Windows Terminal or a Windows 10+ console (legacy conhost isn't \
+supported); the other commands still work with --color never";
@@ -0,0 +1,53 @@
+//! Minimal RFC 4180 CSV writing for `--format csv`.
+
+/// Appends one record to `out`, quoting fields that need it.
+pub fn write_record<'a>(out: &mut String, fields: impl IntoIterator<Item = &'a str>) {
+    for (i, field) in fields.into_iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        push_field(out, field);
+    }
+    out.push_str("\r\n");
+}
+
+/// Fields containing a delimiter, quote or line break are wrapped in quotes,
+/// with embedded quotes doubled.
+fn push_field(out: &mut String, field: &str) {
+    if field.contains([',', '"', '\n', '\r']) {
+        out.push('"');
+        out.push_str(&field.replace('"', "\"\""));
+        out.push('"');
+    } else {
+        out.push_str(field);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(fields: &[&str]) -> String {
+        let mut out = String::new();
+        write_record(&mut out, fields.iter().copied());
+        out
+    }
+
+    #[test]
+    fn plain_fields_are_left_alone() {
+        assert_eq!(
+            record(&["1", "Cowboy Bebop", "82.1"]),
+            "1,Cowboy Bebop,82.1\r\n"
+        );
+        assert_eq!(record(&["", ""]), ",\r\n");
+    }
+
+    #[test]
+    fn commas_quotes_and_line_breaks_are_quoted() {
+        assert_eq!(
+            record(&["Crime, Space", "the \"Bebop\"", "two\nlines", "cr\r"]),
+            "\"Crime, Space\",\"the \"\"Bebop\"\"\",\"two\nlines\",\"cr\r\"\r\n"
+        );
+        assert_eq!(record(&["\""]), "\"\"\"\"\r\n");
+    }
+}
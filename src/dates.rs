@@ -0,0 +1,229 @@
+//! Air dates as Kitsu sends them (`YYYY-MM-DD`), and the "aired 11 years
+//! ago" phrasing shown by `--relative-dates`.
+
+use crate::{cache::now, locale};
+
+/// Days since 1970-01-01 for a `YYYY-MM-DD` date, or `None` if it isn't one.
+pub fn parse(date: &str) -> Option<i64> {
+    let mut parts = date.trim().splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: i64 = parts.next()?.parse().ok()?;
+    let day: i64 = parts.next()?.parse().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    Some(days_from_civil(year, month, day))
+}
+
+/// Parses a `--since`/`--until` value, which must be a real `YYYY-MM-DD`
+/// date.
+pub fn parse_flag(text: &str) -> Result<i64, String> {
+    let invalid = || {
+        format!(
+            "\"{}\" is not a date; use YYYY-MM-DD, e.g. 2013-04-07",
+            text
+        )
+    };
+    let parts: Vec<&str> = text.trim().split('-').collect();
+    let [year, month, day] = parts[..] else {
+        return Err(invalid());
+    };
+    if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+        return Err(invalid());
+    }
+    let (Ok(year), Ok(month), Ok(day)) = (year.parse(), month.parse(), day.parse()) else {
+        return Err(invalid());
+    };
+    if !(1..=12).contains(&month) || !(1..=days_in_month(year, month)).contains(&day) {
+        return Err(invalid());
+    }
+    Ok(days_from_civil(year, month, day))
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// An inclusive range of start dates from `--since` and `--until`, in days
+/// since 1970-01-01; either end may be open.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Range {
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+}
+
+impl Range {
+    pub fn is_open(&self) -> bool {
+        self.since.is_none() && self.until.is_none()
+    }
+
+    pub fn contains(&self, day: i64) -> bool {
+        self.since.is_none_or(|since| day >= since) && self.until.is_none_or(|until| day <= until)
+    }
+}
+
+/// Today, in days since 1970-01-01 (UTC).
+pub fn today() -> i64 {
+    (now() / 86_400) as i64
+}
+
+/// Howard Hinnant's `days_from_civil`: the proleptic Gregorian date as days
+/// relative to the Unix epoch.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_from_march = (month + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// A span of days in the largest whole unit, e.g. "3 months" or "1 year".
+fn span(days: i64) -> String {
+    let (count, unit) = match days {
+        0..30 => (days, "day"),
+        30..365 => (days / 30, "month"),
+        _ => (days / 365, "year"),
+    };
+    if count == 1 {
+        format!("1 {}", unit)
+    } else {
+        format!("{} {}s", count, unit)
+    }
+}
+
+/// When a show started airing relative to `today`: "aired 11 years ago",
+/// "airs in 3 months" or "airs today".
+pub fn relative_start(start: i64, today: i64) -> String {
+    match start - today {
+        0 => "airs today".to_string(),
+        ahead if ahead > 0 => format!("airs in {}", span(ahead)),
+        behind => format!("aired {} ago", span(-behind)),
+    }
+}
+
+/// An airing period in the `--locale` date format: "2013-04-07 to
+/// 2013-09-28", or "2013-04-07 to present" when there is no end date yet.
+pub fn airing_period(start: &str, end: Option<&str>) -> String {
+    match end {
+        Some(end) => format!("{} to {}", locale::date(start), locale::date(end)),
+        None => format!("{} to present", locale::date(start)),
+    }
+}
+
+/// The `--relative-dates` form of an airing period, keeping the absolute
+/// dates: "2013-04-07 to 2013-09-28 (aired 11 years ago)". Dates that don't
+/// parse are shown as-is.
+pub fn describe_airing(start: &str, end: Option<&str>, today: i64) -> String {
+    let period = airing_period(start, end);
+    match parse(start) {
+        Some(days) => format!("{} ({})", period, relative_start(days, today)),
+        None => period,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2024-06-15 stands in for today, so the phrasing doesn't drift.
+    fn june_15() -> i64 {
+        parse("2024-06-15").unwrap()
+    }
+
+    #[test]
+    fn parse_counts_days_from_the_epoch() {
+        assert_eq!(parse("1970-01-01"), Some(0));
+        assert_eq!(parse("2000-03-01"), Some(11_017));
+        assert_eq!(parse("1969-12-31"), Some(-1));
+        assert_eq!(parse("2013-13-01"), None);
+        assert_eq!(parse("soon"), None);
+    }
+
+    #[test]
+    fn past_starts_read_as_aired_ago() {
+        assert_eq!(
+            relative_start(parse("2013-04-07").unwrap(), june_15()),
+            "aired 11 years ago"
+        );
+        assert_eq!(
+            relative_start(parse("2024-05-15").unwrap(), june_15()),
+            "aired 1 month ago"
+        );
+        assert_eq!(relative_start(june_15() - 1, june_15()), "aired 1 day ago");
+    }
+
+    #[test]
+    fn future_starts_read_as_airs_in() {
+        assert_eq!(relative_start(june_15(), june_15()), "airs today");
+        assert_eq!(relative_start(june_15() + 12, june_15()), "airs in 12 days");
+        assert_eq!(
+            relative_start(parse("2024-09-20").unwrap(), june_15()),
+            "airs in 3 months"
+        );
+    }
+
+    #[test]
+    fn ongoing_and_unparseable_airings() {
+        assert_eq!(
+            describe_airing("2013-04-07", Some("2013-09-28"), june_15()),
+            "2013-04-07 to 2013-09-28 (aired 11 years ago)"
+        );
+        assert_eq!(
+            describe_airing("2024-04-06", None, june_15()),
+            "2024-04-06 to present (aired 2 months ago)"
+        );
+        assert_eq!(
+            describe_airing("Spring 2025", None, june_15()),
+            "Spring 2025 to present"
+        );
+    }
+
+    #[test]
+    fn flags_must_be_real_dates() {
+        assert_eq!(
+            parse_flag("2013-04-07"),
+            parse("2013-04-07").ok_or(String::new())
+        );
+        assert_eq!(parse_flag(" 2024-02-29 "), Ok(parse("2024-02-29").unwrap()));
+        for text in [
+            "2023-02-29",
+            "1900-02-29",
+            "2013-04-31",
+            "2013-4-7",
+            "13-04-07",
+            "2013/04/07",
+            "2013-04",
+            "",
+        ] {
+            let err = parse_flag(text).unwrap_err();
+            assert!(err.contains("use YYYY-MM-DD"), "{}: {}", text, err);
+        }
+    }
+
+    #[test]
+    fn ranges_include_both_ends() {
+        let since = parse("2010-01-01").unwrap();
+        let until = parse("2010-12-31").unwrap();
+        let range = Range {
+            since: Some(since),
+            until: Some(until),
+        };
+        assert!(range.contains(since) && range.contains(until));
+        assert!(!range.contains(since - 1) && !range.contains(until + 1));
+
+        let from = Range {
+            since: Some(since),
+            until: None,
+        };
+        assert!(from.contains(i64::MAX) && !from.contains(since - 1));
+        assert!(Range::default().is_open() && Range::default().contains(0));
+        assert!(!from.is_open());
+    }
+}
@@ -0,0 +1,269 @@
+//! Extra per-result lookups (`--with-genres`, `--with-streaming`), run as one
+//! concurrency-limited pipeline and merged back onto the results they were
+//! made for.
+
+use anyhow::Result;
+use futures_util::{StreamExt, stream};
+
+use crate::{
+    AnimeData, CATEGORIES_TTL, Media, cache::CategoriesCache, fetch_categories,
+    fetch_streaming_links, http::HttpClient, summary::Summary,
+};
+
+/// Which lookups to run for each result.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Enrichments {
+    pub genres: bool,
+    pub streaming: bool,
+}
+
+impl Enrichments {
+    pub fn any(&self) -> bool {
+        self.genres || self.streaming
+    }
+}
+
+/// One request to make, for the result at this index of the list.
+enum Lookup {
+    Genres(usize, String),
+    Streaming(usize, String),
+}
+
+/// A finished `Lookup`, for the result at this index.
+enum Found {
+    Genres(usize, Vec<String>),
+    Streaming(usize, Vec<String>),
+}
+
+/// Whether `anime` has a Kitsu id, which is all the lookups can look up.
+fn on_kitsu(anime: &AnimeData) -> bool {
+    anime
+        .source
+        .as_deref()
+        .is_none_or(|source| source == "kitsu")
+}
+
+/// Where `anime`'s genres are cached. Kitsu ids keep the plain
+/// `anime/{id}` the TUI uses too; ids from other sources are told apart by
+/// their source, since the same number names different anime there.
+fn cache_key(media: Media, anime: &AnimeData) -> String {
+    match anime.source.as_deref() {
+        Some(source) if !on_kitsu(anime) => format!("{}/{}/{}", source, media.path(), anime.id),
+        _ => format!("{}/{}", media.path(), anime.id),
+    }
+}
+
+/// Runs the requested lookups for every entry of `anime_list`, at most
+/// `concurrency` requests at a time across all kinds. That limit is the only
+/// throttle: there is no overall rate limit, and a rate-limited request is
+/// left to the client's retries. Lookups finish in any order, but each
+/// result is written back onto the entry it was made for, so the list keeps
+/// its order. A failed lookup doesn't stop the rest; they are tallied in the
+/// returned summary, with the details logged for `-v`.
+pub async fn enrich(
+    client: &HttpClient,
+    media: Media,
+    anime_list: &mut [AnimeData],
+    wanted: Enrichments,
+    concurrency: usize,
+) -> Result<Summary> {
+    let mut cache = CategoriesCache::load()?;
+
+    let mut lookups = Vec::new();
+    for (i, anime) in anime_list.iter().enumerate() {
+        // Both lookups are made on Kitsu, so ids from elsewhere can't be.
+        // Results that already carry genres (from AniList) keep them.
+        if !on_kitsu(anime) {
+            continue;
+        }
+        if wanted.genres
+            && anime.categories.is_empty()
+            && cache
+                .get(&cache_key(media, anime), CATEGORIES_TTL)
+                .is_none()
+        {
+            lookups.push(Lookup::Genres(i, anime.id.clone()));
+        }
+        if wanted.streaming && media == Media::Anime {
+            lookups.push(Lookup::Streaming(i, anime.id.clone()));
+        }
+    }
+
+    let results: Vec<Result<Found>> = stream::iter(lookups)
+        .map(|lookup| async move {
+            match lookup {
+                Lookup::Genres(i, id) => fetch_categories(client, media, &id)
+                    .await
+                    .map(|genres| Found::Genres(i, genres))
+                    .map_err(|err| err.context(format!("couldn't fetch genres for {}", id))),
+                Lookup::Streaming(i, id) => fetch_streaming_links(client, &id)
+                    .await
+                    .map(|links| {
+                        let mut sites: Vec<String> = links
+                            .data
+                            .iter()
+                            .map(|link| links.streamer_name(link).to_string())
+                            .collect();
+                        sites.dedup();
+                        Found::Streaming(i, sites)
+                    })
+                    .map_err(|err| {
+                        err.context(format!("couldn't fetch streaming links for {}", id))
+                    }),
+            }
+        })
+        .buffer_unordered(concurrency)
+        .collect()
+        .await;
+
+    let mut summary = Summary::default();
+    for result in results {
+        match result {
+            Ok(Found::Genres(i, genres)) => cache.insert(cache_key(media, &anime_list[i]), genres),
+            Ok(Found::Streaming(i, sites)) => anime_list[i].streamers = sites,
+            Err(err) => {
+                log::info!("{:#}", err);
+                summary.failed(err);
+                continue;
+            }
+        }
+        summary.succeeded();
+    }
+
+    if wanted.genres {
+        cache.save()?;
+        for anime in anime_list
+            .iter_mut()
+            .filter(|anime| anime.categories.is_empty())
+        {
+            if let Some(categories) = cache.get(&cache_key(media, anime), CATEGORIES_TTL) {
+                anime.categories = categories.clone();
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::kitsu_stub::{KitsuStub, Reply};
+
+    fn streaming_link(site: &str) -> String {
+        format!(
+            r#"{{
+                "data": [{{"id": "1", "attributes": {{"url": "https://example.com"}},
+                           "relationships": {{"streamer": {{"data": {{"id": "9"}}}}}}}}],
+                "included": [{{"id": "9", "attributes": {{"siteName": "{}"}}}}]
+            }}"#,
+            site
+        )
+    }
+
+    #[tokio::test]
+    async fn results_keep_their_order_whatever_finishes_first() {
+        // The first result's lookup answers last, and the last one's first.
+        let stub = KitsuStub::routes(vec![
+            (
+                "anime/1/streaming-links",
+                Reply::json(200, &streaming_link("Crunchyroll")).after(Duration::from_millis(300)),
+            ),
+            (
+                "anime/2/streaming-links",
+                Reply::json(200, &streaming_link("Hulu")).after(Duration::from_millis(100)),
+            ),
+            (
+                "anime/3/streaming-links",
+                Reply::json(200, &streaming_link("Netflix")),
+            ),
+        ])
+        .await;
+        let client = HttpClient::new(Duration::from_secs(5), 0)
+            .unwrap()
+            .with_kitsu_api(Some(stub.base_url.clone()));
+
+        let mut anime_list: Vec<AnimeData> = ["1", "2", "3"]
+            .into_iter()
+            .map(|id| AnimeData {
+                id: id.to_string(),
+                attributes: Default::default(),
+                categories: Vec::new(),
+                streamers: Vec::new(),
+                source: None,
+            })
+            .collect();
+        let wanted = Enrichments {
+            genres: false,
+            streaming: true,
+        };
+        let summary = enrich(&client, Media::Anime, &mut anime_list, wanted, 3)
+            .await
+            .unwrap();
+
+        let found: Vec<(&str, &[String])> = anime_list
+            .iter()
+            .map(|anime| (anime.id.as_str(), &anime.streamers[..]))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("1", &["Crunchyroll".to_string()][..]),
+                ("2", &["Hulu".to_string()][..]),
+                ("3", &["Netflix".to_string()][..]),
+            ]
+        );
+        assert_eq!(summary.line(), "3 succeeded, 0 failed ()");
+
+        // One lookup each, none retried or repeated.
+        assert_eq!(stub.requests().len(), 3);
+    }
+
+    fn from(source: Option<&str>, id: &str) -> AnimeData {
+        AnimeData {
+            id: id.to_string(),
+            attributes: Default::default(),
+            categories: Vec::new(),
+            streamers: Vec::new(),
+            source: source.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn cache_keys_tell_sources_apart() {
+        let kitsu = cache_key(Media::Anime, &from(Some("kitsu"), "1"));
+        assert_eq!(kitsu, "anime/1");
+        assert_eq!(cache_key(Media::Anime, &from(None, "1")), kitsu);
+        assert_eq!(
+            cache_key(Media::Anime, &from(Some("anilist"), "1")),
+            "anilist/anime/1"
+        );
+    }
+
+    #[tokio::test]
+    async fn the_same_id_from_anilist_is_left_alone() {
+        let stub = KitsuStub::start(vec![(
+            "anime/1/streaming-links",
+            streaming_link("Crunchyroll"),
+        )])
+        .await;
+        let client = HttpClient::new(Duration::from_secs(5), 0)
+            .unwrap()
+            .with_kitsu_api(Some(stub.base_url.clone()));
+
+        let mut anime_list = vec![from(Some("anilist"), "1"), from(Some("kitsu"), "1")];
+        let wanted = Enrichments {
+            genres: false,
+            streaming: true,
+        };
+        enrich(&client, Media::Anime, &mut anime_list, wanted, 2)
+            .await
+            .unwrap();
+
+        assert!(anime_list[0].streamers.is_empty());
+        assert_eq!(anime_list[1].streamers, ["Crunchyroll"]);
+        assert_eq!(stub.requests().len(), 1);
+    }
+}
@@ -0,0 +1,175 @@
+//! The non-ASCII symbols the output draws with, kept in one place so that
+//! `--ascii` (or a terminal that looks like it can't show them) can swap in
+//! plain ASCII everywhere at once.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+pub struct Glyphs {
+    pub star_full: &'static str,
+    pub star_half: &'static str,
+    pub star_empty: &'static str,
+    /// Ends text that was cut short.
+    pub ellipsis: &'static str,
+    /// Joins the two halves of the pagination footer.
+    pub dash: &'static str,
+    /// Marks the selected row in the TUI's results list.
+    pub highlight: &'static str,
+    /// The up/down keys in the TUI's key hints.
+    pub arrows: &'static str,
+    pub spinner: &'static [char],
+}
+
+pub const UNICODE: Glyphs = Glyphs {
+    star_full: "★",
+    star_half: "½",
+    star_empty: "☆",
+    ellipsis: "…",
+    dash: "—",
+    highlight: ">> ",
+    arrows: "↑↓",
+    spinner: &['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'],
+};
+
+pub const ASCII: Glyphs = Glyphs {
+    star_full: "*",
+    star_half: "+",
+    star_empty: ".",
+    ellipsis: "...",
+    dash: "-",
+    highlight: "> ",
+    arrows: "Up/Down",
+    spinner: &['|', '/', '-', '\\'],
+};
+
+/// Set once at startup from `--ascii` or `ascii_preferred`.
+static ASCII_ONLY: AtomicBool = AtomicBool::new(false);
+
+pub fn use_ascii() {
+    ASCII_ONLY.store(true, Ordering::Relaxed);
+}
+
+pub fn ascii_only() -> bool {
+    ASCII_ONLY.load(Ordering::Relaxed)
+}
+
+pub fn current() -> &'static Glyphs {
+    if ascii_only() { &ASCII } else { &UNICODE }
+}
+
+/// Whether the environment suggests Unicode won't render: a `dumb` terminal,
+/// a Windows console outside Windows Terminal, or a locale that is set but
+/// isn't UTF-8 (such as `C` or `POSIX`). An unset locale says nothing either
+/// way, so it keeps the Unicode glyphs.
+pub fn ascii_preferred() -> bool {
+    prefers_ascii(|name| std::env::var(name).ok())
+}
+
+/// `ascii_preferred`, reading the environment through `env`.
+fn prefers_ascii(env: impl Fn(&str) -> Option<String>) -> bool {
+    let var = |name: &str| env(name).filter(|value| !value.is_empty());
+    if var("TERM").is_some_and(|term| term == "dumb") {
+        return true;
+    }
+    // Terminals that set TERM on Windows (mintty, ...) manage their own fonts.
+    #[cfg(windows)]
+    if var("TERM").is_none() && !crate::console::windows_terminal() {
+        return true;
+    }
+    ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .find_map(|name| var(name))
+        .is_some_and(|locale| {
+            let locale = locale.to_ascii_lowercase();
+            !locale.contains("utf-8") && !locale.contains("utf8")
+        })
+}
+
+/// The ASCII stand-in for a box-drawing character, for redrawing TUI borders
+/// (which the TUI library only draws with box-drawing characters).
+pub fn ascii_line(symbol: &str) -> Option<&'static str> {
+    match symbol {
+        "─" | "━" | "═" => Some("-"),
+        "│" | "┃" | "║" => Some("|"),
+        "┌" | "┐" | "└" | "┘" | "╭" | "╮" | "╰" | "╯" | "┏" | "┓" | "┗" | "┛" | "╔" | "╗" | "╚"
+        | "╝" | "├" | "┤" | "┬" | "┴" | "┼" => Some("+"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prefers_ascii_with(vars: &[(&str, &str)]) -> bool {
+        prefers_ascii(|name| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| value.to_string())
+        })
+    }
+
+    #[test]
+    fn ascii_glyphs_are_ascii() {
+        let Glyphs {
+            star_full,
+            star_half,
+            star_empty,
+            ellipsis,
+            dash,
+            highlight,
+            arrows,
+            spinner,
+        } = ASCII;
+        for glyph in [
+            star_full, star_half, star_empty, ellipsis, dash, highlight, arrows,
+        ] {
+            assert!(glyph.is_ascii(), "{:?}", glyph);
+        }
+        assert!(spinner.iter().all(char::is_ascii));
+    }
+
+    #[test]
+    fn dumb_terminals_and_non_utf8_locales_prefer_ascii() {
+        assert!(prefers_ascii_with(&[
+            ("TERM", "dumb"),
+            ("LANG", "en_US.UTF-8")
+        ]));
+        assert!(prefers_ascii_with(&[("TERM", "xterm"), ("LANG", "C")]));
+        assert!(prefers_ascii_with(&[
+            ("TERM", "xterm"),
+            ("LC_ALL", "POSIX")
+        ]));
+        // LC_ALL overrides LANG, either way.
+        assert!(prefers_ascii_with(&[
+            ("TERM", "xterm"),
+            ("LC_ALL", "C"),
+            ("LANG", "en_US.UTF-8")
+        ]));
+        assert!(!prefers_ascii_with(&[
+            ("TERM", "xterm"),
+            ("LC_ALL", "de_DE.utf8"),
+            ("LANG", "C")
+        ]));
+    }
+
+    #[test]
+    fn utf8_and_unset_locales_keep_unicode() {
+        assert!(!prefers_ascii_with(&[
+            ("TERM", "xterm-256color"),
+            ("LANG", "en_GB.UTF-8")
+        ]));
+        assert!(!prefers_ascii_with(&[("TERM", "xterm"), ("LANG", "")]));
+        #[cfg(not(windows))]
+        assert!(!prefers_ascii_with(&[]));
+    }
+
+    #[test]
+    fn box_drawing_maps_to_ascii() {
+        assert_eq!(ascii_line("─"), Some("-"));
+        assert_eq!(ascii_line("║"), Some("|"));
+        assert_eq!(ascii_line("╭"), Some("+"));
+        assert_eq!(ascii_line("┼"), Some("+"));
+        assert_eq!(ascii_line("a"), None);
+        assert_eq!(ascii_line(" "), None);
+    }
+}
@@ -0,0 +1,286 @@
+//! Recently run searches, and the listing the last one printed, persisted
+//! as JSON in the data directory.
+
+use std::{fs, path::PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{AnimeData, cache::now, paths};
+
+const HISTORY_FILE: &str = "history.json";
+
+/// Older searches are forgotten once the history holds this many.
+const MAX_HISTORY: usize = 100;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub query: String,
+    /// Seconds since the Unix epoch when the search ran.
+    pub searched_at: u64,
+    pub results: usize,
+}
+
+#[derive(Debug)]
+pub struct History {
+    path: PathBuf,
+    /// Oldest first.
+    pub entries: Vec<HistoryEntry>,
+    /// The results the last search listed, in the order shown, for a bare
+    /// `search --pick` to pick from.
+    pub listing: Vec<AnimeData>,
+}
+
+/// The history file as written.
+#[derive(Serialize)]
+struct HistoryFileRef<'a> {
+    entries: &'a [HistoryEntry],
+    listing: &'a [AnimeData],
+}
+
+/// The history file as read. Files written before listings were kept hold
+/// only the array of entries.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum HistoryFile {
+    Full {
+        entries: Vec<HistoryEntry>,
+        #[serde(default)]
+        listing: Vec<AnimeData>,
+    },
+    Entries(Vec<HistoryEntry>),
+}
+
+impl History {
+    /// Loads the history. A missing or unreadable file is an empty history;
+    /// losing it is no great harm.
+    pub fn load() -> Result<History> {
+        Ok(History::open(paths::data_dir()?.join(HISTORY_FILE)))
+    }
+
+    fn open(path: PathBuf) -> History {
+        let (entries, listing) = match fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+        {
+            Some(HistoryFile::Full { entries, listing }) => (entries, listing),
+            Some(HistoryFile::Entries(entries)) => (entries, Vec::new()),
+            None => Default::default(),
+        };
+
+        History {
+            path,
+            entries,
+            listing,
+        }
+    }
+
+    /// Records a search, re-reading the file first so searches run from
+    /// other terminals in the meantime aren't overwritten.
+    pub fn append(query: &str, results: usize) -> Result<()> {
+        let mut history = History::load()?;
+        history.record(HistoryEntry {
+            query: query.to_string(),
+            searched_at: now(),
+            results,
+        });
+        history.save()
+    }
+
+    /// Keeps `listing` as what the last search printed, re-reading the file
+    /// first like `append`.
+    pub fn remember_listing(listing: &[AnimeData]) -> Result<()> {
+        let mut history = History::load()?;
+        history.listing = listing.to_vec();
+        history.save()
+    }
+
+    /// Adds `entry` as the newest search, forgetting the oldest beyond
+    /// `MAX_HISTORY`. Running the newest search again refreshes it rather
+    /// than recording it twice.
+    fn record(&mut self, entry: HistoryEntry) {
+        if self
+            .entries
+            .last()
+            .is_some_and(|newest| newest.query == entry.query)
+        {
+            self.entries.pop();
+        }
+        self.entries.push(entry);
+
+        let excess = self.entries.len().saturating_sub(MAX_HISTORY);
+        self.entries.drain(..excess);
+    }
+
+    /// The `n`th most recent search, counting from 1.
+    pub fn recent(&self, n: usize) -> Option<&HistoryEntry> {
+        self.entries.iter().rev().nth(n.checked_sub(1)?)
+    }
+
+    /// Writes to a temporary file and renames it into place, so a reader
+    /// never sees a half-written history.
+    fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+
+        let temp = self
+            .path
+            .with_extension(format!("json.{}.tmp", std::process::id()));
+        let contents = serde_json::to_string_pretty(&HistoryFileRef {
+            entries: &self.entries,
+            listing: &self.listing,
+        })?;
+        fs::write(&temp, contents)
+            .with_context(|| format!("Failed to write history to {}", temp.display()))?;
+        fs::rename(&temp, &self.path)
+            .with_context(|| format!("Failed to write history to {}", self.path.display()))
+    }
+}
+
+/// Describes how long ago `timestamp` was, e.g. "5m ago".
+pub fn format_age(timestamp: u64) -> String {
+    let age = now().saturating_sub(timestamp);
+    match age {
+        0..60 => "just now".to_string(),
+        60..3600 => format!("{}m ago", age / 60),
+        3600..86400 => format!("{}h ago", age / 3600),
+        _ => format!("{}d ago", age / 86400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history() -> History {
+        History {
+            path: PathBuf::from("unused.json"),
+            entries: Vec::new(),
+            listing: Vec::new(),
+        }
+    }
+
+    fn search(query: &str) -> HistoryEntry {
+        HistoryEntry {
+            query: query.to_string(),
+            searched_at: 0,
+            results: 1,
+        }
+    }
+
+    fn queries(history: &History) -> Vec<&str> {
+        history
+            .entries
+            .iter()
+            .map(|entry| entry.query.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn record_appends_newest_last() {
+        let mut history = history();
+        history.record(search("naruto"));
+        history.record(search("bleach"));
+        assert_eq!(queries(&history), ["naruto", "bleach"]);
+    }
+
+    #[test]
+    fn repeating_the_newest_search_refreshes_it() {
+        let mut history = history();
+        history.record(search("naruto"));
+        history.record(search("bleach"));
+        history.record(HistoryEntry {
+            results: 7,
+            ..search("bleach")
+        });
+        assert_eq!(queries(&history), ["naruto", "bleach"]);
+        assert_eq!(history.entries[1].results, 7);
+
+        // An older search run again is recorded as new.
+        history.record(search("naruto"));
+        assert_eq!(queries(&history), ["naruto", "bleach", "naruto"]);
+    }
+
+    #[test]
+    fn oldest_searches_are_forgotten_past_the_cap() {
+        let mut history = history();
+        for n in 0..MAX_HISTORY + 5 {
+            history.record(search(&n.to_string()));
+        }
+        assert_eq!(history.entries.len(), MAX_HISTORY);
+        assert_eq!(history.entries[0].query, "5");
+        assert_eq!(
+            history.entries.last().unwrap().query,
+            (MAX_HISTORY + 4).to_string()
+        );
+    }
+
+    #[test]
+    fn recent_counts_back_from_one() {
+        let mut history = history();
+        for query in ["naruto", "bleach", "one piece"] {
+            history.record(search(query));
+        }
+        let recalled = |n| history.recent(n).map(|entry| entry.query.as_str());
+        assert_eq!(recalled(1), Some("one piece"));
+        assert_eq!(recalled(3), Some("naruto"));
+        assert_eq!(recalled(4), None);
+        assert_eq!(recalled(0), None);
+    }
+
+    /// A history file of its own under the temp directory, removed first.
+    fn scratch(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("sabikani-history-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir.join(HISTORY_FILE)
+    }
+
+    #[test]
+    fn listing_survives_a_save_in_order() {
+        let path = scratch("listing");
+        let mut history = History::open(path.clone());
+        history.record(search("bebop"));
+        history.listing = ["1", "4231"]
+            .into_iter()
+            .map(|id| AnimeData {
+                id: id.to_string(),
+                attributes: crate::AnimeAttributes {
+                    canonical_title: format!("Anime {}", id),
+                    ..Default::default()
+                },
+                categories: Vec::new(),
+                streamers: Vec::new(),
+                source: None,
+            })
+            .collect();
+        history.save().unwrap();
+
+        let loaded = History::open(path);
+        assert_eq!(queries(&loaded), ["bebop"]);
+        let ids: Vec<_> = loaded
+            .listing
+            .iter()
+            .map(|anime| anime.id.as_str())
+            .collect();
+        assert_eq!(ids, ["1", "4231"]);
+        assert_eq!(loaded.listing[1].attributes.canonical_title, "Anime 4231");
+    }
+
+    #[test]
+    fn files_of_bare_entries_still_load() {
+        let path = scratch("legacy");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(
+            &path,
+            r#"[{"query": "naruto", "searched_at": 0, "results": 3}]"#,
+        )
+        .unwrap();
+
+        let history = History::open(path);
+        assert_eq!(queries(&history), ["naruto"]);
+        assert!(history.listing.is_empty());
+    }
+}
@@ -0,0 +1,232 @@
+//! Kitsu request URLs, built in one place so that every parameter is
+//! percent-encoded the same way, whatever the request.
+
+use anyhow::{Context, Result, bail};
+use reqwest::Url;
+
+use crate::{AgeRating, Page, Season, SortKey, Subtype};
+
+/// A Kitsu API URL under construction: a path under the API root plus its
+/// query parameters, added in call order. Filters given `None` are left out.
+#[derive(Debug, Clone)]
+pub struct KitsuQuery {
+    url: Url,
+}
+
+impl KitsuQuery {
+    /// A request for `path` (such as `anime` or `anime/1/episodes`) under the
+    /// API root `base_url`.
+    pub fn new(base_url: &str, path: &str) -> Result<KitsuQuery> {
+        let url = Url::parse(&format!("{}/{}", base_url, path))
+            .with_context(|| format!("Invalid Kitsu API URL {}", base_url))?;
+        Ok(KitsuQuery { url })
+    }
+
+    /// A request for one item of `collection` (such as `anime`), by `id`.
+    /// The id is kept to a single encoded path segment, so one like
+    /// `1?filter[x]=y` or `1/../users` can't add a query or climb the path.
+    pub fn item(base_url: &str, collection: &str, id: &str) -> Result<KitsuQuery> {
+        // `.` and `..` would be dropped from the path rather than encoded.
+        if matches!(id, "" | "." | "..") {
+            bail!("Invalid Kitsu ID {:?}", id);
+        }
+        Ok(KitsuQuery::new(base_url, collection)?.segment(id))
+    }
+
+    /// Something belonging to the item, such as `episodes`.
+    pub fn related(self, relationship: &str) -> KitsuQuery {
+        self.segment(relationship)
+    }
+
+    fn segment(mut self, segment: &str) -> KitsuQuery {
+        // Only URLs that can't be a base have no segments, and `new` has
+        // already joined a path onto this one.
+        if let Ok(mut segments) = self.url.path_segments_mut() {
+            segments.push(segment);
+        }
+        self
+    }
+
+    /// Any other parameter, for the few requests that need one.
+    pub fn param(mut self, name: &str, value: &str) -> KitsuQuery {
+        self.url.query_pairs_mut().append_pair(name, value);
+        self
+    }
+
+    /// Searches titles for `query`; an empty one browses instead.
+    pub fn text(self, query: &str) -> KitsuQuery {
+        if query.is_empty() {
+            return self;
+        }
+        self.param("filter[text]", query)
+    }
+
+    pub fn page(self, page: Page) -> KitsuQuery {
+        self.limit(page.limit)
+            .param("page[offset]", &page.offset.to_string())
+    }
+
+    /// The page size alone, for requests that always start at the top.
+    pub fn limit(self, limit: u32) -> KitsuQuery {
+        self.param("page[limit]", &limit.to_string())
+    }
+
+    pub fn season(self, season: Option<Season>) -> KitsuQuery {
+        match season {
+            Some(season) => self.param("filter[season]", season.api_value()),
+            None => self,
+        }
+    }
+
+    pub fn year(self, year: Option<u16>) -> KitsuQuery {
+        match year {
+            Some(year) => self.param("filter[seasonYear]", &year.to_string()),
+            None => self,
+        }
+    }
+
+    pub fn subtype(self, subtype: Option<Subtype>) -> KitsuQuery {
+        match subtype {
+            Some(subtype) => self.param("filter[subtype]", subtype.api_value()),
+            None => self,
+        }
+    }
+
+    /// Kitsu's own status value, such as `current` or `finished`.
+    pub fn status(self, status: Option<&str>) -> KitsuQuery {
+        match status {
+            Some(status) => self.param("filter[status]", status),
+            None => self,
+        }
+    }
+
+    /// Everything rated `max` or below.
+    pub fn max_age_rating(self, max: Option<AgeRating>) -> KitsuQuery {
+        match max {
+            Some(max) => self.param("filter[ageRating]", &max.up_to()),
+            None => self,
+        }
+    }
+
+    pub fn sort(self, sort: Option<SortKey>) -> KitsuQuery {
+        match sort {
+            Some(sort) => self.param("sort", sort.api_value()),
+            None => self,
+        }
+    }
+
+    /// Related resources to send along, e.g. `streamer`.
+    pub fn include(self, relationship: &str) -> KitsuQuery {
+        self.param("include", relationship)
+    }
+
+    pub fn build(self) -> String {
+        self.url.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KITSU_API_URL;
+
+    fn anime() -> KitsuQuery {
+        KitsuQuery::new(KITSU_API_URL, "anime").unwrap()
+    }
+
+    #[test]
+    fn bare_paths_have_no_query() {
+        assert_eq!(anime().build(), "https://kitsu.io/api/edge/anime");
+        assert_eq!(
+            KitsuQuery::new(KITSU_API_URL, "anime/1/episodes")
+                .unwrap()
+                .limit(20)
+                .build(),
+            "https://kitsu.io/api/edge/anime/1/episodes?page%5Blimit%5D=20"
+        );
+    }
+
+    #[test]
+    fn parameters_come_in_call_order() {
+        let url = anime()
+            .text("cowboy bebop")
+            .page(Page::numbered(2, 10))
+            .subtype(Some(Subtype::Tv))
+            .status(Some("finished"))
+            .max_age_rating(Some(AgeRating::R))
+            .sort(Some(SortKey::Rating))
+            .build();
+        assert_eq!(
+            url,
+            "https://kitsu.io/api/edge/anime?filter%5Btext%5D=cowboy+bebop\
+             &page%5Blimit%5D=10&page%5Boffset%5D=10&filter%5Bsubtype%5D=TV\
+             &filter%5Bstatus%5D=finished&filter%5BageRating%5D=G%2CPG%2CR\
+             &sort=-averageRating"
+        );
+
+        let url = anime()
+            .sort(Some(SortKey::DateAsc))
+            .season(Some(Season::Winter))
+            .year(Some(2024))
+            .build();
+        assert_eq!(
+            url,
+            "https://kitsu.io/api/edge/anime?sort=startDate\
+             &filter%5Bseason%5D=winter&filter%5BseasonYear%5D=2024"
+        );
+    }
+
+    #[test]
+    fn unset_filters_and_empty_text_are_left_out() {
+        let url = anime()
+            .text("")
+            .subtype(None)
+            .status(None)
+            .season(None)
+            .year(None)
+            .max_age_rating(None)
+            .sort(None)
+            .build();
+        assert_eq!(url, "https://kitsu.io/api/edge/anime");
+    }
+
+    #[test]
+    fn values_are_percent_encoded() {
+        let url = anime()
+            .text("Re:Zero & friends/100%")
+            .include("streamer,genres")
+            .build();
+        assert_eq!(
+            url,
+            "https://kitsu.io/api/edge/anime?filter%5Btext%5D=Re%3AZero+%26+friends%2F100%25\
+             &include=streamer%2Cgenres"
+        );
+    }
+
+    #[test]
+    fn an_unusable_base_url_is_an_error() {
+        let err = KitsuQuery::new("not a url", "anime").unwrap_err();
+        assert_eq!(err.to_string(), "Invalid Kitsu API URL not a url");
+    }
+
+    #[test]
+    fn ids_stay_one_path_segment() {
+        let item = |id| KitsuQuery::item(KITSU_API_URL, "anime", id);
+        assert_eq!(
+            item("1").unwrap().related("episodes").limit(20).build(),
+            "https://kitsu.io/api/edge/anime/1/episodes?page%5Blimit%5D=20"
+        );
+        assert_eq!(
+            item("1?filter[x]=y").unwrap().related("episodes").build(),
+            "https://kitsu.io/api/edge/anime/1%3Ffilter[x]=y/episodes"
+        );
+        assert_eq!(
+            item("1/../../users").unwrap().build(),
+            "https://kitsu.io/api/edge/anime/1%2F..%2F..%2Fusers"
+        );
+        assert_eq!(
+            item("..").unwrap_err().to_string(),
+            r#"Invalid Kitsu ID "..""#
+        );
+    }
+}
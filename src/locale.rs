@@ -0,0 +1,140 @@
+//! How dates and counts are written for people: `--locale` (or config
+//! `locale`) picks the date order and thousands separator. The default is
+//! ISO dates, which sort and parse the same everywhere; JSON and CSV output
+//! always keep Kitsu's own values.
+
+use std::sync::OnceLock;
+
+use clap::ValueEnum;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Locale {
+    /// 2013-04-07 and 1,234.
+    #[default]
+    Iso,
+    /// 04/07/2013 and 1,234.
+    Us,
+    /// 07/04/2013 and 1,234.
+    Uk,
+    /// 07.04.2013 and 1.234.
+    De,
+    /// 07/04/2013 and 1 234.
+    Fr,
+    /// Whichever of the above matches LC_ALL, LC_TIME or LANG.
+    System,
+}
+
+impl Locale {
+    /// Resolves `System` from the environment, falling back to ISO.
+    pub fn resolve(self) -> Locale {
+        if self != Locale::System {
+            return self;
+        }
+        let lang = ["LC_ALL", "LC_TIME", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .unwrap_or_default();
+        // e.g. "en_GB.UTF-8": the language, then the region.
+        let mut parts = lang.split(['_', '.', '@']);
+        match (parts.next().unwrap_or(""), parts.next().unwrap_or("")) {
+            ("en", "US") => Locale::Us,
+            ("en", "") => Locale::Iso,
+            ("en", _) => Locale::Uk,
+            ("de", _) => Locale::De,
+            ("fr", _) => Locale::Fr,
+            _ => Locale::Iso,
+        }
+    }
+
+    fn thousands_separator(self) -> &'static str {
+        match self {
+            Locale::De => ".",
+            Locale::Fr => " ",
+            _ => ",",
+        }
+    }
+}
+
+static LOCALE: OnceLock<Locale> = OnceLock::new();
+
+/// Sets the locale for the rest of the run. Only the first call takes effect.
+pub fn set(locale: Locale) {
+    let _ = LOCALE.set(locale.resolve());
+}
+
+fn current() -> Locale {
+    *LOCALE.get_or_init(Locale::default)
+}
+
+/// A Kitsu `YYYY-MM-DD` date in the current locale's order. Anything else
+/// is returned unchanged.
+pub fn date(text: &str) -> String {
+    date_in(current(), text)
+}
+
+fn date_in(locale: Locale, text: &str) -> String {
+    let parts: Vec<&str> = text.trim().split('-').collect();
+    let [year, month, day] = parts[..] else {
+        return text.to_string();
+    };
+    if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+        return text.to_string();
+    }
+    match locale {
+        Locale::Us => format!("{}/{}/{}", month, day, year),
+        Locale::Uk | Locale::Fr => format!("{}/{}/{}", day, month, year),
+        Locale::De => format!("{}.{}.{}", day, month, year),
+        Locale::Iso | Locale::System => text.to_string(),
+    }
+}
+
+/// `n` with the current locale's thousands separator, e.g. `1,234,567`.
+pub fn count(n: u64) -> String {
+    count_in(current(), n)
+}
+
+fn count_in(locale: Locale, n: u64) -> String {
+    let separator = locale.thousands_separator();
+    let digits = n.to_string();
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push_str(separator);
+        }
+        out.push(digit);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates_follow_the_locale_order() {
+        assert_eq!(date_in(Locale::Iso, "2013-04-07"), "2013-04-07");
+        assert_eq!(date_in(Locale::Us, "2013-04-07"), "04/07/2013");
+        assert_eq!(date_in(Locale::Uk, "2013-04-07"), "07/04/2013");
+        assert_eq!(date_in(Locale::De, "2013-04-07"), "07.04.2013");
+        // Anything that isn't a Kitsu date is left alone.
+        assert_eq!(date_in(Locale::Us, "2013-4-7"), "2013-4-7");
+        assert_eq!(date_in(Locale::De, "Spring 2013"), "Spring 2013");
+    }
+
+    #[test]
+    fn counts_group_thousands() {
+        assert_eq!(count_in(Locale::Iso, 0), "0");
+        assert_eq!(count_in(Locale::Iso, 999), "999");
+        assert_eq!(count_in(Locale::Us, 1_000), "1,000");
+        assert_eq!(count_in(Locale::De, 1_234_567), "1.234.567");
+        assert_eq!(count_in(Locale::Fr, 123_456), "123 456");
+    }
+
+    #[test]
+    fn explicit_locales_resolve_to_themselves() {
+        for locale in [Locale::Iso, Locale::Us, Locale::Uk, Locale::De, Locale::Fr] {
+            assert_eq!(locale.resolve(), locale);
+        }
+    }
+}
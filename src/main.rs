@@ -1,57 +1,835 @@
-use std::sync::{Arc, Mutex};
+/// `print!` through `output`, so `--output` can redirect it.
+macro_rules! out {
+    ($($arg:tt)*) => {
+        $crate::output::print(format_args!($($arg)*))
+    };
+}
+
+/// `println!` through `output`, so `--output` can redirect it.
+macro_rules! outln {
+    () => {
+        $crate::output::print(format_args!("\n"))
+    };
+    ($($arg:tt)*) => {
+        $crate::output::print(format_args!("{}\n", format_args!($($arg)*)))
+    };
+}
+
+mod anilist;
+mod cache;
+mod compare;
+mod completions;
+mod config;
+mod console;
+mod csv;
+mod dates;
+mod enrich;
+mod glyphs;
+mod history;
+mod http;
+mod kitsu;
+#[cfg(test)]
+#[path = "../tests/support/kitsu_stub.rs"]
+mod kitsu_stub;
+mod locale;
+mod logging;
+mod offline;
+mod output;
+mod paths;
+mod player;
+mod queries;
+mod random;
+mod session;
+mod source;
+mod spinner;
+mod summary;
+mod table;
+mod theme;
+mod tui;
+mod watchlist;
+
+use std::{
+    cmp::Reverse, collections::HashMap, ffi::OsString, io::IsTerminal, path::PathBuf,
+    process::ExitCode, time::Duration,
+};
 
-use anyhow::{Context, Result};
-use clap::{Parser, Subcommand};
-use colored::Colorize;
-use reqwest::Client;
+use anyhow::{Context, Result, bail};
+use clap::{ArgAction, CommandFactory, Parser, Subcommand, ValueEnum, error::ErrorKind};
+use colored::{ColoredString, Colorize};
+use futures_util::{StreamExt, future, stream};
+use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
 use terminal_size::{Width, terminal_size};
-use tui::{
-    Frame, Terminal,
-    backend::{Backend, CrosstermBackend},
-    layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Modifier, Style},
-    text::{Span, Spans},
-    widgets::{Block, Borders, List, ListItem, Paragraph, Tabs},
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::{
+    cache::{CachedSearch, SearchCache, SearchKey},
+    completions::Shell,
+    config::{Config, config_path},
+    enrich::{Enrichments, enrich},
+    history::{History, format_age},
+    http::{DEFAULT_MAX_WAIT, HttpClient, NetworkError, StatusError},
+    kitsu::KitsuQuery,
+    locale::Locale,
+    queries::SavedQueries,
+    random::Rng,
+    source::{AnimeSource, Backend, SourceKind},
+    spinner::Spinner,
+    summary::Summary,
+    theme::ThemeName,
+    watchlist::{AddOutcome, WatchStatus, Watchlist, WatchlistEntry},
 };
 
-enum InputMode {
-    Normal,
-    Editing,
+const KITSU_API_URL: &str = "https://kitsu.io/api/edge";
+/// Where an anime's web page lives, as `{KITSU_WEB_URL}/{id}`.
+const KITSU_WEB_URL: &str = "https://kitsu.io/anime";
+const DEFAULT_PAGE_LIMIT: u32 = 10;
+/// The most results Kitsu returns for a single request.
+const KITSU_MAX_PAGE_LIMIT: u32 = 20;
+/// Limits above this get a warning, since each 20 results is a request.
+const LARGE_LIMIT: u32 = 200;
+/// How many category titles to show per result.
+const MAX_GENRES: usize = 5;
+/// How many optional attributes `completeness_score` counts.
+const COMPLETENESS_FIELDS: u8 = 13;
+/// How many `search --stdin` queries may be in flight at once.
+const BATCH_CONCURRENCY: usize = 4;
+/// How many `--with-genres`/`--with-streaming` lookups may be in flight at
+/// once, unless `--concurrency` says otherwise.
+const DEFAULT_CONCURRENCY: u32 = 4;
+/// How many wrapped synopsis lines search results show by default.
+const DEFAULT_SYNOPSIS_LINES: usize = 3;
+const CATEGORIES_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+#[derive(Subcommand)]
+enum Commands {
+    Search {
+        /// Text to search for; may be left out when filtering by --season
+        /// or --year.
+        query: Option<String>,
+        /// Re-run the nth most recent search from `history` instead of
+        /// giving a query [default when given: 1, the last search].
+        #[arg(long, num_args = 0..=1, default_missing_value = "1", conflicts_with = "query",
+              value_parser = clap::value_parser!(u32).range(1..))]
+        last: Option<u32>,
+        /// Read queries from stdin, one per line, and search for each.
+        #[arg(long, conflicts_with_all = ["query", "last"])]
+        stdin: bool,
+        /// Show the details of the nth result instead of listing them all.
+        /// Without a query this picks from the last listing printed, as it
+        /// was shown, so `search --pick 3` follows up on it.
+        #[arg(long, conflicts_with = "stdin", value_parser = clap::value_parser!(u32).range(1..))]
+        pick: Option<u32>,
+        /// After the listing, ask for result numbers and show each one's
+        /// details, until `q`. Only when reading from a terminal and printing
+        /// text or a table.
+        #[arg(long, visible_alias = "paged", conflicts_with_all = ["stdin", "pick"])]
+        interactive: bool,
+        /// Page of results to show, starting at 1.
+        #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+        page: u32,
+        /// Number of results per page; more than 20 are fetched as several
+        /// requests [default: config `limit`, then 10].
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+        limit: Option<u32>,
+        /// Only show this kind of media.
+        #[arg(long = "type", value_enum, ignore_case = true)]
+        subtype: Option<Subtype>,
+        /// Order results instead of using Kitsu's relevance ranking. Prefix
+        /// with `-` to reverse [default: config `sort`, then relevance].
+        #[arg(long, value_enum, allow_hyphen_values = true)]
+        sort: Option<SortKey>,
+        /// Only show anime that started airing in this season.
+        #[arg(long, value_enum, ignore_case = true)]
+        season: Option<Season>,
+        /// Only show anime that started airing in this year.
+        #[arg(long, value_parser = clap::value_parser!(u16).range(1900..=2100))]
+        year: Option<u16>,
+        /// Only show what is airing (or publishing) now, most popular first
+        /// unless `--sort` says otherwise. Needs no query.
+        #[arg(long)]
+        airing: bool,
+        /// Only show anime that started airing on or after this date.
+        #[arg(long, value_name = "YYYY-MM-DD", value_parser = dates::parse_flag)]
+        since: Option<i64>,
+        /// Only show anime that started airing on or before this date.
+        #[arg(long, value_name = "YYYY-MM-DD", value_parser = dates::parse_flag)]
+        until: Option<i64>,
+        /// Hide anything rated above this.
+        #[arg(long, value_enum, ignore_case = true)]
+        max_age_rating: Option<AgeRating>,
+        /// Which service to search, or `all` to merge every service's
+        /// results [default: config `source`, then kitsu].
+        #[arg(long, value_enum)]
+        source: Option<SourceKind>,
+        /// Which kind of media to search.
+        #[arg(long, value_enum, default_value_t = Media::Anime)]
+        media: Media,
+        /// Which poster image variant to link to.
+        #[arg(long, value_enum, default_value_t = ImageSize::Medium)]
+        poster_size: ImageSize,
+        /// How to print the results [default: config `format`, then text].
+        #[arg(long, value_enum)]
+        format: Option<OutputFormat>,
+        /// Print only the matching IDs, one per line, for scripting.
+        #[arg(short, long, conflicts_with = "format")]
+        quiet: bool,
+        /// How many stars a perfect rating is drawn with.
+        #[arg(long, value_enum, default_value_t = RatingScale::Five)]
+        rating_scale: RatingScale,
+        /// Comma-separated lines to print per result, in order, e.g.
+        /// `title,rating,episodes,status` [default: all of them].
+        #[arg(long, value_enum, value_delimiter = ',')]
+        fields: Vec<Field>,
+        /// Leave out each result's synopsis (text output only).
+        #[arg(long)]
+        no_synopsis: bool,
+        /// Wrap the synopsis onto at most this many lines; 0 shows all of it.
+        #[arg(long, default_value_t = DEFAULT_SYNOPSIS_LINES)]
+        synopsis_lines: usize,
+        /// Cut the synopsis off after this many characters, whatever the
+        /// width; 0 leaves the length to --synopsis-lines alone.
+        #[arg(long, default_value_t = 0)]
+        synopsis_chars: usize,
+        /// Keep the synopsis's own line breaks instead of re-wrapping it to
+        /// the terminal width.
+        #[arg(long)]
+        no_wrap: bool,
+        /// Also say how long ago each anime aired, e.g. "aired 11 years ago".
+        #[arg(long)]
+        relative_dates: bool,
+        /// Print each result on a single line without separator rules (text
+        /// output only).
+        #[arg(long)]
+        compact: bool,
+        /// Also look up each result's genres (one extra request per result).
+        #[arg(long)]
+        with_genres: bool,
+        /// Also look up where each anime streams (one extra request per
+        /// result).
+        #[arg(long)]
+        with_streaming: bool,
+        /// How many extra per-result lookups may run at once.
+        #[arg(long, default_value_t = DEFAULT_CONCURRENCY,
+              value_parser = clap::value_parser!(u32).range(1..=16))]
+        concurrency: u32,
+        /// Collapse results with the same title, keeping the most complete.
+        #[arg(long)]
+        dedup: bool,
+        /// Hide results rated below this, out of 100. Unrated results are
+        /// kept unless `--drop-unrated` is also given.
+        #[arg(long, value_parser = clap::value_parser!(u8).range(0..=100))]
+        min_rating: Option<u8>,
+        /// Hide results that have no rating.
+        #[arg(long)]
+        drop_unrated: bool,
+        /// Hide results with fewer episodes than this. Results whose count
+        /// is unknown are hidden too, unless `--include-unknown`.
+        #[arg(long)]
+        min_episodes: Option<u16>,
+        /// Hide results with more episodes than this.
+        #[arg(long)]
+        max_episodes: Option<u16>,
+        /// Keep results with an unknown episode count when filtering by
+        /// `--min-episodes`/`--max-episodes`.
+        #[arg(long)]
+        include_unknown: bool,
+        /// Hide results with fewer than this many of the optional attributes
+        /// (synopsis, rating, dates, episodes, poster, ...) filled in.
+        #[arg(long, value_parser = clap::value_parser!(u8).range(0..=COMPLETENESS_FIELDS as i64))]
+        min_completeness: Option<u8>,
+        /// List the most complete results first; equally complete ones keep
+        /// their order.
+        #[arg(long)]
+        by_completeness: bool,
+        /// Keep results marked as adult content, which are hidden by default
+        /// [default: config `include_nsfw`, then false].
+        #[arg(long)]
+        include_nsfw: bool,
+        /// Always fetch fresh results instead of reading the cache.
+        #[arg(long)]
+        no_cache: bool,
+        /// Seconds a cached search stays fresh.
+        #[arg(long, default_value_t = 3600)]
+        cache_ttl: u64,
+        /// Print the requests the search would send, then exit without
+        /// sending them.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    Watch {
+        id: String,
+        /// Play the first streaming link in a media player instead of
+        /// listing them; names the player to use [default when given: mpv].
+        #[arg(long, num_args = 0..=1, default_missing_value = player::DEFAULT_PLAYER)]
+        player: Option<String>,
+    },
+    /// Show everything known about one anime.
+    Info { id: String },
+    /// Compare two anime side by side, highlighting which is ahead on
+    /// rating, length and popularity.
+    Compare { id_a: String, id_b: String },
+    /// Show what's currently trending on Kitsu.
+    Trending {
+        /// Number of anime to show [default: config `limit`, then 10].
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+        limit: Option<u32>,
+        /// Keep anime marked as adult content [default: config
+        /// `include_nsfw`, then false].
+        #[arg(long)]
+        include_nsfw: bool,
+    },
+    /// Pick a random anime, for when you can't decide what to watch.
+    Random {
+        /// Only pick anime rated at least this, out of 100.
+        #[arg(long, value_parser = clap::value_parser!(u8).range(0..=100))]
+        min_rating: Option<u8>,
+        /// Only pick this kind of media.
+        #[arg(long = "type", value_enum, ignore_case = true)]
+        subtype: Option<Subtype>,
+        /// Seed the pick, so the same seed picks the same anime again.
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Let the pick be an anime marked as adult content [default: config
+        /// `include_nsfw`, then false].
+        #[arg(long)]
+        include_nsfw: bool,
+    },
+    /// Browse and search interactively in the terminal UI.
+    Tui {
+        /// Start from the search that was open when the TUI last quit.
+        #[arg(long)]
+        resume: bool,
+        /// Look up each result's genres, filling them in as they arrive.
+        #[arg(long)]
+        with_genres: bool,
+        /// Look up where each result streams as soon as it is shown, rather
+        /// than when its details are opened.
+        #[arg(long)]
+        with_streaming: bool,
+        /// Keep search results marked as adult content [default: config
+        /// `include_nsfw`, then false].
+        #[arg(long)]
+        include_nsfw: bool,
+    },
+    /// List every episode of an anime.
+    Episodes { id: String },
+    /// List an anime's sequels, prequels, side stories and other related
+    /// anime and manga.
+    Related { id: String },
+    /// Save an anime to your local watchlist.
+    Add {
+        id: String,
+        #[arg(long, value_enum, default_value_t = WatchStatus::PlanToWatch)]
+        status: WatchStatus,
+    },
+    /// Remove an anime from your watchlist.
+    Remove { id: String },
+    /// Record how many episodes of a watchlist anime you've watched.
+    Progress {
+        id: String,
+        /// Episodes watched, capped at the anime's episode count.
+        episodes: u16,
+    },
+    /// Show everything on your watchlist.
+    List {
+        /// Only show anime marked as watching.
+        #[arg(long)]
+        watching: bool,
+    },
+    /// List recent searches, most recent first.
+    History,
+    /// Inspect the configuration file.
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Print a shell completion script to stdout.
+    ///
+    /// Redirect it into your shell's completion directory, for example
+    /// `sabikani completions bash > ~/.local/share/bash-completion/completions/sabikani`
+    /// or `sabikani completions fish > ~/.config/fish/completions/sabikani.fish`.
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    /// Manage the on-disk search cache.
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+    /// Save searches under a name and run them again later.
+    Query {
+        #[command(subcommand)]
+        action: QueryAction,
+    },
 }
 
-enum Tab {
-    Search,
-    Details,
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Print where the config file is expected to live.
+    Path,
 }
 
 #[derive(Subcommand)]
-enum Commands {
-    Search { query: String },
+enum CacheAction {
+    /// List the cached searches, newest first.
+    List,
+    /// Delete every cached search and genre lookup.
+    Clear {
+        /// Only delete the searches made with this source.
+        #[arg(long, value_enum)]
+        source: Option<SourceKind>,
+    },
+}
+
+#[derive(Subcommand)]
+enum QueryAction {
+    /// Save `search` arguments under a name, replacing any query already
+    /// saved as it, e.g. `query save winter --season winter --year 2024`.
+    Save {
+        name: String,
+        /// The query text and flags, exactly as they'd follow `search`.
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// Run a saved query. Anything after the name is added to its arguments,
+    /// so later flags override saved ones.
+    Run {
+        name: String,
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// List saved queries and what each one runs.
+    List,
+    /// Forget a saved query.
+    Remove { name: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// Colorized, human-readable listing.
+    Text,
+    /// Pretty-printed JSON, suitable for piping into `jq`.
+    Json,
+    /// One row per result, for spreadsheets.
+    Csv,
+    /// Aligned columns, one row per result.
+    Table,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+enum Media {
+    #[default]
+    Anime,
+    Manga,
+}
+
+impl Media {
+    fn path(self) -> &'static str {
+        match self {
+            Media::Anime => "anime",
+            Media::Manga => "manga",
+        }
+    }
+}
+
+/// Kitsu's media subtypes, as accepted by `filter[subtype]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Subtype {
+    #[value(name = "TV")]
+    Tv,
+    Movie,
+    #[value(name = "OVA")]
+    Ova,
+    #[value(name = "ONA")]
+    Ona,
+    Special,
+    Music,
+}
+
+impl Subtype {
+    fn api_value(self) -> &'static str {
+        match self {
+            Subtype::Tv => "TV",
+            Subtype::Movie => "movie",
+            Subtype::Ova => "OVA",
+            Subtype::Ona => "ONA",
+            Subtype::Special => "special",
+            Subtype::Music => "music",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum SortKey {
+    /// Highest rated first.
+    Rating,
+    #[value(name = "-rating")]
+    RatingAsc,
+    /// Most popular first.
+    Popularity,
+    #[value(name = "-popularity")]
+    PopularityAsc,
+    /// Most recently started first.
+    Date,
+    #[value(name = "-date")]
+    DateAsc,
+}
+
+impl SortKey {
+    fn api_value(self) -> &'static str {
+        match self {
+            SortKey::Rating => "-averageRating",
+            SortKey::RatingAsc => "averageRating",
+            // Rank 1 is the most popular, so "most popular first" ascends.
+            SortKey::Popularity => "popularityRank",
+            SortKey::PopularityAsc => "-popularityRank",
+            SortKey::Date => "-startDate",
+            SortKey::DateAsc => "startDate",
+        }
+    }
+}
+
+/// The anime season a show started airing in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Season {
+    Winter,
+    Spring,
+    Summer,
+    Fall,
+}
+
+impl Season {
+    fn api_value(self) -> &'static str {
+        match self {
+            Season::Winter => "winter",
+            Season::Spring => "spring",
+            Season::Summer => "summer",
+            Season::Fall => "fall",
+        }
+    }
+}
+
+/// Describes a season/year browse for the header, e.g. "spring 2021".
+fn season_label(season: Option<Season>, year: Option<u16>) -> String {
+    match (season, year) {
+        (Some(season), Some(year)) => format!("{} {}", season.api_value(), year),
+        (Some(season), None) => format!("{} of any year", season.api_value()),
+        (None, Some(year)) => year.to_string(),
+        (None, None) => "everything".to_string(),
+    }
+}
+
+/// Kitsu's age ratings, ordered from least to most restricted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum AgeRating {
+    #[value(name = "G")]
+    G,
+    #[value(name = "PG")]
+    Pg,
+    #[value(name = "R")]
+    R,
+    #[value(name = "R18")]
+    R18,
+}
+
+impl AgeRating {
+    const ALL: [AgeRating; 4] = [AgeRating::G, AgeRating::Pg, AgeRating::R, AgeRating::R18];
+
+    fn api_value(self) -> &'static str {
+        match self {
+            AgeRating::G => "G",
+            AgeRating::Pg => "PG",
+            AgeRating::R => "R",
+            AgeRating::R18 => "R18",
+        }
+    }
+
+    /// The comma-separated `filter[ageRating]` value admitting every rating
+    /// up to and including `self`.
+    fn up_to(self) -> String {
+        AgeRating::ALL
+            .iter()
+            .filter(|rating| **rating <= self)
+            .map(|rating| rating.api_value())
+            .collect::<Vec<_>>()
+            .join(",")
+    }
 }
 
 #[derive(Parser)]
-#[command(author, version, about, long_about = None)]
+#[command(
+    author,
+    version,
+    about,
+    long_about = None,
+    // A later flag wins over an earlier one, which `query run` relies on to
+    // let the command line override a saved query.
+    args_override_self = true,
+    after_help = "Exit status: 0 on success, 1 when a search found nothing, 2 when a request \
+                  to Kitsu or AniList failed, 3 on any other error.\n\n\
+                  Kitsu allows more requests when signed in. Set SABIKANI_TOKEN (or `token` \
+                  under `[kitsu]` in the config file) to an access token, which you can get \
+                  with:\n  curl -X POST https://kitsu.io/api/oauth/token \\\n    \
+                  -d grant_type=password -d username=<email> -d password=<password>\n\n\
+                  To go through a Kitsu mirror or proxy, set SABIKANI_BASE_URL (or `base_url` \
+                  under `[kitsu]`) to its API root, e.g. https://kitsu.example.com/api/edge. \
+                  Headers such a gateway requires go in a `[headers]` table, one \
+                  `Name = \"value\"` per line."
+)]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// Seconds to wait for Kitsu before giving up on a request.
+    #[arg(long, global = true, default_value_t = 30)]
+    timeout: u64,
+    /// How many times to retry rate-limited or failed requests.
+    #[arg(long, global = true, default_value_t = 3)]
+    retries: u32,
+    /// Longest to wait, in seconds, when rate limited before retrying;
+    /// asked to wait longer, give up instead.
+    #[arg(long, global = true, default_value_t = DEFAULT_MAX_WAIT.as_secs())]
+    max_wait: u64,
+    /// When to use colored output [default: config `color`, then auto].
+    #[arg(long, value_enum, global = true)]
+    color: Option<ColorMode>,
+    /// Colors and rules for text output [default: config `theme`, then
+    /// default].
+    #[arg(long, value_enum, global = true)]
+    theme: Option<ThemeName>,
+    /// Lay text output out for this many columns instead of the terminal's
+    /// width, for reproducible output (at least 40).
+    #[arg(
+        long,
+        global = true,
+        value_parser = clap::value_parser!(u16).range(i64::from(MIN_WRAP_WIDTH)..)
+    )]
+    wrap_width: Option<u16>,
+    /// How to write dates and counts in text output [default: config
+    /// `locale`, then iso].
+    #[arg(long, value_enum, global = true)]
+    locale: Option<Locale>,
+    /// When to link titles to their web page in text output.
+    #[arg(long, value_enum, global = true, default_value_t = HyperlinkMode::Auto)]
+    hyperlinks: HyperlinkMode,
+    /// Draw stars, rules, borders and the spinner with plain ASCII (the
+    /// default when TERM is `dumb` or the locale isn't UTF-8).
+    #[arg(long, global = true)]
+    ascii: bool,
+    /// Which title variant to show, falling back to the canonical title.
+    #[arg(long, value_enum, global = true, default_value_t = TitleLanguage::Canonical)]
+    title_language: TitleLanguage,
+    /// Log requests and results to stderr; repeat for more detail (-vv).
+    #[arg(short, long, global = true, action = ArgAction::Count)]
+    verbose: u8,
+    /// Answer from bundled sample data instead of the network (also
+    /// `SABIKANI_OFFLINE=1`).
+    #[arg(long, global = true)]
+    offline: bool,
+    /// Write the output to this file instead of stdout, without colors.
+    #[arg(long, global = true)]
+    output: Option<PathBuf>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ColorMode {
+    /// Color only when stdout is a terminal and NO_COLOR is unset.
+    Auto,
+    Always,
+    Never,
+}
+
+/// When to write titles as clickable terminal hyperlinks (OSC 8).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+enum HyperlinkMode {
+    /// Link only in terminals known to support hyperlinks.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl HyperlinkMode {
+    fn apply(self, to_file: bool) {
+        let enabled = match self {
+            HyperlinkMode::Always => true,
+            HyperlinkMode::Never => false,
+            HyperlinkMode::Auto => {
+                !to_file
+                    && std::io::stdout().is_terminal()
+                    && output::terminal_supports_hyperlinks()
+            }
+        };
+        if enabled {
+            output::enable_hyperlinks();
+        }
+    }
+}
+
+impl ColorMode {
+    fn apply(self) {
+        let enabled = match self {
+            ColorMode::Always => {
+                console::enable_ansi();
+                true
+            }
+            ColorMode::Never => false,
+            ColorMode::Auto => {
+                // https://no-color.org: any non-empty value disables color.
+                let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+                // On Windows this also switches the console to ANSI mode.
+                !no_color && std::io::stdout().is_terminal() && console::enable_ansi()
+            }
+        };
+        colored::control::set_override(enabled);
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 struct AnimeResponse {
     data: Vec<AnimeData>,
+    links: Option<PaginationLinks>,
+    meta: Option<ResponseMeta>,
+    /// Entries dropped because they didn't parse; see `RawAnimeResponse`.
+    #[serde(skip)]
+    skipped: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct ResponseMeta {
+    /// Total matches across all pages.
+    count: Option<u64>,
+}
+
+/// A Kitsu list response with each entry left unparsed, so one malformed
+/// entry can be skipped instead of failing the whole response.
+#[derive(Debug, Deserialize)]
+struct RawAnimeResponse {
+    #[serde(default)]
+    data: Vec<serde_json::Value>,
+    links: Option<PaginationLinks>,
+    meta: Option<ResponseMeta>,
+}
+
+impl RawAnimeResponse {
+    fn into_response(self) -> AnimeResponse {
+        let total = self.data.len();
+        let data: Vec<AnimeData> = self
+            .data
+            .into_iter()
+            .filter_map(|entry| serde_json::from_value(entry).ok())
+            .collect();
+
+        AnimeResponse {
+            skipped: total - data.len(),
+            data,
+            links: self.links,
+            meta: self.meta,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PaginationLinks {
+    first: Option<String>,
+    prev: Option<String>,
+    next: Option<String>,
+    last: Option<String>,
+}
+
+impl AnimeResponse {
+    fn has_next_page(&self) -> bool {
+        self.links.as_ref().is_some_and(|l| l.next.is_some())
+    }
+}
+
+/// A window into a paginated Kitsu collection.
+#[derive(Debug, Clone, Copy)]
+struct Page {
+    limit: u32,
+    offset: u32,
+}
+
+impl Page {
+    /// Builds the window for a 1-based page number.
+    fn numbered(page: u32, limit: u32) -> Page {
+        Page {
+            limit,
+            offset: page.saturating_sub(1) * limit,
+        }
+    }
+}
+
+impl Default for Page {
+    fn default() -> Page {
+        Page::numbered(1, DEFAULT_PAGE_LIMIT)
+    }
+}
+
+/// Everything besides the query text that shapes a search request.
+#[derive(Debug, Clone, Default)]
+struct SearchOptions {
+    media: Media,
+    page: Page,
+    subtype: Option<Subtype>,
+    sort: Option<SortKey>,
+    season: Option<Season>,
+    year: Option<u16>,
+    /// Only what is currently airing.
+    airing: bool,
+    max_age_rating: Option<AgeRating>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+struct SingleAnimeResponse {
+    data: AnimeData,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct AnimeData {
     id: String,
     attributes: AnimeAttributes,
+    /// Category titles, only filled in when requested with `--with-genres`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    categories: Vec<String>,
+    /// Streaming site names, only filled in when requested with
+    /// `--with-streaming`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    streamers: Vec<String>,
+    /// Which backend found this result, only set when several were merged
+    /// with `--source all`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    source: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CategoriesResponse {
+    data: Vec<CategoryData>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+struct CategoryData {
+    id: String,
+    attributes: CategoryAttributes,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CategoryAttributes {
+    title: String,
+}
+
+/// Every field is optional on the wire, so a sparse entry still parses.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 struct AnimeAttributes {
-    #[serde(rename = "canonicalTitle")]
-    cononical_title: String,
+    #[serde(rename = "canonicalTitle", deserialize_with = "null_as_default")]
+    canonical_title: String,
+    #[serde(deserialize_with = "null_as_default")]
+    titles: Titles,
+    /// Kitsu sends `null` rather than an empty list for most entries.
+    #[serde(rename = "abbreviatedTitles")]
+    abbreviated_titles: Option<Vec<String>>,
     synopsis: Option<String>,
     #[serde(rename = "averageRating")]
     average_rating: Option<String>,
@@ -60,319 +838,3593 @@ struct AnimeAttributes {
     #[serde(rename = "endDate")]
     end_date: Option<String>,
     status: Option<String>,
-    #[serde(rename = "episodeCount")]
+    #[serde(rename = "episodeCount", default)]
     episode_count: Option<u16>,
+    #[serde(rename = "chapterCount", default)]
+    chapter_count: Option<u32>,
+    #[serde(rename = "volumeCount", default)]
+    volume_count: Option<u32>,
+    subtype: Option<String>,
+    #[serde(rename = "ageRating", default)]
+    age_rating: Option<String>,
+    #[serde(rename = "ageRatingGuide", default)]
+    age_rating_guide: Option<String>,
+    #[serde(rename = "posterImage", default)]
+    poster_image: Option<ImageSet>,
+    #[serde(rename = "coverImage", default)]
+    cover_image: Option<ImageSet>,
+    #[serde(rename = "popularityRank")]
+    popularity_rank: Option<u32>,
+    #[serde(rename = "ratingRank")]
+    rating_rank: Option<u32>,
+    #[serde(rename = "userCount")]
+    user_count: Option<u64>,
+    #[serde(rename = "favoritesCount")]
+    favorites_count: Option<u64>,
+    /// Adult content; searches hide it unless `--include-nsfw` is given.
+    #[serde(default)]
+    nsfw: Option<bool>,
 }
 
-struct App {
-    input: String,
-    input_mode: InputMode,
-    active_tab: Tab,
-    search_results: Vec<AnimeData>,
-    selected_anime_index: Option<usize>,
-    loading: bool,
-}
+impl AnimeAttributes {
+    /// The title in `language`, or the canonical title when that variant is
+    /// missing.
+    fn display_title(&self, language: TitleLanguage) -> &str {
+        let variant = match language {
+            TitleLanguage::Canonical => None,
+            TitleLanguage::English => self.titles.en.as_deref(),
+            TitleLanguage::Romaji => self.titles.en_jp.as_deref(),
+            TitleLanguage::Japanese => self.titles.ja_jp.as_deref(),
+        };
+        variant
+            .filter(|title| !title.trim().is_empty())
+            .unwrap_or(&self.canonical_title)
+    }
 
-impl App {
-    fn new() -> App {
-        App {
-            input: String::new(),
-            input_mode: InputMode::Normal,
-            active_tab: Tab::Search,
-            search_results: Vec::new(),
-            selected_anime_index: None,
-            loading: false,
+    /// Every other known title: the remaining variants, then the
+    /// abbreviations, without duplicates of the shown title.
+    fn alternative_titles(&self, language: TitleLanguage) -> Vec<&str> {
+        let shown = self.display_title(language);
+        let mut titles: Vec<&str> = Vec::new();
+        let candidates = [
+            Some(self.canonical_title.as_str()),
+            self.titles.en.as_deref(),
+            self.titles.en_jp.as_deref(),
+            self.titles.ja_jp.as_deref(),
+        ]
+        .into_iter()
+        .flatten()
+        .chain(self.abbreviated_titles.iter().flatten().map(String::as_str));
+        for title in candidates {
+            if !title.trim().is_empty() && title != shown && !titles.contains(&title) {
+                titles.push(title);
+            }
         }
+        titles
     }
-}
-
-#[tokio::main]
-async fn main() -> Result<()> {
-    let cli = Cli::parse();
 
-    match &cli.command {
-        Commands::Search { query } => {
-            println!("Searching for: {}", query.yellow());
+    /// Formats the age rating with its content guide, e.g. "R (Violence)".
+    fn age_rating_label(&self) -> Option<String> {
+        let rating = self.age_rating.as_ref()?;
+        Some(match self.age_rating_guide.as_deref() {
+            Some(guide) if !guide.is_empty() => format!("{} ({})", rating, guide),
+            _ => rating.clone(),
+        })
+    }
 
-            let results = search_anime(query).await?;
-            display_anime_results(&results.data);
+    /// How many of the `COMPLETENESS_FIELDS` optional attributes are filled
+    /// in, for telling well-documented entries from stubs.
+    fn completeness_score(&self) -> u8 {
+        let populated: [bool; COMPLETENESS_FIELDS as usize] = [
+            self.synopsis.is_some(),
+            self.average_rating.is_some(),
+            self.start_date.is_some(),
+            self.end_date.is_some(),
+            self.status.is_some(),
+            self.episode_count.is_some(),
+            self.chapter_count.is_some(),
+            self.volume_count.is_some(),
+            self.subtype.is_some(),
+            self.age_rating.is_some(),
+            self.age_rating_guide.is_some(),
+            self.poster_image.is_some(),
+            self.cover_image.is_some(),
+        ];
+        populated.into_iter().filter(|&populated| populated).count() as u8
+    }
 
-            println!(
-                "\nTo watch an anime, run: {} <anime_id>",
-                "anime-cli watch".cyan()
-            );
-        }
+    fn rating_value(&self) -> Option<f64> {
+        self.average_rating.as_deref()?.trim().parse().ok()
     }
-    Ok(())
 }
 
-fn ui<B: Backend>(f: &mut Frame<B>, app: &Arc<Mutex<App>>) {
-    let app = app.blocking_lock();
+/// Reads `null` as the field's default, like a missing field.
+fn null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
 
-    let chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .margin(2)
-        .constraints(
-            [
-                Constraint::Length(3),
-                Constraint::Length(3),
-                Constraint::Min(0),
-            ]
-            .as_ref(),
-        )
-        .split(f.size());
+/// The resized variants Kitsu serves for an image. Any of them may be absent.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ImageSet {
+    tiny: Option<String>,
+    small: Option<String>,
+    medium: Option<String>,
+    large: Option<String>,
+    original: Option<String>,
+}
 
-    let titles = vec!["Search [1]", "Details [2]"];
-    let tabs = Tabs::new(
-        titles
-            .iter()
-            .map(|t| Spans::from(Span::styled(*t, Style::default().fg(Color::Green))))
-            .collect(),
-    )
-    .block(Block::default().borders(Borders::ALL).title("Tabs"))
-    .highlight_style(
-        Style::default()
-            .fg(Color::Yellow)
-            .add_modifier(Modifier::BOLD),
-    )
-    .select(match app.active_tab {
-        Tab::Search => 0,
-        Tab::Details => 1,
-    });
-    f.render_widget(tabs, chunks[0]);
+impl ImageSet {
+    /// Returns the URL for `size`, falling back to the original upload.
+    fn url(&self, size: ImageSize) -> Option<&str> {
+        let sized = match size {
+            ImageSize::Tiny => &self.tiny,
+            ImageSize::Small => &self.small,
+            ImageSize::Medium => &self.medium,
+            ImageSize::Large => &self.large,
+            ImageSize::Original => &self.original,
+        };
+        sized.as_deref().or(self.original.as_deref())
+    }
+}
 
-    let input = Paragraph::new(app.input.as_ref())
-        .style(match app.input_mode {
-            InputMode::Normal => Style::default(),
-            InputMode::Editing => Style::default().fg(Color::Yellow),
-        })
-        .block(Block::default().borders(Borders::ALL).title("Search"));
-    f.render_widget(input, chunks[1]);
+/// Kitsu's localized title variants, keyed by language code.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct Titles {
+    en: Option<String>,
+    en_jp: Option<String>,
+    ja_jp: Option<String>,
+}
 
-    if let InputMode::Editing = app.input_mode {
-        f.set_cursor(chunks[1].x + app.input.width() as u16, chunks[1].y + 1);
-    }
+/// Which entry of `titles` to display.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+enum TitleLanguage {
+    #[default]
+    Canonical,
+    English,
+    /// `en_jp`, the romanized Japanese title.
+    Romaji,
+    /// `ja_jp`, the title in Japanese script.
+    Japanese,
+}
 
-    match app.active_tab {
-        Tab::Search => {
-            render_search_tab(f, chunks[2], &app);
-        }
-        Tab::Details => {
-            render_details_tab(f, chunks[2], &app);
+/// How many stars a perfect rating gets.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+enum RatingScale {
+    #[default]
+    #[value(name = "5")]
+    Five,
+    #[value(name = "10")]
+    Ten,
+}
+
+impl RatingScale {
+    fn stars(self) -> usize {
+        match self {
+            RatingScale::Five => 5,
+            RatingScale::Ten => 10,
         }
     }
 }
 
-fn render_search_tab<B: Backend>(f: &mut Frame<B>, area: Rect, app: &App) {
-    if app.loading {
-        let loading_text = Paragraph::new("Loading...")
-            .style(Style::default().fg(tui::style::Color::Yellow))
-            .block(Block::default().borders(Borders::ALL).title("Results"));
-        f.render_widget(loading_text, area);
-        return;
-    }
+/// A line of per-result output, selectable with `--fields`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Field {
+    Title,
+    Type,
+    Genres,
+    Streaming,
+    Rating,
+    Popularity,
+    AgeRating,
+    Episodes,
+    Chapters,
+    Volumes,
+    Status,
+    Aired,
+    Poster,
+    Cover,
+    Synopsis,
+}
 
-    if app.search_results.is_empty() {
-        let help_message = if app.input.is_empty() {
-            "Press 'e' to endter search mode, type your query, and press Enter to search."
-        } else {
-            "No results found. Try a different search term."
-        };
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+enum ImageSize {
+    Tiny,
+    Small,
+    #[default]
+    Medium,
+    Large,
+    Original,
+}
 
-        let help_text = Paragraph::new(help_message)
-            .style(Style::default().fg(tui::style::Color::Gray))
-            .block(Block::default().borders(Borders::ALL).title("Results"));
-        f.render_widget(help_text, area);
-        return;
-    }
+/// Knobs controlling how `display_anime_results` renders each entry.
+#[derive(Debug, Clone)]
+struct DisplayOptions {
+    poster_size: ImageSize,
+    rating_scale: RatingScale,
+    rating_bands: RatingBands,
+    title_language: TitleLanguage,
+    /// Lines to print per entry, in order.
+    fields: Vec<Field>,
+    no_synopsis: bool,
+    /// Wrapped synopsis lines to show before cutting off; 0 shows them all.
+    synopsis_lines: usize,
+    /// Characters of synopsis to show before cutting off; 0 for no limit.
+    synopsis_chars: usize,
+    /// Re-wrap the synopsis to the output width, rather than keeping its
+    /// original line breaks.
+    wrap: bool,
+    /// Follow air dates with how long ago they were.
+    relative_dates: bool,
+    /// One line per result: title, rating and status only.
+    compact: bool,
+    /// Where titles link to, as `{web_url}/{id}`, or `None` when the IDs
+    /// aren't Kitsu anime IDs.
+    web_url: Option<&'static str>,
+    /// The query, to highlight where it appears in each title.
+    highlight: Option<String>,
+}
 
-    let items: Vec<ListItem> = app
-        .search_results
-        .iter()
-        .map(|anime| {
-            let rating = anime
-                .attributes
-                .average_rating
-                .as_ref()
-                .map(|r| format!(" ({}*)", r))
-                .unwrap_or_default();
-            let title = format!("{}{}", anime.attributes.cononical_title, rating);
+impl Default for DisplayOptions {
+    fn default() -> Self {
+        Self {
+            poster_size: ImageSize::default(),
+            rating_scale: RatingScale::default(),
+            rating_bands: RatingBands::default(),
+            title_language: TitleLanguage::default(),
+            fields: Field::value_variants().to_vec(),
+            no_synopsis: false,
+            synopsis_lines: DEFAULT_SYNOPSIS_LINES,
+            synopsis_chars: 0,
+            wrap: true,
+            relative_dates: false,
+            compact: false,
+            web_url: Some(KITSU_WEB_URL),
+            highlight: None,
+        }
+    }
+}
 
-            ListItem::new(Spans::from(vec![Span::styled(title, Style::default())]))
-        })
-        .collect();
-
-    let list = List::new(items)
-        .block(Block::default().borders(Borders::ALL).title("Results"))
-        .highlight_style(
-            Style::default()
-                .fg(tui::style::Color::Cyan)
-                .add_modifier(Modifier::BOLD),
-        )
-        .highlight_symbol(">> ");
-
-    let mut state = tui::widgets::ListState::default();
-    state.select(app.selected_anime_index);
-    f.render_stateful_widget(list, area, &mut state);
-}
-
-fn render_details_tab<B: Backend>(f: &mut Frame<B>, area: Rect, app: &App) {
-    if let Some(selected) = app.selected_anime_index {
-        if selected < app.search_results.len() {
-            let anime = &app.search_results[selected];
-            let attrs = &anime.attributes;
-
-            let chunks = Layout::default()
-                .direction(Direction::Vertical)
-                .constraints(
-                    [
-                        Constraint::Length(3),
-                        Constraint::Length(3),
-                        Constraint::Min(0),
-                    ]
-                    .as_ref(),
-                )
-                .split(area);
-            // Title
-            let title = Paragraph::new(attrs.cononical_title.clone())
-                .style(
-                    Style::default()
-                        .fg(tui::style::Color::Cyan)
-                        .add_modifier(Modifier::BOLD),
-                )
-                .block(Block::default().borders(Borders::ALL).title("Title"));
-            f.render_widget(title, chunks[0]);
+#[derive(Debug, Serialize, Deserialize)]
+struct EpisodesResponse {
+    data: Vec<EpisodeData>,
+    links: Option<PaginationLinks>,
+}
 
-            //Info
-            let mut info = vec![];
+#[derive(Debug, Serialize, Deserialize)]
+struct EpisodeData {
+    id: String,
+    attributes: EpisodeAttributes,
+}
 
-            if let Some(rating) = &attrs.average_rating {
-                info.push(format!("Rating: {}/100", rating));
-            }
+#[derive(Debug, Serialize, Deserialize)]
+struct EpisodeAttributes {
+    #[serde(rename = "canonicalTitle")]
+    canonical_title: Option<String>,
+    number: Option<u32>,
+    #[serde(rename = "seasonNumber")]
+    season_number: Option<u32>,
+    airdate: Option<String>,
+}
 
-            if let Some(eps) = attrs.episode_count {
-                info.push(format!("Episodes: {}", eps));
-            }
+#[derive(Debug, Serialize, Deserialize)]
+struct StreamingLinksResponse {
+    data: Vec<StreamingLinkData>,
+    #[serde(default)]
+    included: Vec<StreamerData>,
+}
 
-            if let Some(status) = &attrs.status {
-                info.push(format!("Status: {}", status));
-            }
+#[derive(Debug, Serialize, Deserialize)]
+struct StreamingLinkData {
+    id: String,
+    attributes: StreamingLinkAttributes,
+    relationships: Option<StreamingLinkRelationships>,
+}
 
-            if let Some(start) = &attrs.start_date {
-                let date_str = if let Some(end) = &attrs.end_date {
-                    format!("Aired: {} to {}", start, end)
-                } else {
-                    format!("Aired: {} to present", start)
-                };
+#[derive(Debug, Serialize, Deserialize)]
+struct StreamingLinkAttributes {
+    url: String,
+    #[serde(default)]
+    subs: Vec<String>,
+    #[serde(default)]
+    dubs: Vec<String>,
+}
 
-                info.push(date_str);
-            }
+#[derive(Debug, Serialize, Deserialize)]
+struct StreamingLinkRelationships {
+    streamer: Option<Relationship>,
+}
 
-            let info_text = Paragraph::new(info.join(" | "))
-                .block(Block::default().borders(Borders::ALL).title("Info"));
-            f.render_widget(info_text, chunks[1]);
+#[derive(Debug, Serialize, Deserialize)]
+struct Relationship {
+    data: Option<ResourceIdentifier>,
+}
 
-            let synopsis = attrs
-                .synopsis
-                .clone()
-                .unwrap_or_else(|| "No synopsis available.".to_string());
+#[derive(Debug, Serialize, Deserialize)]
+struct ResourceIdentifier {
+    id: String,
+}
 
-            let synopsis_text = Paragraph::new(synopsis)
-                .block(Block::default().borders(Borders::ALL).title("Synopsis"))
-                .wrap(tui::widgets::Wrap { trim: true });
-            f.render_widget(synopsis_text, chunks[2]);
-        }
-    } else {
-        let message = Paragraph::new("No anime selected.")
-            .style(Style::default().fg(tui::style::Color::Gray))
-            .block(Block::default().borders(Borders::ALL).title("Details"));
-        f.render_widget(message, area);
+impl StreamingLinksResponse {
+    /// The site `link` is hosted on, looked up in the included streamers.
+    fn streamer_name(&self, link: &StreamingLinkData) -> &str {
+        link.relationships
+            .as_ref()
+            .and_then(|r| r.streamer.as_ref())
+            .and_then(|s| s.data.as_ref())
+            .and_then(|d| self.included.iter().find(|s| s.id == d.id))
+            .map(|s| s.attributes.site_name.as_str())
+            .unwrap_or("Unknown streamer")
     }
 }
 
-async fn search_anime(query: &str) -> Result<AnimeResponse> {
-    let client = Client::new();
+#[derive(Debug, Serialize, Deserialize)]
+struct StreamerData {
+    id: String,
+    attributes: StreamerAttributes,
+}
 
-    let url = format!("https://kitsu.io/api/edge/anime?filter[text]={}", query);
+#[derive(Debug, Serialize, Deserialize)]
+struct StreamerAttributes {
+    #[serde(rename = "siteName")]
+    site_name: String,
+}
 
-    let response = client
-        .get(&url)
-        .header("Accept", "application/vnd.api+json")
-        .header("Content-Type", "application/vnd.api+json")
-        .send()
-        .await
-        .context("Failed to send request to kitsu API")?;
+/// A page of `/anime/{id}/media-relationships?include=destination`.
+#[derive(Debug, Serialize, Deserialize)]
+struct MediaRelationshipsResponse {
+    data: Vec<MediaRelationshipData>,
+    /// The related anime and manga themselves.
+    #[serde(default)]
+    included: Vec<RelatedMedia>,
+    links: Option<PaginationLinks>,
+}
 
-    let anime_data = response
-        .json::<AnimeResponse>()
-        .await
-        .context("Failed to parse anime data")?;
+#[derive(Debug, Serialize, Deserialize)]
+struct MediaRelationshipData {
+    id: String,
+    attributes: MediaRelationshipAttributes,
+    relationships: MediaRelationshipLinks,
+}
 
-    Ok(anime_data)
+#[derive(Debug, Serialize, Deserialize)]
+struct MediaRelationshipAttributes {
+    /// How the destination relates to the anime asked about, e.g. "sequel"
+    /// or "side_story".
+    role: String,
 }
 
-fn display_anime_results(anime_list: &[AnimeData]) {
-    if anime_list.is_empty() {
-        println!("{}", "No results found.".red());
-        return;
-    }
+#[derive(Debug, Serialize, Deserialize)]
+struct MediaRelationshipLinks {
+    destination: TypedRelationship,
+}
 
-    let width = if let Some((Width(w), _)) = terminal_size() {
-        w as usize
-    } else {
-        80
-    };
+#[derive(Debug, Serialize, Deserialize)]
+struct TypedRelationship {
+    data: Option<TypedResourceIdentifier>,
+}
+
+/// A resource link that needs its type: related anime and manga ids overlap.
+#[derive(Debug, Serialize, Deserialize)]
+struct TypedResourceIdentifier {
+    id: String,
+    #[serde(rename = "type")]
+    kind: String,
+}
 
-    println!("\n{}", "SEARCH RESULTS:".green().bold());
-    println!("{}", "=".repeat(width.min(100)));
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RelatedMedia {
+    id: String,
+    /// "anime" or "manga".
+    #[serde(rename = "type")]
+    kind: String,
+    attributes: AnimeAttributes,
+}
 
-    for (i, anime) in anime_list.iter().enumerate() {
-        let attrs = &anime.attributes;
+/// Relationship roles in the order `related` lists them, so a franchise
+/// reads from what came before to what came after.
+const RELATION_ROLES: [&str; 12] = [
+    "prequel",
+    "sequel",
+    "parent_story",
+    "side_story",
+    "full_story",
+    "summary",
+    "alternative_setting",
+    "alternative_version",
+    "spinoff",
+    "adaptation",
+    "character",
+    "other",
+];
 
-        println!(
-            "{}. {} (ID: {})",
-            (i + 1).to_string().yellow().bold(),
-            attrs.cononical_title.cyan().bold(),
-            anime.id
-        );
+/// Exit status when a search or listing came back empty.
+const EXIT_NO_RESULTS: u8 = 1;
+/// Exit status when a request failed or an API answered with an error.
+const EXIT_NETWORK_ERROR: u8 = 2;
+/// Exit status for every other error, such as a broken config file.
+const EXIT_ERROR: u8 = 3;
 
-        if let Some(rating) = &attrs.average_rating {
-            println!("  Rating: {}/100", rating.green());
-        }
+/// What a successful run found, which decides the exit status.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Success,
+    NoResults,
+}
 
-        if let Some(eps) = attrs.episode_count {
-            println!("  Episodes: {}", eps.to_string().yellow());
+impl Outcome {
+    fn found(any: bool) -> Outcome {
+        if any {
+            Outcome::Success
+        } else {
+            Outcome::NoResults
         }
+    }
+}
 
-        if let Some(status) = &attrs.status {
-            let status_colored = match status.as_str() {
-                "finished" => status.green(),
-                "current" => status.cyan(),
-                "upcoming" => status.yellow(),
-                _ => status.normal(),
-            };
-            println!("  Status: {}", status_colored);
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = expand_saved_query(parse_cli());
+    let verbose = cli.as_ref().is_ok_and(|cli| cli.verbose > 0);
+    let result = match cli {
+        Ok(cli) => run(cli).await,
+        Err(err) => Err(err),
+    };
+    // Flush whatever was written, even if the run failed part way.
+    let finished = output::finish();
+    match result.and_then(|outcome| finished.map(|()| outcome)) {
+        Ok(Outcome::Success) => ExitCode::SUCCESS,
+        Ok(Outcome::NoResults) => ExitCode::from(EXIT_NO_RESULTS),
+        Err(err) => {
+            eprintln!("Error: {}", describe_error(&err, verbose));
+            ExitCode::from(exit_code(&err))
         }
+    }
+}
 
-        if let Some(start) = &attrs.start_date {
-            let date_str = if let Some(end) = &attrs.end_date {
-                format!("{} to {}", start, end)
-            } else {
-                format!("{} to present", start)
-            };
-            println!("  Aired: {}", date_str.blue());
+/// The error with its causes, like anyhow's `{:?}`, except that a
+/// `NetworkError` ends the chain unless `verbose`: what reqwest and the OS
+/// said underneath is rarely useful and looks alarming.
+fn describe_error(err: &anyhow::Error, verbose: bool) -> String {
+    if verbose || err.downcast_ref::<NetworkError>().is_none() {
+        return format!("{:?}", err);
+    }
+    let mut causes = Vec::new();
+    for cause in err
+        .chain()
+        .take_while(|cause| !cause.is::<reqwest::Error>())
+    {
+        causes.push(cause.to_string());
+    }
+    let mut text = causes.remove(0);
+    if !causes.is_empty() {
+        text.push_str("\n\nCaused by:");
+        for cause in causes {
+            text.push_str(&format!("\n    {}", cause));
         }
+    }
+    text.push_str("\n\nRun with -v for the underlying error.");
+    text
+}
 
-        if let Some(synopsis) = &attrs.synopsis {
-            let max_len = width.min(100) - 10;
-            let disp_synopsis = if synopsis.len() > max_len {
-                format!("{}...", &synopsis[..max_len])
-            } else {
-                synopsis.clone()
-            };
-            println!("  {}", disp_synopsis.truecolor(200, 200, 200));
+/// `EXIT_NETWORK_ERROR` when anything in the error chain came from an HTTP
+/// request, `EXIT_ERROR` otherwise.
+fn exit_code(err: &anyhow::Error) -> u8 {
+    let network = err
+        .chain()
+        .any(|cause| cause.is::<reqwest::Error>() || cause.is::<StatusError>());
+    if network {
+        EXIT_NETWORK_ERROR
+    } else {
+        EXIT_ERROR
+    }
+}
+
+/// Parses the command line. Run bare (or with only global flags) from a
+/// terminal, sabikani opens the TUI rather than just printing usage; piped or
+/// scripted, it still prints usage and fails as before.
+fn parse_cli() -> Cli {
+    match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(err)
+            if matches!(
+                err.kind(),
+                ErrorKind::MissingSubcommand | ErrorKind::DisplayHelpOnMissingArgumentOrSubcommand
+            ) && std::io::stdin().is_terminal()
+                && std::io::stdout().is_terminal() =>
+        {
+            Cli::parse_from(std::env::args_os().chain(std::iter::once("tui".into())))
         }
+        Err(err) => err.exit(),
+    }
+}
+
+/// Turns `query run <name> [args]` into the `search` it stands for by
+/// splicing the saved arguments into the real command line and parsing that
+/// again, so global flags on either side still apply.
+fn expand_saved_query(cli: Cli) -> Result<Cli> {
+    let Commands::Query {
+        action: QueryAction::Run { name, .. },
+    } = &cli.command
+    else {
+        return Ok(cli);
+    };
+
+    let saved = SavedQueries::load()?;
+    let saved_args = saved.get(name)?;
+    let args: Vec<OsString> = std::env::args_os().collect();
+    let at = args
+        .windows(3)
+        .position(|words| words[0] == "query" && words[1] == "run" && words[2] == name.as_str())
+        .context("Couldn't find `query run` on the command line")?;
+
+    let expanded = args[..at]
+        .iter()
+        .cloned()
+        .chain(std::iter::once(OsString::from("search")))
+        .chain(saved_args.iter().map(OsString::from))
+        .chain(args[at + 3..].iter().cloned());
+    Ok(Cli::parse_from(expanded))
+}
+
+async fn run(cli: Cli) -> Result<Outcome> {
+    if let Some(path) = &cli.output {
+        if matches!(cli.command, Commands::Tui { .. }) {
+            bail!("--output doesn't apply to the TUI");
+        }
+        output::redirect(path)?;
+    }
+
+    // Answer these before loading the config so they work even when the
+    // config file is broken.
+    match &cli.command {
+        Commands::Config {
+            action: ConfigAction::Path,
+        } => {
+            outln!("{}", config_path()?.display());
+            return Ok(Outcome::Success);
+        }
+        Commands::Completions { shell } => {
+            completions::generate(*shell, &mut Cli::command(), &mut std::io::stdout())?;
+            return Ok(Outcome::Success);
+        }
+        _ => {}
+    }
+
+    if cli.offline || offline::requested_by_env() {
+        offline::enable();
+    }
+    // Log lines would scribble over the TUI.
+    if !matches!(cli.command, Commands::Tui { .. }) {
+        logging::init(cli.verbose);
+    }
+
+    let config = Config::load()?;
+    cli.color
+        .or(config.color)
+        .unwrap_or(ColorMode::Auto)
+        .apply();
+    if cli.output.is_some() {
+        colored::control::set_override(false);
+    }
+    cli.hyperlinks.apply(cli.output.is_some());
+    if cli.ascii || glyphs::ascii_preferred() {
+        glyphs::use_ascii();
+    }
+    let mut theme = config.theme(cli.theme)?;
+    if glyphs::ascii_only() {
+        theme.ascii_rules();
+    }
+    theme::set(theme);
+    locale::set(cli.locale.or(config.locale).unwrap_or_default());
+    if let Some(width) = cli.wrap_width {
+        output::fix_width(usize::from(width));
+    }
+    let client = HttpClient::new(Duration::from_secs(cli.timeout), cli.retries)?
+        .with_kitsu_token(config.token())
+        .with_kitsu_headers(config.headers.clone())
+        .with_kitsu_api(config.base_url()?)
+        .with_max_wait(Duration::from_secs(cli.max_wait));
+
+    let mut outcome = Outcome::Success;
+    match &cli.command {
+        Commands::Search {
+            query,
+            last,
+            stdin,
+            pick,
+            interactive,
+            page,
+            limit,
+            subtype,
+            sort,
+            season,
+            year,
+            airing,
+            since,
+            until,
+            max_age_rating,
+            source,
+            media,
+            poster_size,
+            quiet,
+            rating_scale,
+            format,
+            fields,
+            no_synopsis,
+            synopsis_lines,
+            synopsis_chars,
+            no_wrap,
+            relative_dates,
+            compact,
+            with_genres,
+            with_streaming,
+            concurrency,
+            dedup,
+            min_rating,
+            drop_unrated,
+            min_episodes,
+            max_episodes,
+            include_unknown,
+            min_completeness,
+            by_completeness,
+            include_nsfw,
+            no_cache,
+            cache_ttl,
+            dry_run,
+        } => {
+            // A bare --pick follows up on the listing just printed, as it was
+            // shown, without searching again.
+            if let Some(n) = pick
+                && query.is_none()
+                && last.is_none()
+                && season.is_none()
+                && year.is_none()
+                && !*airing
+            {
+                let history = History::load()?;
+                if history.listing.is_empty() {
+                    bail!("There is no listing to pick from; search first, or give --pick a query");
+                }
+                let anime = pick_result(&history.listing, *n)?;
+                let format = format.or(config.format).unwrap_or(OutputFormat::Text);
+                display_pick(anime, *quiet, format, cli.title_language)?;
+                return Ok(outcome);
+            }
+            let query_text = match last {
+                Some(n) => {
+                    let history = History::load()?;
+                    let entry = history
+                        .recent(*n as usize)
+                        .with_context(|| format!("There is no search #{} in history", n))?;
+                    entry.query.clone()
+                }
+                None => query.clone().unwrap_or_default(),
+            };
+            let query = query_text.trim();
+            let browsing = season.is_some() || year.is_some();
+            if query.is_empty() && !browsing && !*airing && !*stdin {
+                bail!(
+                    "The search query is empty; to browse without one, try {} or \
+                     --season/--year/--airing",
+                    "sabikani trending".cyan()
+                );
+            }
+
+            let source = source.or(config.source).unwrap_or_default();
+            let format = format.or(config.format).unwrap_or(OutputFormat::Text);
+            let limit = limit.or(config.limit).unwrap_or(DEFAULT_PAGE_LIMIT);
+            // What's airing now is browsed by popularity, unless a flag says
+            // otherwise.
+            let sort = sort
+                .or(airing.then_some(SortKey::Popularity))
+                .or(config.sort);
+            let include_nsfw = *include_nsfw || config.include_nsfw.unwrap_or(false);
+            if limit > LARGE_LIMIT && source != SourceKind::AniList {
+                eprintln!(
+                    "{} --limit {} will take {} requests to Kitsu",
+                    "warning:".yellow().bold(),
+                    limit,
+                    limit.div_ceil(KITSU_MAX_PAGE_LIMIT)
+                );
+            }
+            // Only the IDs go to stdout in quiet mode, whatever the config says.
+            let chatty =
+                matches!(format, OutputFormat::Text | OutputFormat::Table) && !*quiet && !*dry_run;
+
+            // A pick shows one anime's details, not a listing to introduce.
+            if chatty && !*stdin && pick.is_none() {
+                if query.is_empty() {
+                    let label = match (*airing, browsing) {
+                        (true, false) => "currently airing".to_string(),
+                        (true, true) => {
+                            format!("{}, currently airing", season_label(*season, *year))
+                        }
+                        (false, _) => season_label(*season, *year),
+                    };
+                    outln!("Browsing: {}", label.yellow());
+                } else {
+                    outln!("Searching for: {}", query.yellow());
+                }
+            }
+
+            if *media == Media::Manga && subtype.is_some() {
+                bail!("--type only applies to anime searches");
+            }
+            if *media == Media::Manga && browsing {
+                bail!("--season and --year only apply to anime searches");
+            }
+            let aired = dates::Range {
+                since: *since,
+                until: *until,
+            };
+            if let (Some(since), Some(until)) = (since, until)
+                && since > until
+            {
+                bail!("--since is after --until, so nothing could match");
+            }
+            if let (Some(min), Some(max)) = (min_episodes, max_episodes)
+                && min > max
+            {
+                bail!("--min-episodes is above --max-episodes, so nothing could match");
+            }
+            let episodes = EpisodeRange {
+                min: *min_episodes,
+                max: *max_episodes,
+                include_unknown: *include_unknown,
+            };
+
+            let options = SearchOptions {
+                media: *media,
+                page: Page::numbered(*page, limit),
+                subtype: *subtype,
+                sort,
+                season: *season,
+                year: *year,
+                airing: *airing,
+                max_age_rating: *max_age_rating,
+            };
+            let mut display = DisplayOptions {
+                poster_size: *poster_size,
+                rating_scale: *rating_scale,
+                rating_bands: config.rating_bands(),
+                title_language: cli.title_language,
+                no_synopsis: *no_synopsis,
+                synopsis_lines: *synopsis_lines,
+                synopsis_chars: *synopsis_chars,
+                wrap: !*no_wrap,
+                relative_dates: *relative_dates,
+                compact: *compact,
+                web_url: (*media == Media::Anime && source != SourceKind::AniList)
+                    .then_some(KITSU_WEB_URL),
+                highlight: (!query.is_empty()).then(|| query.to_string()),
+                ..DisplayOptions::default()
+            };
+            if !fields.is_empty() {
+                display.fields = fields.clone();
+            }
+
+            let backend = Backend::new(source, client.clone());
+
+            if *dry_run {
+                let queries = if *stdin {
+                    read_queries()?
+                } else {
+                    vec![query.to_string()]
+                };
+                for query in &queries {
+                    for request in backend.describe_search(query, &options)? {
+                        outln!("{}\n", request);
+                    }
+                }
+                return Ok(Outcome::Success);
+            }
+            // Offline samples must not end up in the cache of real results.
+            let cache_ttl =
+                (!*no_cache && !offline::enabled()).then(|| Duration::from_secs(*cache_ttl));
+
+            if *stdin {
+                let batch = Batch {
+                    backend: &backend,
+                    options: &options,
+                    cache_ttl,
+                    dedup: *dedup,
+                    min_rating: *min_rating,
+                    drop_unrated: *drop_unrated,
+                    episodes,
+                    min_completeness: *min_completeness,
+                    by_completeness: *by_completeness,
+                    aired,
+                    include_nsfw,
+                };
+                let (results, summary) = batch.run(read_queries()?).await;
+                let batch_output = if *quiet {
+                    BatchOutput::Ids
+                } else {
+                    match format {
+                        OutputFormat::Json => BatchOutput::Json,
+                        OutputFormat::Csv => BatchOutput::Csv(cli.title_language),
+                        OutputFormat::Text => BatchOutput::Text(&display),
+                        OutputFormat::Table => BatchOutput::Table(&display),
+                    }
+                };
+                display_batch(&results, batch_output)?;
+                summary.finish("searches")?;
+                return Ok(Outcome::found(
+                    results.iter().any(|result| !result.results.is_empty()),
+                ));
+            }
+
+            let spinner = if chatty {
+                Spinner::start("Searching...")
+            } else {
+                Spinner::disabled()
+            };
+            let mut results = run_search(&backend, query, &options, cache_ttl).await?;
+
+            drop(spinner);
+            log::info!("{} result(s) for {:?}", results.data.len(), query);
+            warn_skipped(&results);
+            // Before any of the filters below drop some.
+            let fetched = results.data.len();
+
+            // Browses without a query have nothing worth recalling, and a
+            // recalled search is already in history.
+            if !query.is_empty()
+                && last.is_none()
+                && let Err(err) = History::append(query, results.data.len())
+            {
+                eprintln!(
+                    "{} couldn't save search history: {:#}",
+                    "warning:".yellow().bold(),
+                    err
+                );
+            }
+
+            if *dedup {
+                let removed = dedup_results(&mut results.data);
+                if chatty && removed > 0 {
+                    outln!("Removed {} duplicate result(s)", removed);
+                }
+            }
+
+            let filtered = filter_by_rating(&mut results.data, *min_rating, *drop_unrated);
+            if chatty && filtered > 0 {
+                outln!("Filtered out {} result(s) by rating", filtered);
+            }
+            let filtered = filter_by_start(&mut results.data, aired);
+            if chatty && filtered > 0 {
+                outln!("Filtered out {} result(s) by air date", filtered);
+            }
+            let filtered = filter_by_episodes(&mut results.data, episodes);
+            if chatty && filtered > 0 {
+                outln!("Filtered out {} result(s) by episode count", filtered);
+            }
+            let filtered = filter_by_completeness(&mut results.data, *min_completeness);
+            if chatty && filtered > 0 {
+                outln!("Filtered out {} sparse result(s)", filtered);
+            }
+            if *by_completeness {
+                sort_by_completeness(&mut results.data);
+            }
+            let hidden_nsfw = if include_nsfw {
+                0
+            } else {
+                filter_nsfw(&mut results.data)
+            };
+
+            let wanted = Enrichments {
+                // AniList includes genres in the search response itself.
+                genres: *with_genres && source != SourceKind::AniList,
+                streaming: *with_streaming,
+            };
+            // Reported after the results, which are worth showing even if
+            // some lookups failed.
+            let lookups = if wanted.any() {
+                enrich(
+                    &client,
+                    *media,
+                    &mut results.data,
+                    wanted,
+                    *concurrency as usize,
+                )
+                .await?
+            } else {
+                Summary::default()
+            };
+
+            outcome = Outcome::found(!results.data.is_empty());
+            if let Some(n) = pick {
+                let anime = pick_result(&results.data, *n)?;
+                display_pick(anime, *quiet, format, cli.title_language)?;
+                lookups.finish("lookups")?;
+                return Ok(outcome);
+            }
+            if let Err(err) = History::remember_listing(&results.data) {
+                eprintln!(
+                    "{} couldn't save the listing for --pick: {:#}",
+                    "warning:".yellow().bold(),
+                    err
+                );
+            }
+            if *quiet {
+                for anime in &results.data {
+                    outln!("{}", anime.id);
+                }
+                lookups.finish("lookups")?;
+                return Ok(outcome);
+            }
+
+            match format {
+                OutputFormat::Json => {
+                    let json = serde_json::to_string_pretty(&results.data)?;
+                    outln!("{}", json);
+                }
+                OutputFormat::Csv => {
+                    let mut csv = String::new();
+                    csv::write_record(&mut csv, CSV_COLUMNS);
+                    for anime in &results.data {
+                        let fields = csv_fields(anime, cli.title_language);
+                        csv::write_record(&mut csv, fields.iter().map(String::as_str));
+                    }
+                    out!("{}", csv);
+                }
+                OutputFormat::Text | OutputFormat::Table => {
+                    if format == OutputFormat::Table {
+                        display_anime_table(&results.data, &display);
+                    } else {
+                        display_anime_results(&results.data, &display);
+                    }
+
+                    let dropped = fetched - results.data.len();
+                    if let Some(footer) = pagination_footer(&results, dropped, &options.page, *page)
+                    {
+                        outln!("\n{}", footer);
+                    }
+                    if hidden_nsfw > 0 {
+                        outln!("{}", hidden_nsfw_note(hidden_nsfw));
+                    }
+
+                    // `watch` looks ids up on Kitsu, so only offer it for Kitsu ids.
+                    if *media == Media::Anime
+                        && source == SourceKind::Kitsu
+                        && !results.data.is_empty()
+                    {
+                        outln!(
+                            "\nTo watch an anime, run: {} <anime_id>",
+                            "sabikani watch".cyan()
+                        );
+                    }
+                }
+            }
+            lookups.finish("lookups")?;
+            if *interactive
+                && matches!(format, OutputFormat::Text | OutputFormat::Table)
+                && cli.output.is_none()
+                && std::io::stdin().is_terminal()
+            {
+                browse_details(&results.data, cli.title_language)?;
+            }
+        }
+        Commands::Watch { id, player } => {
+            let anime = fetch_anime(&client, id).await?;
+            outln!(
+                "Where to watch: {}",
+                anime.data.attributes.canonical_title.cyan().bold()
+            );
+
+            let links = fetch_streaming_links(&client, id).await?;
+            match (player, links.data.first()) {
+                (Some(player), Some(link)) => {
+                    outln!("Playing {} with {}", link.attributes.url.blue(), player);
+                    player::play(player, &link.attributes.url)?;
+                }
+                _ => display_streaming_links(&links),
+            }
+        }
+        Commands::Info { id } => {
+            let anime = fetch_anime(&client, id).await?;
+            outln!("{}", format_anime_details(&anime.data, cli.title_language));
+        }
+        Commands::Compare { id_a, id_b } => {
+            let (a, b) = future::join(fetch_anime(&client, id_a), fetch_anime(&client, id_b)).await;
+            match (a, b) {
+                (Ok(a), Ok(b)) => {
+                    compare::display(&a.data, &b.data, cli.title_language, output_width())
+                }
+                // Still show the one that exists, and say what became of
+                // the other.
+                (Ok(found), Err(err)) | (Err(err), Ok(found)) => {
+                    outln!("{}", format_anime_details(&found.data, cli.title_language));
+                    eprintln!(
+                        "{} nothing to compare it with: {:#}",
+                        "warning:".yellow().bold(),
+                        err
+                    );
+                }
+                (Err(err_a), Err(err_b)) => {
+                    let mut summary = Summary::default();
+                    summary.failed(err_a);
+                    summary.failed(err_b);
+                    summary.finish("lookups")?;
+                }
+            }
+        }
+        Commands::Trending {
+            limit,
+            include_nsfw,
+        } => {
+            let limit = limit.or(config.limit).unwrap_or(DEFAULT_PAGE_LIMIT);
+            let mut trending = fetch_trending(&client, limit).await?;
+            warn_skipped(&trending);
+            let hidden_nsfw = if *include_nsfw || config.include_nsfw.unwrap_or(false) {
+                0
+            } else {
+                filter_nsfw(&mut trending.data)
+            };
+            outcome = Outcome::found(!trending.data.is_empty());
+            let display = DisplayOptions {
+                title_language: cli.title_language,
+                ..DisplayOptions::default()
+            };
+            display_anime_results(&trending.data, &display);
+            if hidden_nsfw > 0 {
+                outln!("\n{}", hidden_nsfw_note(hidden_nsfw));
+            }
+        }
+        Commands::Random {
+            min_rating,
+            subtype,
+            seed,
+            include_nsfw,
+        } => {
+            let mut rng = seed.map(Rng::seeded).unwrap_or_else(Rng::from_time);
+            let options = SearchOptions {
+                subtype: *subtype,
+                ..SearchOptions::default()
+            };
+            let include_nsfw = *include_nsfw || config.include_nsfw.unwrap_or(false);
+            let anime =
+                fetch_random(&client, &options, *min_rating, include_nsfw, &mut rng).await?;
+            outln!("{}", format_anime_details(&anime, cli.title_language));
+        }
+        Commands::Tui {
+            resume,
+            with_genres,
+            with_streaming,
+            include_nsfw,
+        } => {
+            let enrichments = Enrichments {
+                genres: *with_genres,
+                streaming: *with_streaming,
+            };
+            tui::run(
+                client,
+                *resume,
+                config.rating_bands(),
+                cli.title_language,
+                enrichments,
+                *include_nsfw || config.include_nsfw.unwrap_or(false),
+            )
+            .await?;
+        }
+        Commands::Episodes { id } => {
+            let anime = fetch_anime(&client, id).await?;
+            outln!(
+                "Episodes of {}",
+                anime.data.attributes.canonical_title.cyan().bold()
+            );
+
+            let episodes = fetch_episodes(&client, id).await?;
+            display_episodes(&episodes);
+        }
+        Commands::Related { id } => {
+            let (anime, related) =
+                future::join(fetch_anime(&client, id), fetch_related(&client, id)).await;
+            let anime = anime?;
+            outln!(
+                "Related to {}",
+                anime
+                    .data
+                    .attributes
+                    .display_title(cli.title_language)
+                    .cyan()
+                    .bold()
+            );
+            let related = related?;
+            outcome = Outcome::found(!related.is_empty());
+            display_related(&related, cli.title_language);
+        }
+        Commands::Add { id, status } => {
+            let anime = fetch_anime(&client, id).await?.data;
+            let title = anime.attributes.canonical_title.clone();
+
+            let mut watchlist = Watchlist::load()?;
+            let outcome = watchlist.add(WatchlistEntry {
+                id: anime.id,
+                title: title.clone(),
+                status: *status,
+                attributes: Some(anime.attributes),
+                progress: 0,
+                favorite: false,
+            });
+            watchlist.save()?;
+
+            let verb = match outcome {
+                AddOutcome::Added => "Added",
+                AddOutcome::Updated => "Updated",
+            };
+            outln!(
+                "{} {} as {}",
+                verb,
+                title.cyan().bold(),
+                status.label().yellow()
+            );
+        }
+        Commands::Remove { id } => {
+            let mut watchlist = Watchlist::load()?;
+            match watchlist.remove(id) {
+                Some(entry) => {
+                    watchlist.save()?;
+                    outln!("Removed {} from your watchlist", entry.title.cyan().bold());
+                }
+                None => outln!("{} is not on your watchlist", id.yellow()),
+            }
+        }
+        Commands::Progress { id, episodes } => {
+            let mut watchlist = Watchlist::load()?;
+            let Some(entry) = watchlist.set_progress(id, *episodes) else {
+                bail!(
+                    "{} is not on your watchlist; add it first with {} {}",
+                    id,
+                    "sabikani add".cyan(),
+                    id
+                );
+            };
+            let clamped = entry.progress < *episodes;
+            outln!(
+                "Progress on {}: {}{}",
+                entry.title.cyan().bold(),
+                entry.progress_label().yellow(),
+                if clamped {
+                    " (it has no more episodes than that)"
+                } else {
+                    ""
+                }
+            );
+            watchlist.save()?;
+        }
+        Commands::List { watching } => {
+            let mut watchlist = Watchlist::load()?;
+            display_watchlist(&client, &mut watchlist, *watching).await?;
+        }
+        Commands::History => {
+            display_history(&History::load()?);
+        }
+        Commands::Config { .. } | Commands::Completions { .. } => {
+            unreachable!("handled before loading the config")
+        }
+        Commands::Cache { action } => match action {
+            CacheAction::List => {
+                let cache = SearchCache::load()?;
+                let entries = cache.entries();
+                if entries.is_empty() {
+                    outln!("The search cache is empty.");
+                }
+                for (fetched_at, cached) in entries {
+                    let key = &cached.key;
+                    outln!(
+                        "{}  {:<8} {}  {}",
+                        cache::describe_age(cache::now().saturating_sub(fetched_at)),
+                        key.source,
+                        format!("{:?}", key.query).yellow(),
+                        key.filters.dimmed()
+                    );
+                }
+            }
+            CacheAction::Clear { source: None } => {
+                cache::clear_all()?;
+                outln!("{}", "Search cache cleared.".green());
+            }
+            CacheAction::Clear {
+                source: Some(source),
+            } => {
+                let mut cache = SearchCache::load()?;
+                let removed = cache.retain(|cached| cached.key.source != source.name());
+                cache.save()?;
+                outln!(
+                    "{}",
+                    format!("Removed {} cached {} search(es).", removed, source.name()).green()
+                );
+            }
+        },
+        Commands::Query { action } => match action {
+            QueryAction::Save { name, args } => {
+                // Catch typos now rather than on every later run.
+                let search = ["sabikani", "search"].into_iter().map(String::from);
+                if let Err(err) = Cli::try_parse_from(search.chain(args.iter().cloned())) {
+                    bail!("Not valid `search` arguments:\n{}", err.render());
+                }
+
+                let mut saved = SavedQueries::load()?;
+                let replaced = saved.insert(name, args.clone())?;
+                saved.save()?;
+                outln!(
+                    "{} {}: {}",
+                    if replaced { "Updated" } else { "Saved" }.green(),
+                    name.yellow().bold(),
+                    queries::expansion(args).cyan()
+                );
+            }
+            QueryAction::List => display_saved_queries(&SavedQueries::load()?),
+            QueryAction::Remove { name } => {
+                let mut saved = SavedQueries::load()?;
+                if !saved.remove(name) {
+                    bail!("There is no saved query named `{}`", name);
+                }
+                saved.save()?;
+                outln!("{} {}", "Removed".green(), name.yellow().bold());
+            }
+            QueryAction::Run { .. } => unreachable!("expanded into a search before running"),
+        },
+    }
+    Ok(outcome)
+}
+
+/// `search --interactive`: asks for a result number and prints its details,
+/// again and again, until `q`, an empty line or end of input.
+fn browse_details(results: &[AnimeData], language: TitleLanguage) -> Result<()> {
+    if results.is_empty() {
+        return Ok(());
+    }
+    let mut line = String::new();
+    loop {
+        // On stderr, like other prompts, so the details alone go to stdout.
+        eprint!("\nView details for # (or q): ");
+        line.clear();
+        if std::io::stdin().read_line(&mut line)? == 0 {
+            eprintln!();
+            return Ok(());
+        }
+        let answer = line.trim();
+        if answer.is_empty() || answer.eq_ignore_ascii_case("q") {
+            return Ok(());
+        }
+        let picked = answer
+            .parse::<u32>()
+            .ok()
+            .filter(|&n| n >= 1)
+            .with_context(|| format!("\"{}\" is not a result number", answer))
+            .and_then(|n| pick_result(results, n));
+        match picked {
+            Ok(anime) => outln!("{}", format_anime_details(anime, language)),
+            Err(err) => eprintln!("{} {:#}", "error:".red().bold(), err),
+        }
+    }
+}
+
+/// Prints a `--pick`ed result: its id with `quiet`, else its details.
+fn display_pick(
+    anime: &AnimeData,
+    quiet: bool,
+    format: OutputFormat,
+    language: TitleLanguage,
+) -> Result<()> {
+    if quiet {
+        outln!("{}", anime.id);
+    } else if format == OutputFormat::Json {
+        outln!("{}", serde_json::to_string_pretty(anime)?);
+    } else {
+        outln!("{}", format_anime_details(anime, language));
+    }
+    Ok(())
+}
+
+/// The `--pick`ed result, numbered from 1 as in the listing.
+fn pick_result(results: &[AnimeData], n: u32) -> Result<&AnimeData> {
+    match results.len() {
+        0 => bail!("There are no results to pick from"),
+        count => results.get(n as usize - 1).with_context(|| {
+            format!(
+                "There is no result #{}; the search found {} (pick 1-{})",
+                n, count, count
+            )
+        }),
+    }
+}
+
+/// Builds a search URL against the Kitsu API at `base_url`, percent-encoding
+/// every parameter so queries like "Fate/Stay Night & more" arrive intact.
+fn search_url(base_url: &str, query: &str, options: &SearchOptions) -> Result<String> {
+    Ok(KitsuQuery::new(base_url, options.media.path())?
+        .text(query)
+        .page(options.page)
+        .season(options.season)
+        .year(options.year)
+        .subtype(options.subtype)
+        .status(options.airing.then_some("current"))
+        .max_age_rating(options.max_age_rating)
+        .sort(options.sort)
+        .build())
+}
+
+/// Searches the Kitsu API at `base_url` (normally `KITSU_API_URL`). Kitsu
+/// serves at most `KITSU_MAX_PAGE_LIMIT` results per request, so larger
+/// limits are fetched as consecutive pages and combined.
+async fn search_anime(
+    client: &HttpClient,
+    base_url: &str,
+    query: &str,
+    options: &SearchOptions,
+) -> Result<AnimeResponse> {
+    if offline::enabled() {
+        return offline::search(query, options);
+    }
+
+    let mut combined: Option<AnimeResponse> = None;
+    let mut remaining = options.page.limit;
+    let mut offset = options.page.offset;
+
+    while remaining > 0 {
+        let limit = remaining.min(KITSU_MAX_PAGE_LIMIT);
+        let chunk_options = SearchOptions {
+            page: Page { limit, offset },
+            ..options.clone()
+        };
+        let url = search_url(base_url, query, &chunk_options)?;
+
+        let response = client.fetch_with_retry(&url).await?;
+
+        let chunk = response
+            .json::<RawAnimeResponse>()
+            .await
+            .context("Failed to parse anime data")?
+            .into_response();
+
+        let received = chunk.data.len() + chunk.skipped;
+        log::debug!(
+            "Got {} result(s) at offset {}, skipped {}",
+            chunk.data.len(),
+            offset,
+            chunk.skipped
+        );
+        let exhausted = (received as u32) < limit || !chunk.has_next_page();
+        match &mut combined {
+            None => combined = Some(chunk),
+            Some(combined) => {
+                combined.data.extend(chunk.data);
+                combined.skipped += chunk.skipped;
+                // The last page's links say whether anything lies beyond it.
+                combined.links = chunk.links;
+            }
+        }
+
+        if exhausted {
+            break;
+        }
+        remaining -= limit;
+        offset += limit;
+    }
+
+    Ok(combined.unwrap_or_default())
+}
+
+/// The requests `search_anime` would send, for `--dry-run`: one per page
+/// of up to `KITSU_MAX_PAGE_LIMIT` results. A real search stops early at the
+/// page where Kitsu runs out of results.
+fn search_anime_requests(
+    client: &HttpClient,
+    base_url: &str,
+    query: &str,
+    options: &SearchOptions,
+) -> Result<Vec<String>> {
+    let mut requests = Vec::new();
+    let mut remaining = options.page.limit;
+    let mut offset = options.page.offset;
+
+    while remaining > 0 {
+        let limit = remaining.min(KITSU_MAX_PAGE_LIMIT);
+        let chunk_options = SearchOptions {
+            page: Page { limit, offset },
+            ..options.clone()
+        };
+        let url = search_url(base_url, query, &chunk_options)?;
+        requests.push(client.describe_get(&url)?);
+        remaining -= limit;
+        offset += limit;
+    }
+
+    Ok(requests)
+}
+
+/// Pages of candidates `fetch_random` tries before giving up on finding one
+/// rated at least `--min-rating`.
+const RANDOM_ATTEMPTS: usize = 5;
+
+/// Picks a random anime matching `options`. Kitsu has no random endpoint, so
+/// this reads the total from `meta.count`, fetches the page around a random
+/// offset and picks an entry from it that meets `min_rating`, passing over
+/// adult entries unless `include_nsfw`.
+async fn fetch_random(
+    client: &HttpClient,
+    options: &SearchOptions,
+    min_rating: Option<u8>,
+    include_nsfw: bool,
+    rng: &mut Rng,
+) -> Result<AnimeData> {
+    let probe = SearchOptions {
+        page: Page {
+            limit: 1,
+            offset: 0,
+        },
+        ..options.clone()
+    };
+    let count = search_anime(client, client.kitsu_api(), "", &probe)
+        .await?
+        .meta
+        .and_then(|meta| meta.count)
+        .unwrap_or(0);
+    if count == 0 {
+        bail!("No anime match those filters");
+    }
+
+    for _ in 0..RANDOM_ATTEMPTS {
+        let offset = rng.below(count) as u32;
+        let page = SearchOptions {
+            page: Page {
+                limit: KITSU_MAX_PAGE_LIMIT,
+                offset: offset - offset % KITSU_MAX_PAGE_LIMIT,
+            },
+            ..options.clone()
+        };
+        let mut candidates = search_anime(client, client.kitsu_api(), "", &page)
+            .await?
+            .data;
+
+        // Unrated entries can't be shown to meet a minimum.
+        filter_by_rating(&mut candidates, min_rating, min_rating.is_some());
+        if !include_nsfw {
+            filter_nsfw(&mut candidates);
+        }
+        if !candidates.is_empty() {
+            let pick = rng.below(candidates.len() as u64) as usize;
+            return Ok(candidates.swap_remove(pick));
+        }
+    }
+
+    match min_rating {
+        Some(min_rating) => bail!(
+            "Couldn't find an anime rated {} or higher after {} tries",
+            min_rating,
+            RANDOM_ATTEMPTS
+        ),
+        None => bail!(
+            "Couldn't find an anime not marked as adult content after {} tries",
+            RANDOM_ATTEMPTS
+        ),
+    }
+}
+
+async fn fetch_trending(client: &HttpClient, limit: u32) -> Result<AnimeResponse> {
+    if offline::enabled() {
+        let options = SearchOptions {
+            page: Page { limit, offset: 0 },
+            ..SearchOptions::default()
+        };
+        return offline::search("", &options);
+    }
+
+    let url = KitsuQuery::new(client.kitsu_api(), "trending/anime")?
+        .param("limit", &limit.to_string())
+        .build();
+
+    let response = client.fetch_with_retry(&url).await?;
+
+    let trending = response
+        .json::<RawAnimeResponse>()
+        .await
+        .context("Failed to parse trending anime")?
+        .into_response();
+
+    Ok(trending)
+}
+
+/// Drops entries rated below `min_rating` (a rating equal to it passes),
+/// and unrated ones too when `drop_unrated`. Returns how many were dropped.
+fn filter_by_rating(
+    anime_list: &mut Vec<AnimeData>,
+    min_rating: Option<u8>,
+    drop_unrated: bool,
+) -> usize {
+    let before = anime_list.len();
+    anime_list.retain(|anime| match anime.attributes.rating_value() {
+        Some(rating) => min_rating.is_none_or(|min| rating >= f64::from(min)),
+        None => !drop_unrated,
+    });
+    before - anime_list.len()
+}
+
+/// Drops entries that didn't start airing within `aired`, and those with no
+/// known start date unless the range is open. Returns how many were dropped.
+fn filter_by_start(anime_list: &mut Vec<AnimeData>, aired: dates::Range) -> usize {
+    if aired.is_open() {
+        return 0;
+    }
+    let before = anime_list.len();
+    anime_list.retain(|anime| {
+        anime
+            .attributes
+            .start_date
+            .as_deref()
+            .and_then(dates::parse)
+            .is_some_and(|start| aired.contains(start))
+    });
+    before - anime_list.len()
+}
+
+/// `--min-episodes` and `--max-episodes`, both inclusive.
+#[derive(Debug, Clone, Copy, Default)]
+struct EpisodeRange {
+    min: Option<u16>,
+    max: Option<u16>,
+    /// Keep entries whose episode count isn't known.
+    include_unknown: bool,
+}
+
+/// Drops entries with an episode count outside `range`, and those with no
+/// known count unless `range.include_unknown`. Returns how many were
+/// dropped.
+fn filter_by_episodes(anime_list: &mut Vec<AnimeData>, range: EpisodeRange) -> usize {
+    if range.min.is_none() && range.max.is_none() {
+        return 0;
+    }
+    let before = anime_list.len();
+    anime_list.retain(|anime| match anime.attributes.episode_count {
+        Some(count) => {
+            range.min.is_none_or(|min| count >= min) && range.max.is_none_or(|max| count <= max)
+        }
+        None => range.include_unknown,
+    });
+    before - anime_list.len()
+}
+
+/// Drops entries with fewer than `min` attributes filled in. Returns how
+/// many were dropped.
+fn filter_by_completeness(anime_list: &mut Vec<AnimeData>, min: Option<u8>) -> usize {
+    let Some(min) = min else {
+        return 0;
+    };
+    let before = anime_list.len();
+    anime_list.retain(|anime| anime.attributes.completeness_score() >= min);
+    before - anime_list.len()
+}
+
+/// Puts the most complete entries first, keeping the order among equals.
+fn sort_by_completeness(anime_list: &mut [AnimeData]) {
+    anime_list.sort_by_key(|anime| Reverse(anime.attributes.completeness_score()));
+}
+
+/// Drops entries the source marks as adult content. Returns how many were
+/// dropped.
+fn filter_nsfw(anime_list: &mut Vec<AnimeData>) -> usize {
+    let before = anime_list.len();
+    anime_list.retain(|anime| anime.attributes.nsfw != Some(true));
+    before - anime_list.len()
+}
+
+/// Says how many results `filter_nsfw` dropped and how to keep them.
+fn hidden_nsfw_note(hidden: usize) -> String {
+    format!(
+        "{} adult result(s) hidden; use {} to show them.",
+        hidden,
+        "--include-nsfw".cyan()
+    )
+}
+
+/// "Showing 1-10 of 342 results — use --page 2 for more." when Kitsu sent a
+/// total, or just the next-page hint when it didn't. The range and total are
+/// Kitsu's, so when `dropped` of the fetched results were filtered out here,
+/// the footer says how many were left: "Showing 4 of results 1-10 of 342".
+fn pagination_footer(
+    results: &AnimeResponse,
+    dropped: usize,
+    window: &Page,
+    page: u32,
+) -> Option<String> {
+    let next = format!("--page {}", page + 1).cyan();
+    let total = results.meta.as_ref().and_then(|meta| meta.count);
+    let fetched = results.data.len() + dropped;
+
+    match total {
+        Some(total) if fetched > 0 => {
+            let first = u64::from(window.offset) + 1;
+            let last = (u64::from(window.offset) + fetched as u64).min(total);
+            let mut footer = if dropped > 0 {
+                format!(
+                    "Showing {} of results {}-{} of {} ({} filtered out)",
+                    results.data.len(),
+                    first,
+                    last,
+                    total,
+                    dropped
+                )
+            } else {
+                format!("Showing {}-{} of {} results", first, last, total)
+            };
+            if results.has_next_page() {
+                footer.push_str(&format!(
+                    " {} use {} for more.",
+                    glyphs::current().dash,
+                    next
+                ));
+            } else {
+                footer.push('.');
+            }
+            Some(footer)
+        }
+        _ => results
+            .has_next_page()
+            .then(|| format!("More results available, run with {}", next)),
+    }
+}
+
+/// Collapses entries whose titles match ignoring case and surrounding
+/// whitespace into one, keeping whichever has the most attributes filled in
+/// (then the highest rating) at the position of the first. Returns how many
+/// entries were removed.
+fn dedup_results(anime_list: &mut Vec<AnimeData>) -> usize {
+    fn rank(anime: &AnimeData) -> (u8, f64) {
+        let attrs = &anime.attributes;
+        (
+            attrs.completeness_score(),
+            attrs.rating_value().unwrap_or(-1.0),
+        )
+    }
+
+    let before = anime_list.len();
+    let mut kept: Vec<AnimeData> = Vec::with_capacity(before);
+    let mut positions: HashMap<String, usize> = HashMap::new();
+
+    for anime in anime_list.drain(..) {
+        let key = anime.attributes.canonical_title.trim().to_lowercase();
+        match positions.get(&key) {
+            Some(&i) => {
+                if rank(&anime) > rank(&kept[i]) {
+                    kept[i] = anime;
+                }
+            }
+            None => {
+                positions.insert(key, kept.len());
+                kept.push(anime);
+            }
+        }
+    }
+
+    *anime_list = kept;
+    before - anime_list.len()
+}
+
+/// Tells the user how many results were dropped for not parsing.
+fn warn_skipped(response: &AnimeResponse) {
+    if response.skipped > 0 {
+        eprintln!(
+            "{} skipped {} result(s) that came back in an unexpected format",
+            "warning:".yellow().bold(),
+            response.skipped
+        );
+    }
+}
+
+/// Searches `source`, through the disk cache when `cache_ttl` is set.
+async fn run_search(
+    source: &impl AnimeSource,
+    query: &str,
+    options: &SearchOptions,
+    cache_ttl: Option<Duration>,
+) -> Result<AnimeResponse> {
+    match cache_ttl {
+        Some(ttl) => cached_search(source, query, options, ttl).await,
+        None => source.search(query, options).await,
+    }
+}
+
+/// Reads one query per line from stdin, trimmed, skipping blank lines.
+fn read_queries() -> Result<Vec<String>> {
+    let mut queries = Vec::new();
+    for line in std::io::stdin().lines() {
+        let line = line.context("Failed to read queries from stdin")?;
+        let query = line.trim();
+        if !query.is_empty() {
+            queries.push(query.to_string());
+        }
+    }
+    Ok(queries)
+}
+
+/// Settings shared by every search in a `search --stdin` batch.
+struct Batch<'a, S> {
+    backend: &'a S,
+    options: &'a SearchOptions,
+    cache_ttl: Option<Duration>,
+    dedup: bool,
+    min_rating: Option<u8>,
+    drop_unrated: bool,
+    episodes: EpisodeRange,
+    min_completeness: Option<u8>,
+    by_completeness: bool,
+    aired: dates::Range,
+    include_nsfw: bool,
+}
+
+/// One query's outcome in a batch. A failed search keeps its error instead
+/// of aborting the rest of the batch.
+#[derive(Debug, Serialize)]
+struct BatchResult {
+    query: String,
+    results: Vec<AnimeData>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl<S: AnimeSource> Batch<'_, S> {
+    /// Runs every query, at most `BATCH_CONCURRENCY` at a time, keeping the
+    /// input order, and tallies how the searches went.
+    async fn run(&self, queries: Vec<String>) -> (Vec<BatchResult>, Summary) {
+        let outcomes: Vec<(BatchResult, Result<()>)> = stream::iter(queries)
+            .map(|query| async move {
+                match run_search(self.backend, &query, self.options, self.cache_ttl).await {
+                    Ok(mut response) => {
+                        if self.dedup {
+                            dedup_results(&mut response.data);
+                        }
+                        filter_by_rating(&mut response.data, self.min_rating, self.drop_unrated);
+                        filter_by_start(&mut response.data, self.aired);
+                        filter_by_episodes(&mut response.data, self.episodes);
+                        filter_by_completeness(&mut response.data, self.min_completeness);
+                        if self.by_completeness {
+                            sort_by_completeness(&mut response.data);
+                        }
+                        if !self.include_nsfw {
+                            filter_nsfw(&mut response.data);
+                        }
+                        let result = BatchResult {
+                            query,
+                            results: response.data,
+                            error: None,
+                        };
+                        (result, Ok(()))
+                    }
+                    Err(err) => {
+                        let result = BatchResult {
+                            query,
+                            results: Vec::new(),
+                            error: Some(format!("{:#}", err)),
+                        };
+                        (result, Err(err))
+                    }
+                }
+            })
+            .buffered(BATCH_CONCURRENCY)
+            .collect()
+            .await;
+
+        let mut summary = Summary::default();
+        let mut results = Vec::with_capacity(outcomes.len());
+        for (result, outcome) in outcomes {
+            match outcome {
+                Ok(()) => summary.succeeded(),
+                Err(err) => summary.failed(err),
+            }
+            results.push(result);
+        }
+        (results, summary)
+    }
+}
+
+enum BatchOutput<'a> {
+    Text(&'a DisplayOptions),
+    Table(&'a DisplayOptions),
+    Json,
+    /// Like the single-search CSV, with the query as an extra first column.
+    Csv(TitleLanguage),
+    Ids,
+}
+
+fn display_batch(results: &[BatchResult], output: BatchOutput) -> Result<()> {
+    if let BatchOutput::Json = output {
+        let json = serde_json::to_string_pretty(results)?;
+        outln!("{}", json);
+        return Ok(());
+    }
+
+    let mut csv = String::new();
+    if let BatchOutput::Csv(_) = output {
+        csv::write_record(&mut csv, std::iter::once("query").chain(CSV_COLUMNS));
+    }
+
+    for result in results {
+        if let Some(error) = &result.error {
+            eprintln!(
+                "{} search for \"{}\" failed: {}",
+                "warning:".yellow().bold(),
+                result.query,
+                error
+            );
+            continue;
+        }
+
+        match output {
+            BatchOutput::Text(display) | BatchOutput::Table(display) => {
+                outln!(
+                    "\n{} {}",
+                    "QUERY:".color(theme::current().header).bold(),
+                    result.query.yellow()
+                );
+                let display = DisplayOptions {
+                    highlight: Some(result.query.clone()),
+                    ..display.clone()
+                };
+                if let BatchOutput::Table(_) = output {
+                    display_anime_table(&result.results, &display);
+                } else {
+                    display_anime_results(&result.results, &display);
+                }
+            }
+            BatchOutput::Ids => {
+                for anime in &result.results {
+                    outln!("{}", anime.id);
+                }
+            }
+            BatchOutput::Csv(language) => {
+                for anime in &result.results {
+                    let fields = csv_fields(anime, language);
+                    csv::write_record(
+                        &mut csv,
+                        std::iter::once(result.query.as_str())
+                            .chain(fields.iter().map(String::as_str)),
+                    );
+                }
+            }
+            BatchOutput::Json => unreachable!("handled above"),
+        }
+    }
+
+    if let BatchOutput::Csv(_) = output {
+        out!("{}", csv);
+    }
+    Ok(())
+}
+
+/// The header row of `--format csv`.
+const CSV_COLUMNS: [&str; 8] = [
+    "id",
+    "title",
+    "rating",
+    "episode_count",
+    "status",
+    "start_date",
+    "end_date",
+    "synopsis",
+];
+
+/// One CSV row, in `CSV_COLUMNS` order. Missing values are left empty.
+fn csv_fields(anime: &AnimeData, language: TitleLanguage) -> [String; 8] {
+    let attrs = &anime.attributes;
+    let text = |value: &Option<String>| value.clone().unwrap_or_default();
+    [
+        anime.id.clone(),
+        attrs.display_title(language).to_string(),
+        text(&attrs.average_rating),
+        attrs
+            .episode_count
+            .map(|count| count.to_string())
+            .unwrap_or_default(),
+        text(&attrs.status),
+        text(&attrs.start_date),
+        text(&attrs.end_date),
+        text(&attrs.synopsis),
+    ]
+}
+
+/// Serves a search from the disk cache when a fresh entry exists, otherwise
+/// fetches it and writes the response through to the cache.
+async fn cached_search(
+    source: &impl AnimeSource,
+    query: &str,
+    options: &SearchOptions,
+    ttl: Duration,
+) -> Result<AnimeResponse> {
+    let mut cache = SearchCache::load()?;
+    let key = SearchKey::new(source.name(), query, options);
+    let hash = key.hash();
+
+    if let Some(cached) = cache.get(&hash, ttl) {
+        return Ok(cached.response.clone());
+    }
+
+    let response = source.search(query, options).await?;
+    let cached = CachedSearch {
+        key,
+        response: response.clone(),
+    };
+    cache.insert(hash, cached);
+    cache.save()?;
+
+    Ok(response)
+}
+
+async fn fetch_categories(client: &HttpClient, media: Media, id: &str) -> Result<Vec<String>> {
+    let url = KitsuQuery::item(client.kitsu_api(), media.path(), id)?
+        .related("categories")
+        .limit(MAX_GENRES as u32)
+        .build();
+
+    let response = client.fetch_with_retry(&url).await?;
+
+    let categories = response
+        .json::<CategoriesResponse>()
+        .await
+        .context("Failed to parse categories")?;
+
+    Ok(categories
+        .data
+        .into_iter()
+        .map(|c| c.attributes.title)
+        .collect())
+}
+
+async fn fetch_anime(client: &HttpClient, id: &str) -> Result<SingleAnimeResponse> {
+    if offline::enabled() {
+        return match offline::find(id)? {
+            Some(data) => Ok(SingleAnimeResponse { data }),
+            None => bail!("No anime found with ID {} in the offline samples", id),
+        };
+    }
+
+    let url = KitsuQuery::item(client.kitsu_api(), "anime", id)?.build();
+
+    let response = match client.fetch_with_retry(&url).await {
+        Err(err)
+            if err
+                .downcast_ref::<StatusError>()
+                .is_some_and(|err| err.status == StatusCode::NOT_FOUND) =>
+        {
+            bail!("No anime found with ID {}", id);
+        }
+        response => response?,
+    };
+
+    let anime = response
+        .json::<SingleAnimeResponse>()
+        .await
+        .with_context(|| format!("Failed to parse anime with ID {}", id))?;
+
+    Ok(anime)
+}
+
+/// Fetches every episode of an anime, a page at a time until Kitsu has no
+/// next page, sorted by episode number. Each page is requested by offset from
+/// the configured API root rather than by following `links.next`: that link
+/// is absolute, so it would leave a mirror and take the token and headers to
+/// whichever host it names.
+async fn fetch_episodes(client: &HttpClient, id: &str) -> Result<Vec<EpisodeData>> {
+    let mut episodes = Vec::new();
+    let mut offset = 0;
+
+    loop {
+        let url = KitsuQuery::item(client.kitsu_api(), "anime", id)?
+            .related("episodes")
+            .page(Page {
+                limit: KITSU_MAX_PAGE_LIMIT,
+                offset,
+            })
+            .param("sort", "number")
+            .build();
+        let response = client.fetch_with_retry(&url).await?;
+
+        let page = response
+            .json::<EpisodesResponse>()
+            .await
+            .context("Failed to parse episode list")?;
+
+        let fetched = page.data.len() as u32;
+        episodes.extend(page.data);
+        if fetched == 0 || page.links.and_then(|links| links.next).is_none() {
+            break;
+        }
+        offset += fetched;
+    }
+
+    episodes.sort_by_key(|e| e.attributes.number.unwrap_or(u32::MAX));
+    Ok(episodes)
+}
+
+fn display_episodes(episodes: &[EpisodeData]) {
+    if episodes.is_empty() {
+        outln!("{}", "No episodes listed for this anime.".red());
+        return;
+    }
+
+    let width = output_width();
+    outln!("{}", theme::current().rule_line(width));
+    outln!(
+        "{:>5}  {:<12}  {}",
+        "#".bold(),
+        "Aired".bold(),
+        "Title".bold()
+    );
+    outln!("{}", theme::current().separator_line(width));
+
+    for episode in episodes {
+        let attrs = &episode.attributes;
+        let number = attrs
+            .number
+            .map(|n| n.to_string())
+            .unwrap_or_else(|| "?".to_string());
+        let airdate = attrs
+            .airdate
+            .as_deref()
+            .map_or_else(|| "unknown".to_string(), locale::date);
+        let title = attrs.canonical_title.as_deref().unwrap_or("Untitled");
+
+        outln!(
+            "{}  {}  {}",
+            format!("{:>5}", number).yellow(),
+            format!("{:<12}", airdate).blue(),
+            title.cyan()
+        );
+    }
+}
+
+async fn fetch_streaming_links(client: &HttpClient, id: &str) -> Result<StreamingLinksResponse> {
+    let url = KitsuQuery::item(client.kitsu_api(), "anime", id)?
+        .related("streaming-links")
+        .include("streamer")
+        .build();
+
+    let response = client.fetch_with_retry(&url).await?;
+
+    let links = response
+        .json::<StreamingLinksResponse>()
+        .await
+        .context("Failed to parse streaming links")?;
+
+    Ok(links)
+}
+
+fn display_streaming_links(links: &StreamingLinksResponse) {
+    if links.data.is_empty() {
+        outln!("{}", "No streaming sources found for this anime.".red());
+        return;
+    }
+
+    for link in &links.data {
+        let streamer = links.streamer_name(link);
+
+        outln!(
+            "  {}: {}",
+            streamer.yellow().bold(),
+            link.attributes.url.blue()
+        );
+
+        if !link.attributes.subs.is_empty() {
+            outln!("    Subs: {}", link.attributes.subs.join(", "));
+        }
+        if !link.attributes.dubs.is_empty() {
+            outln!("    Dubs: {}", link.attributes.dubs.join(", "));
+        }
+    }
+}
+
+/// Every relationship of an anime, paged through by offset as in
+/// `fetch_episodes`, paired with the media it points to. Relationships whose media wasn't included are dropped.
+async fn fetch_related(client: &HttpClient, id: &str) -> Result<Vec<(String, RelatedMedia)>> {
+    let mut related = Vec::new();
+    let mut offset = 0;
+
+    loop {
+        let url = KitsuQuery::item(client.kitsu_api(), "anime", id)?
+            .related("media-relationships")
+            .include("destination")
+            .page(Page {
+                limit: KITSU_MAX_PAGE_LIMIT,
+                offset,
+            })
+            .build();
+        let response = client.fetch_with_retry(&url).await?;
+
+        let page = response
+            .json::<MediaRelationshipsResponse>()
+            .await
+            .context("Failed to parse related media")?;
+
+        let fetched = page.data.len() as u32;
+        for relationship in page.data {
+            let found = relationship
+                .relationships
+                .destination
+                .data
+                .and_then(|target| {
+                    page.included
+                        .iter()
+                        .find(|media| media.id == target.id && media.kind == target.kind)
+                });
+            if let Some(media) = found {
+                related.push((relationship.attributes.role, media.clone()));
+            }
+        }
+        if fetched == 0 || page.links.and_then(|links| links.next).is_none() {
+            break;
+        }
+        offset += fetched;
+    }
+
+    Ok(related)
+}
+
+/// Lists related media grouped under each role, in `RELATION_ROLES` order
+/// with any roles Kitsu adds later at the end.
+fn display_related(related: &[(String, RelatedMedia)], language: TitleLanguage) {
+    if related.is_empty() {
+        outln!("{}", "No related anime or manga listed.".red());
+        return;
+    }
+
+    let rank = |role: &str| {
+        RELATION_ROLES
+            .iter()
+            .position(|known| *known == role)
+            .unwrap_or(RELATION_ROLES.len())
+    };
+    let mut roles: Vec<&str> = related.iter().map(|(role, _)| role.as_str()).collect();
+    roles.sort_by_key(|role| (rank(role), *role));
+    roles.dedup();
+
+    for role in roles {
+        outln!(
+            "\n{}",
+            role_label(role).color(theme::current().header).bold()
+        );
+        for (_, media) in related.iter().filter(|(r, _)| r == role) {
+            let attrs = &media.attributes;
+            let mut line = format!(
+                "  {} (ID: {})",
+                attrs.display_title(language).color(theme::current().title),
+                media.id
+            );
+            let mut details = vec![media.kind.clone()];
+            details.extend(attrs.subtype.clone());
+            details.extend(
+                attrs
+                    .start_date
+                    .as_deref()
+                    .map(|date| date.split('-').next().unwrap_or(date).to_string()),
+            );
+            line.push_str(&format!(" - {}", details.join(", ")));
+            if let Some(status) = &attrs.status {
+                line.push_str(&format!(" - {}", format_status(status)));
+            }
+            outln!("{}", line);
+        }
+    }
+}
+
+/// "side_story" as "Side story".
+fn role_label(role: &str) -> String {
+    let words = role.replace('_', " ");
+    let mut chars = words.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => words,
+    }
+}
+
+fn display_anime_results(anime_list: &[AnimeData], options: &DisplayOptions) {
+    if anime_list.is_empty() {
+        outln!("{}", "No results found.".red());
+        return;
+    }
+
+    let width = output_width();
+    results_heading();
+
+    if options.compact {
+        for (i, anime) in anime_list.iter().enumerate() {
+            display_anime_line(i + 1, anime, options);
+        }
+        return;
+    }
+
+    outln!("{}", theme::current().rule_line(width));
+
+    for (i, anime) in anime_list.iter().enumerate() {
+        display_anime_entry(i + 1, anime, width, options);
+        outln!("{}", theme::current().separator_line(width));
+    }
+}
+
+/// `--format table`: the same results, one aligned row each.
+fn display_anime_table(anime_list: &[AnimeData], options: &DisplayOptions) {
+    if anime_list.is_empty() {
+        outln!("{}", "No results found.".red());
+        return;
+    }
+    results_heading();
+    table::display(anime_list, options, output_width());
+}
+
+fn results_heading() {
+    if offline::enabled() {
+        outln!(
+            "\n{}",
+            "SEARCH RESULTS (offline samples):"
+                .color(theme::current().header)
+                .bold()
+        );
+    } else {
+        outln!(
+            "\n{}",
+            "SEARCH RESULTS:".color(theme::current().header).bold()
+        );
+    }
+}
+
+/// Prints the watchlist, or with `watching` only the entries marked as
+/// watching, refreshing each entry from Kitsu. Entries that can't be fetched
+/// (e.g. offline) fall back to the attributes saved with them.
+async fn display_watchlist(
+    client: &HttpClient,
+    watchlist: &mut Watchlist,
+    watching: bool,
+) -> Result<()> {
+    if watchlist.entries.is_empty() {
+        outln!(
+            "Your watchlist is empty. Add something with {} <anime_id>",
+            "sabikani add".cyan()
+        );
+        return Ok(());
+    }
+    let shown = |entry: &WatchlistEntry| !watching || entry.status == WatchStatus::Watching;
+    if !watchlist.entries.iter().any(shown) {
+        outln!("Nothing on your watchlist is marked as watching.");
+        return Ok(());
+    }
+
+    let width = output_width();
+    let options = DisplayOptions::default();
+    let mut refreshed = false;
+
+    outln!("\n{}", "WATCHLIST:".color(theme::current().header).bold());
+    outln!("{}", theme::current().rule_line(width));
+
+    for (i, entry) in watchlist.entries.iter_mut().enumerate() {
+        if !shown(entry) {
+            continue;
+        }
+        match fetch_anime(client, &entry.id).await {
+            Ok(fresh) => {
+                entry.title = fresh.data.attributes.canonical_title.clone();
+                entry.attributes = Some(fresh.data.attributes);
+                refreshed = true;
+            }
+            Err(_) if entry.attributes.is_some() => {}
+            Err(err) => {
+                outln!(
+                    "{}. {} (ID: {}) - {}",
+                    (i + 1).to_string().yellow().bold(),
+                    entry.title.cyan().bold(),
+                    entry.id,
+                    format!("unavailable: {}", err).red()
+                );
+                outln!("{}", theme::current().separator_line(width));
+                continue;
+            }
+        }
+
+        if let Some(attributes) = &entry.attributes {
+            let anime = AnimeData {
+                id: entry.id.clone(),
+                attributes: attributes.clone(),
+                categories: Vec::new(),
+                streamers: Vec::new(),
+                source: None,
+            };
+            display_anime_entry(i + 1, &anime, width, &options);
+        }
+        outln!("  List status: {}", entry.status.label().yellow());
+        if entry.progress > 0 || entry.status == WatchStatus::Watching {
+            outln!("  Progress: {}", entry.progress_label().yellow());
+        }
+        outln!("{}", theme::current().separator_line(width));
+    }
+
+    if refreshed {
+        watchlist.save()?;
+    }
+
+    Ok(())
+}
+
+/// The one-line summary of an anime's metadata, e.g.
+/// `Rating: 82.5/100 | Type: TV | Episodes: 26`.
+fn info_line(attrs: &AnimeAttributes) -> String {
+    let mut info = vec![];
+
+    if let Some(rating) = &attrs.average_rating {
+        info.push(format!("Rating: {}/100", rating));
+    }
+    info.extend(info_parts(attrs));
+
+    info.join(" | ")
+}
+
+/// Rank and audience figures, e.g. "Popularity #12", "Users: 1,234,567".
+fn popularity_parts(attrs: &AnimeAttributes) -> Vec<String> {
+    let mut parts = vec![];
+
+    if let Some(rank) = attrs.popularity_rank {
+        parts.push(format!("Popularity #{}", locale::count(rank.into())));
+    }
+    if let Some(rank) = attrs.rating_rank {
+        parts.push(format!("Rating #{}", locale::count(rank.into())));
+    }
+    if let Some(users) = attrs.user_count {
+        parts.push(format!("Users: {}", locale::count(users)));
+    }
+    if let Some(favorites) = attrs.favorites_count {
+        parts.push(format!("Favorites: {}", locale::count(favorites)));
+    }
+
+    parts
+}
+
+/// Everything in `info_line` after the rating, for callers that style the
+/// rating themselves.
+fn info_parts(attrs: &AnimeAttributes) -> Vec<String> {
+    let mut info = vec![];
+
+    if let Some(subtype) = &attrs.subtype {
+        info.push(format!("Type: {}", subtype));
+    }
+
+    if let Some(age_rating) = attrs.age_rating_label() {
+        info.push(format!("Age: {}", age_rating));
+    }
+
+    if let Some(eps) = attrs.episode_count {
+        info.push(format!("Episodes: {}", eps));
+    }
+
+    if let Some(chapters) = attrs.chapter_count {
+        info.push(format!("Chapters: {}", chapters));
+    }
+
+    if let Some(volumes) = attrs.volume_count {
+        info.push(format!("Volumes: {}", volumes));
+    }
+
+    if let Some(status) = &attrs.status {
+        info.push(format!("Status: {}", status_label(status)));
+    }
+
+    if let Some(start) = &attrs.start_date {
+        info.push(format!(
+            "Aired: {}",
+            dates::airing_period(start, attrs.end_date.as_deref())
+        ));
+    }
+
+    info
+}
+
+/// The full plain-text detail view: title, info line, poster and synopsis,
+/// matching what the TUI's details tab shows.
+fn format_anime_details(anime: &AnimeData, language: TitleLanguage) -> String {
+    let attrs = &anime.attributes;
+    let mut details = format!("{} (ID: {})\n", attrs.display_title(language), anime.id);
+
+    let alternatives = attrs.alternative_titles(language);
+    if !alternatives.is_empty() {
+        details.push_str(&format!("Also known as: {}\n", alternatives.join(", ")));
+    }
+
+    let info = info_line(attrs);
+    if !info.is_empty() {
+        details.push_str(&format!("{}\n", info));
+    }
+
+    let popularity = popularity_parts(attrs);
+    if !popularity.is_empty() {
+        details.push_str(&format!("{}\n", popularity.join(" | ")));
+    }
+
+    if !anime.categories.is_empty() {
+        details.push_str(&format!("Genres: {}\n", anime.categories.join(", ")));
+    }
+
+    if let Some(poster) = attrs
+        .poster_image
+        .as_ref()
+        .and_then(|p| p.url(ImageSize::Medium))
+    {
+        details.push_str(&format!("Poster: {}\n", poster));
+    }
+
+    let synopsis = attrs
+        .synopsis
+        .as_deref()
+        .unwrap_or("No synopsis available.");
+    details.push_str(&format!("\n{}", synopsis));
+
+    details
+}
+
+fn display_history(history: &History) {
+    if history.entries.is_empty() {
+        outln!("No searches yet.");
+        return;
+    }
+
+    outln!(
+        "\n{}",
+        "RECENT SEARCHES:".color(theme::current().header).bold()
+    );
+    for (i, entry) in history.entries.iter().rev().enumerate() {
+        outln!(
+            "{}. {} ({} results, {})",
+            (i + 1).to_string().yellow().bold(),
+            entry.query.cyan(),
+            entry.results,
+            format_age(entry.searched_at)
+        );
+    }
+    outln!(
+        "\nRe-run one with {}",
+        "sabikani search --last <number>".cyan()
+    );
+}
+
+fn display_saved_queries(saved: &SavedQueries) {
+    if saved.queries.is_empty() {
+        outln!(
+            "No saved queries yet; save one with {}",
+            "sabikani query save <name> <search arguments>".cyan()
+        );
+        return;
+    }
+
+    outln!(
+        "\n{}",
+        "SAVED QUERIES:".color(theme::current().header).bold()
+    );
+    for (name, args) in &saved.queries {
+        outln!(
+            "{}  {}",
+            name.yellow().bold(),
+            queries::expansion(args).cyan()
+        );
+    }
+    outln!("\nRun one with {}", "sabikani query run <name>".cyan());
+}
+
+/// The narrowest `--wrap-width` accepted, since narrower output has no room
+/// left for the synopsis after its indent.
+const MIN_WRAP_WIDTH: u16 = 40;
+
+/// Width used for rules and truncation: `--wrap-width` when given, else the
+/// terminal width capped at 100.
+fn output_width() -> usize {
+    if let Some(width) = output::fixed_width() {
+        return width;
+    }
+    let width = if let Some((Width(w), _)) = terminal_size() {
+        w as usize
+    } else {
+        80
+    };
+    width.min(100)
+}
+
+/// The `1. Title (ID: 42)` heading every entry starts with.
+fn entry_heading(number: usize, anime: &AnimeData, options: &DisplayOptions) -> String {
+    let title = highlight_matches(
+        anime.attributes.display_title(options.title_language),
+        options.highlight.as_deref(),
+        |part| part.color(theme::current().title).bold(),
+    );
+    // Merged results from AniList carry AniList IDs.
+    let title = match options.web_url {
+        Some(web_url) if anime.source.as_deref() != Some("anilist") => {
+            output::link(&title, &format!("{}/{}", web_url, anime.id))
+        }
+        _ => title,
+    };
+    let mut heading = format!(
+        "{}. {} (ID: {})",
+        number.to_string().yellow().bold(),
+        title,
+        anime.id
+    );
+    if let Some(source) = &anime.source {
+        heading.push_str(&format!(" [{}]", source.magenta()));
+    }
+    heading
+}
+
+/// Where `query` appears in `text`, ignoring case, as non-overlapping byte
+/// ranges that always fall on char boundaries. Chars are compared one by
+/// one, so a title whose lowercase form has a different length (such as one
+/// with "İ") still maps back onto its own bytes.
+fn match_ranges(text: &str, query: &str) -> Vec<std::ops::Range<usize>> {
+    let query: Vec<char> = query.trim().chars().collect();
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let same = |a: char, b: char| a == b || a.to_lowercase().eq(b.to_lowercase());
+
+    let mut ranges = Vec::new();
+    let mut i = 0;
+    while i + query.len() <= chars.len() {
+        let candidate = &chars[i..i + query.len()];
+        if candidate.iter().zip(&query).all(|(&(_, a), &b)| same(a, b)) {
+            let end = chars.get(i + query.len()).map_or(text.len(), |&(at, _)| at);
+            ranges.push(chars[i].0..end);
+            i += query.len();
+        } else {
+            i += 1;
+        }
+    }
+    ranges
+}
+
+/// `text` styled with `style`, except where it matches `query`, which is
+/// drawn bold and underlined in the theme's highlight color.
+fn highlight_matches(
+    text: &str,
+    query: Option<&str>,
+    style: impl Fn(&str) -> ColoredString,
+) -> String {
+    let ranges = query.map_or_else(Vec::new, |query| match_ranges(text, query));
+    let mut out = String::new();
+    let mut done = 0;
+    for range in ranges {
+        if range.start > done {
+            out.push_str(&style(&text[done..range.start]).to_string());
+        }
+        let matched = &text[range.clone()];
+        out.push_str(
+            &matched
+                .color(theme::current().highlight)
+                .bold()
+                .underline()
+                .to_string(),
+        );
+        done = range.end;
+    }
+    if done < text.len() {
+        out.push_str(&style(&text[done..]).to_string());
+    }
+    out
+}
+
+/// Renders a 0-100 rating as stars plus the raw number, e.g.
+/// `★★★★½ 82.5/100`, or "N/A" when the rating is missing or unparseable.
+fn colored_rating(rating: Option<&str>, options: &DisplayOptions) -> ColoredString {
+    match rating.and_then(|r| r.trim().parse::<f64>().ok()) {
+        Some(value) if (0.0..=100.0).contains(&value) => format!(
+            "{} {}/100",
+            rating_stars(value, options.rating_scale),
+            value
+        )
+        .color(theme::current().rating(options.rating_bands.band(value as f32))),
+        _ => "N/A".dimmed(),
+    }
+}
+
+/// Where ratings change color: below `low` is poor, above `high` is good.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct RatingBands {
+    low: f32,
+    high: f32,
+}
+
+impl Default for RatingBands {
+    fn default() -> Self {
+        RatingBands {
+            low: 60.0,
+            high: 75.0,
+        }
+    }
+}
+
+/// Which side of the `RatingBands` a rating falls on; the theme picks the
+/// color for each.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RatingBand {
+    Low,
+    Mid,
+    High,
+}
+
+impl RatingBands {
+    /// Low below `low`, high above `high`, mid in between (inclusive).
+    fn band(&self, rating: f32) -> RatingBand {
+        if rating < self.low {
+            RatingBand::Low
+        } else if rating > self.high {
+            RatingBand::High
+        } else {
+            RatingBand::Mid
+        }
+    }
+}
+
+/// Converts a 0-100 rating to `scale` stars, rounded to the nearest half.
+fn rating_stars(value: f64, scale: RatingScale) -> String {
+    let stars = scale.stars();
+    let halves = (value / 100.0 * stars as f64 * 2.0).round() as usize;
+    let full = halves / 2;
+    let half = halves % 2;
+    let glyphs = glyphs::current();
+
+    format!(
+        "{}{}{}",
+        glyphs.star_full.repeat(full),
+        glyphs.star_half.repeat(half),
+        glyphs.star_empty.repeat(stars - full - half)
+    )
+}
+
+/// How a Kitsu status value reads to people, e.g. "current" is "Airing".
+/// Values Kitsu may add later are shown as they come.
+fn status_label(status: &str) -> &str {
+    match status {
+        "finished" => "Finished",
+        "current" => "Airing",
+        "upcoming" => "Upcoming",
+        "tba" => "TBA",
+        "unreleased" => "Unreleased",
+        // Only AniList reports these two.
+        "cancelled" => "Cancelled",
+        "hiatus" => "On hiatus",
+        other => other,
+    }
+}
+
+/// A status's label in the theme's color for it, shared by the CLI and the
+/// TUI so both describe a status the same way.
+fn format_status(status: &str) -> ColoredString {
+    let theme = theme::current();
+    let label = status_label(status);
+    match status {
+        "finished" => label.color(theme.finished),
+        "current" => label.color(theme.airing),
+        "upcoming" => label.color(theme.upcoming),
+        "tba" => label.color(theme.tba),
+        "unreleased" => label.color(theme.unreleased),
+        "cancelled" => label.color(theme.cancelled),
+        "hiatus" => label.color(theme.hiatus),
+        _ => label.normal(),
+    }
+}
+
+/// Prints one numbered entry on a single line, for `--compact`.
+fn display_anime_line(number: usize, anime: &AnimeData, options: &DisplayOptions) {
+    let attrs = &anime.attributes;
+    let mut line = entry_heading(number, anime, options);
+
+    line.push_str(&format!(
+        " - {}",
+        colored_rating(attrs.average_rating.as_deref(), options)
+    ));
+    if let Some(status) = &attrs.status {
+        line.push_str(&format!(" - {}", format_status(status)));
+    }
+
+    outln!("{}", line);
+}
+
+/// Prints one numbered entry, without the trailing separator rule.
+fn display_anime_entry(number: usize, anime: &AnimeData, width: usize, options: &DisplayOptions) {
+    for field in &options.fields {
+        display_field(*field, number, anime, width, options);
+    }
+}
+
+/// Prints the line(s) for one `--fields` entry, or nothing when the anime
+/// lacks that attribute.
+fn display_field(
+    field: Field,
+    number: usize,
+    anime: &AnimeData,
+    width: usize,
+    options: &DisplayOptions,
+) {
+    let attrs = &anime.attributes;
+
+    match field {
+        Field::Title => outln!("{}", entry_heading(number, anime, options)),
+        Field::Type => {
+            if let Some(subtype) = &attrs.subtype {
+                outln!("  Type: {}", subtype.magenta());
+            }
+        }
+        Field::Genres => {
+            if !anime.categories.is_empty() {
+                outln!("  Genres: {}", anime.categories.join(", ").magenta());
+            }
+        }
+        Field::Streaming => {
+            if !anime.streamers.is_empty() {
+                outln!("  Streaming on: {}", anime.streamers.join(", ").blue());
+            }
+        }
+        Field::Rating => outln!(
+            "  Rating: {}",
+            colored_rating(attrs.average_rating.as_deref(), options)
+        ),
+        Field::Popularity => {
+            let parts = popularity_parts(attrs);
+            if !parts.is_empty() {
+                outln!("  Popularity: {}", parts.join(" | ").cyan());
+            }
+        }
+        Field::AgeRating => {
+            if let Some(age_rating) = attrs.age_rating_label() {
+                outln!("  Age rating: {}", age_rating.red());
+            }
+        }
+        Field::Episodes => {
+            if let Some(eps) = attrs.episode_count {
+                outln!("  Episodes: {}", eps.to_string().yellow());
+            }
+        }
+        Field::Chapters => {
+            if let Some(chapters) = attrs.chapter_count {
+                outln!("  Chapters: {}", chapters.to_string().yellow());
+            }
+        }
+        Field::Volumes => {
+            if let Some(volumes) = attrs.volume_count {
+                outln!("  Volumes: {}", volumes.to_string().yellow());
+            }
+        }
+        Field::Status => {
+            if let Some(status) = &attrs.status {
+                outln!("  Status: {}", format_status(status));
+            }
+        }
+        Field::Aired => {
+            if let Some(start) = &attrs.start_date {
+                let date_str = if options.relative_dates {
+                    dates::describe_airing(start, attrs.end_date.as_deref(), dates::today())
+                } else {
+                    dates::airing_period(start, attrs.end_date.as_deref())
+                };
+                outln!("  Aired: {}", date_str.blue());
+            }
+        }
+        Field::Poster => {
+            if let Some(poster) = attrs
+                .poster_image
+                .as_ref()
+                .and_then(|p| p.url(options.poster_size))
+            {
+                outln!("  Poster: {}", poster.blue().underline());
+            }
+        }
+        Field::Cover => {
+            if let Some(cover) = attrs
+                .cover_image
+                .as_ref()
+                .and_then(|c| c.url(options.poster_size))
+            {
+                outln!("  Cover: {}", cover.blue().underline());
+            }
+        }
+        Field::Synopsis => {
+            if let Some(synopsis) = attrs.synopsis.as_ref().filter(|_| !options.no_synopsis) {
+                display_synopsis(synopsis, width, options);
+            }
+        }
+    }
+}
+
+/// Prints `synopsis` under the entry heading, wrapped unless `options.wrap`
+/// is off, and cut off with "..." after `options.synopsis_chars` characters
+/// or `options.synopsis_lines` lines, whichever comes first (0 for no limit).
+fn display_synopsis(synopsis: &str, width: usize, options: &DisplayOptions) {
+    // Indent every line by two spaces to hang under the entry heading.
+    let line_width = width.saturating_sub(2).max(1);
+    let max_lines = options.synopsis_lines;
+    let synopsis = truncate_chars(synopsis, options.synopsis_chars);
+    let synopsis = synopsis.as_str();
+    let mut lines = if options.wrap {
+        wrap_words(synopsis, line_width)
+    } else {
+        synopsis
+            .lines()
+            .map(|line| line.trim_end().to_string())
+            .collect()
+    };
+
+    if max_lines > 0 && lines.len() > max_lines {
+        lines.truncate(max_lines);
+        if let Some(last) = lines.last_mut() {
+            while !last.is_empty() && last.width() + 3 > line_width {
+                last.pop();
+            }
+            last.truncate(last.trim_end().len());
+            last.push_str("...");
+        }
+    }
+
+    for line in lines {
+        outln!("  {}", line.color(theme::current().synopsis));
+    }
+}
+
+/// `text` cut to at most `max` characters (not bytes), backing up to a word
+/// boundary and ending in "..." when anything was cut. 0 keeps it whole.
+fn truncate_chars(text: &str, max: usize) -> String {
+    if max == 0 || text.chars().count() <= max {
+        return text.to_string();
+    }
+    let cut: String = text.chars().take(max).collect();
+    // Prefer not to end mid-word, unless the first word alone is too long.
+    let kept = match cut.rfind(char::is_whitespace) {
+        Some(end) if end > 0 => &cut[..end],
+        _ => cut.as_str(),
+    };
+    format!("{}...", kept.trim_end())
+}
+
+/// Greedily wraps `text` onto lines at most `width` columns wide, breaking on
+/// whitespace. Words wider than a whole line are split across lines.
+fn wrap_words(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.width() + 1 + word.width() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+
+        for c in word.chars() {
+            if !line.is_empty() && line.width() + c.width().unwrap_or(0) > width {
+                lines.push(std::mem::take(&mut line));
+            }
+            line.push(c);
+        }
+    }
+
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncate_chars_cuts_multibyte_text_on_char_boundaries() {
+        let synopsis = "鋼の錬金術師 — エドとアルの兄弟は失われたものを取り戻す旅に出る";
+        let cut = truncate_chars(synopsis, 10);
+        assert_eq!(cut, "鋼の錬金術師 —...");
+        assert!(cut.chars().count() <= 10 + "...".len());
+
+        // No whitespace to back up to: cut mid-word, still on a boundary.
+        let cut = truncate_chars("αβγδεζηθικλμ", 5);
+        assert_eq!(cut, "αβγδε...");
+    }
+
+    #[test]
+    fn truncate_chars_keeps_short_text_whole() {
+        assert_eq!(truncate_chars("Cowboy Bebop", 12), "Cowboy Bebop");
+        assert_eq!(truncate_chars("日本語", 3), "日本語");
+        assert_eq!(truncate_chars("", 5), "");
+    }
+
+    #[test]
+    fn truncate_chars_backs_up_to_a_word_boundary() {
+        assert_eq!(
+            truncate_chars("Bounty hunters travel aboard the Bebop", 20),
+            "Bounty hunters..."
+        );
+    }
+
+    #[test]
+    fn wrap_words_measures_columns_not_bytes() {
+        assert_eq!(
+            wrap_words("カウボーイ ビバップ と スパイク", 10),
+            ["カウボーイ", "ビバップ", "と", "スパイク"]
+        );
+        // A word wider than the line is split across lines.
+        assert_eq!(wrap_words("ééééééé", 3), ["ééé", "ééé", "é"]);
+    }
+
+    fn search_options(page: Page) -> SearchOptions {
+        SearchOptions {
+            page,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn search_url_asks_for_the_numbered_page() {
+        let url = search_url(
+            KITSU_API_URL,
+            "naruto",
+            &search_options(Page::numbered(3, 10)),
+        )
+        .unwrap();
+        assert_eq!(
+            url,
+            "https://kitsu.io/api/edge/anime?filter%5Btext%5D=naruto\
+             &page%5Blimit%5D=10&page%5Boffset%5D=20"
+        );
+    }
+
+    #[test]
+    fn next_link_means_another_page() {
+        let page = |links: &str| {
+            let json = format!(r#"{{"data": [], "links": {}}}"#, links);
+            serde_json::from_str::<RawAnimeResponse>(&json)
+                .unwrap()
+                .into_response()
+        };
+        let next = "https://kitsu.io/api/edge/anime?page%5Boffset%5D=10";
+        assert!(page(&format!(r#"{{"next": "{}"}}"#, next)).has_next_page());
+        assert!(!page(r#"{"first": "https://kitsu.io/api/edge/anime"}"#).has_next_page());
+        assert!(!page("null").has_next_page());
+    }
+
+    fn cli(args: &[&str]) -> Result<Cli, clap::Error> {
+        Cli::try_parse_from(std::iter::once("sabikani").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn subtype_becomes_its_filter() {
+        let options = SearchOptions {
+            subtype: Some(Subtype::Movie),
+            ..Default::default()
+        };
+        let url = search_url(KITSU_API_URL, "akira", &options).unwrap();
+        assert!(url.contains("&filter%5Bsubtype%5D=movie"), "{}", url);
+
+        let options = SearchOptions {
+            subtype: Some(Subtype::Tv),
+            ..Default::default()
+        };
+        let url = search_url(KITSU_API_URL, "akira", &options).unwrap();
+        assert!(url.contains("&filter%5Bsubtype%5D=TV"), "{}", url);
+    }
+
+    #[test]
+    fn unknown_types_are_rejected() {
+        let err = cli(&["search", "akira", "--type", "movei"])
+            .err()
+            .expect("a misspelled type should not parse");
+        assert_eq!(err.kind(), ErrorKind::InvalidValue);
+
+        let Commands::Search { subtype, .. } =
+            cli(&["search", "akira", "--type", "tv"]).unwrap().command
+        else {
+            panic!("expected a search");
+        };
+        assert_eq!(subtype, Some(Subtype::Tv));
+    }
+
+    #[test]
+    fn sort_rating_asks_for_highest_rated_first() {
+        let Commands::Search { sort, .. } = cli(&["search", "naruto", "--sort", "rating"])
+            .unwrap()
+            .command
+        else {
+            panic!("expected a search");
+        };
+        let options = SearchOptions {
+            sort,
+            ..Default::default()
+        };
+        let url = search_url(KITSU_API_URL, "naruto", &options).unwrap();
+        assert!(url.ends_with("&sort=-averageRating"), "{}", url);
+    }
+
+    #[test]
+    fn reversed_and_unknown_sort_keys() {
+        let Commands::Search { sort, .. } = cli(&["search", "naruto", "--sort", "-date"])
+            .unwrap()
+            .command
+        else {
+            panic!("expected a search");
+        };
+        assert_eq!(sort.map(SortKey::api_value), Some("startDate"));
+
+        let err = cli(&["search", "naruto", "--sort", "score"])
+            .err()
+            .expect("an unknown sort key should not parse");
+        assert_eq!(err.kind(), ErrorKind::InvalidValue);
+    }
+
+    #[test]
+    fn no_sort_keeps_relevance() {
+        let url = search_url(KITSU_API_URL, "naruto", &SearchOptions::default()).unwrap();
+        assert!(!url.contains("sort="), "{}", url);
+    }
+
+    #[test]
+    fn max_age_rating_admits_only_lower_ratings() {
+        assert_eq!(AgeRating::G.up_to(), "G");
+        assert_eq!(AgeRating::Pg.up_to(), "G,PG");
+        assert_eq!(AgeRating::R18.up_to(), "G,PG,R,R18");
+        assert!(AgeRating::G < AgeRating::Pg && AgeRating::R < AgeRating::R18);
+
+        let options = SearchOptions {
+            max_age_rating: Some(AgeRating::Pg),
+            ..Default::default()
+        };
+        let url = search_url(KITSU_API_URL, "naruto", &options).unwrap();
+        assert!(url.contains("&filter%5BageRating%5D=G%2CPG"), "{}", url);
+        assert!(!url.contains("R18"), "{}", url);
+    }
+
+    #[test]
+    fn age_rating_label_adds_the_guide() {
+        let attrs = AnimeAttributes {
+            age_rating: Some("R".to_string()),
+            age_rating_guide: Some("17+ (violence & profanity)".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            attrs.age_rating_label().as_deref(),
+            Some("R (17+ (violence & profanity))")
+        );
+
+        let attrs = AnimeAttributes {
+            age_rating: Some("PG".to_string()),
+            age_rating_guide: Some(String::new()),
+            ..Default::default()
+        };
+        assert_eq!(attrs.age_rating_label().as_deref(), Some("PG"));
+        assert!(AnimeAttributes::default().age_rating_label().is_none());
+    }
+
+    #[test]
+    fn rating_stars_round_to_the_nearest_half() {
+        assert_eq!(rating_stars(100.0, RatingScale::Five), "★★★★★");
+        assert_eq!(rating_stars(0.0, RatingScale::Five), "☆☆☆☆☆");
+        // 45/100 is exactly two and a half stars; just under rounds down.
+        assert_eq!(rating_stars(45.0, RatingScale::Five), "★★½☆☆");
+        assert_eq!(rating_stars(44.9, RatingScale::Five), "★★☆☆☆");
+        assert_eq!(rating_stars(55.0, RatingScale::Five), "★★★☆☆");
+        assert_eq!(rating_stars(85.0, RatingScale::Ten), "★★★★★★★★½☆");
+        assert_eq!(rating_stars(82.1, RatingScale::Ten), "★★★★★★★★☆☆");
+    }
+
+    #[test]
+    fn malformed_ratings_show_as_na() {
+        let options = DisplayOptions::default();
+        let shown = |rating: Option<&str>| colored_rating(rating, &options).input;
+        assert_eq!(shown(Some(" 82.1 ")), "★★★★☆ 82.1/100");
+        assert_eq!(shown(None), "N/A");
+        assert_eq!(shown(Some("")), "N/A");
+        assert_eq!(shown(Some("eighty")), "N/A");
+        assert_eq!(shown(Some("150")), "N/A");
+        assert_eq!(shown(Some("-3")), "N/A");
+    }
+
+    #[test]
+    fn search_url_encodes_the_query() {
+        let url = search_url(
+            KITSU_API_URL,
+            "Fate/Stay Night & more",
+            &SearchOptions::default(),
+        )
+        .unwrap();
+        assert!(
+            url.starts_with(
+                "https://kitsu.io/api/edge/anime?filter%5Btext%5D=Fate%2FStay+Night+%26+more&"
+            ),
+            "{}",
+            url
+        );
+
+        let url = search_url(KITSU_API_URL, "#1 100% = ?", &SearchOptions::default()).unwrap();
+        assert!(
+            url.contains("filter%5Btext%5D=%231+100%25+%3D+%3F&"),
+            "{}",
+            url
+        );
+
+        // Parsed back, the query is exactly what was searched for.
+        let parsed = reqwest::Url::parse(&url).unwrap();
+        let text = parsed
+            .query_pairs()
+            .find(|(name, _)| name == "filter[text]")
+            .map(|(_, value)| value.into_owned());
+        assert_eq!(text.as_deref(), Some("#1 100% = ?"));
+    }
+
+    #[test]
+    fn season_and_year_filters_combine() {
+        let url = |season, year, query| {
+            let options = SearchOptions {
+                season,
+                year,
+                ..Default::default()
+            };
+            search_url(KITSU_API_URL, query, &options).unwrap()
+        };
+
+        let both = url(Some(Season::Spring), Some(2021), "");
+        assert!(
+            both.ends_with("&filter%5Bseason%5D=spring&filter%5BseasonYear%5D=2021"),
+            "{}",
+            both
+        );
+        assert!(!both.contains("filter%5Btext%5D"), "{}", both);
+
+        let season = url(Some(Season::Fall), None, "mecha");
+        assert!(season.contains("filter%5Btext%5D=mecha&"), "{}", season);
+        assert!(season.ends_with("&filter%5Bseason%5D=fall"), "{}", season);
+        assert!(!season.contains("seasonYear"), "{}", season);
+
+        let year = url(None, Some(1998), "");
+        assert!(year.ends_with("&filter%5BseasonYear%5D=1998"), "{}", year);
+        assert!(!year.contains("filter%5Bseason%5D"), "{}", year);
+    }
+
+    #[test]
+    fn seasons_and_years_are_validated() {
+        assert!(cli(&["search", "--season", "Winter", "--year", "2000"]).is_ok());
+        for args in [
+            ["search", "--season", "autumn"],
+            ["search", "--year", "1899"],
+            ["search", "--year", "2101"],
+            ["search", "--year", "soon"],
+        ] {
+            assert!(cli(&args).is_err(), "{:?}", args);
+        }
+        assert_eq!(
+            season_label(Some(Season::Spring), Some(2021)),
+            "spring 2021"
+        );
+        assert_eq!(season_label(Some(Season::Fall), None), "fall of any year");
+        assert_eq!(season_label(None, Some(1998)), "1998");
+    }
+
+    #[test]
+    fn malformed_entries_are_skipped_and_counted() {
+        let json = r#"{
+            "data": [
+                {"id": "1", "type": "anime", "attributes": {"canonicalTitle": "Cowboy Bebop",
+                 "episodeCount": 26, "unknownField": {"nested": true}}},
+                {"id": "2", "type": "anime", "attributes": {"canonicalTitle": "Broken",
+                 "episodeCount": "twenty-six"}},
+                {"type": "anime"},
+                "not even an object"
+            ],
+            "meta": {"count": 4}
+        }"#;
+        let response = serde_json::from_str::<RawAnimeResponse>(json)
+            .unwrap()
+            .into_response();
+
+        assert_eq!(response.skipped, 3);
+        let [anime] = &response.data[..] else {
+            panic!("expected one result, got {:?}", response.data);
+        };
+        assert_eq!(anime.attributes.canonical_title, "Cowboy Bebop");
+        assert_eq!(anime.attributes.episode_count, Some(26));
+    }
+
+    #[test]
+    fn a_response_without_data_is_empty() {
+        let response = serde_json::from_str::<RawAnimeResponse>(r#"{"meta": {}}"#)
+            .unwrap()
+            .into_response();
+        assert!(response.data.is_empty());
+        assert_eq!(response.skipped, 0);
+    }
+
+    fn anime(id: &str, title: &str) -> AnimeData {
+        AnimeData {
+            id: id.to_string(),
+            attributes: AnimeAttributes {
+                canonical_title: title.to_string(),
+                ..Default::default()
+            },
+            categories: Vec::new(),
+            streamers: Vec::new(),
+            source: None,
+        }
+    }
+
+    fn ids(anime_list: &[AnimeData]) -> Vec<&str> {
+        anime_list.iter().map(|anime| anime.id.as_str()).collect()
+    }
+
+    #[test]
+    fn dedup_keeps_the_most_complete_entry_in_the_first_place() {
+        let mut sparse = anime("1", "Cowboy Bebop");
+        sparse.attributes.average_rating = Some("90.0".to_string());
+        let mut full = anime("2", " cowboy bebop ");
+        full.attributes.average_rating = Some("82.1".to_string());
+        full.attributes.episode_count = Some(26);
+        full.attributes.synopsis = Some("Bounty hunters.".to_string());
+
+        let mut results = vec![sparse, anime("3", "Trigun"), full];
+        assert_eq!(dedup_results(&mut results), 1);
+        assert_eq!(ids(&results), ["2", "3"]);
+    }
+
+    #[test]
+    fn dedup_breaks_ties_on_rating_then_keeps_the_first() {
+        let rated = |id, rating: Option<&str>| {
+            let mut anime = anime(id, "Trigun");
+            anime.attributes.average_rating = rating.map(str::to_string);
+            anime
+        };
+
+        let mut results = vec![rated("1", Some("70")), rated("2", Some("80"))];
+        assert_eq!(dedup_results(&mut results), 1);
+        assert_eq!(ids(&results), ["2"]);
+
+        let mut results = vec![rated("1", Some("80")), rated("2", Some("80"))];
+        dedup_results(&mut results);
+        assert_eq!(ids(&results), ["1"]);
+
+        let mut results = vec![anime("1", "Trigun"), anime("2", "Trigun Stampede")];
+        assert_eq!(dedup_results(&mut results), 0);
+        assert_eq!(ids(&results), ["1", "2"]);
+    }
+
+    #[test]
+    fn rating_band_boundaries_belong_to_the_middle() {
+        let bands = RatingBands::default();
+        assert_eq!(bands.band(0.0), RatingBand::Low);
+        assert_eq!(bands.band(59.9), RatingBand::Low);
+        assert_eq!(bands.band(60.0), RatingBand::Mid);
+        assert_eq!(bands.band(75.0), RatingBand::Mid);
+        assert_eq!(bands.band(75.1), RatingBand::High);
+        assert_eq!(bands.band(100.0), RatingBand::High);
+    }
+
+    fn rated(id: &str, rating: Option<&str>) -> AnimeData {
+        let mut anime = anime(id, id);
+        anime.attributes.average_rating = rating.map(str::to_string);
+        anime
+    }
+
+    #[test]
+    fn min_rating_keeps_entries_at_the_threshold() {
+        let mut results = vec![
+            rated("1", Some("69.99")),
+            rated("2", Some("70.00")),
+            rated("3", Some("88.2")),
+            rated("4", None),
+        ];
+        assert_eq!(filter_by_rating(&mut results, Some(70), false), 1);
+        assert_eq!(ids(&results), ["2", "3", "4"]);
+    }
+
+    #[test]
+    fn unrated_entries_go_only_when_asked() {
+        let mut results = vec![
+            rated("1", None),
+            rated("2", Some("n/a")),
+            rated("3", Some("0")),
+        ];
+        assert_eq!(filter_by_rating(&mut results, None, false), 0);
+        assert_eq!(filter_by_rating(&mut results, None, true), 2);
+        assert_eq!(ids(&results), ["3"]);
+    }
+
+    #[test]
+    fn popularity_fields_parse_and_may_be_null() {
+        let attrs: AnimeAttributes = serde_json::from_str(
+            r#"{"canonicalTitle": "Cowboy Bebop", "popularityRank": 27, "ratingRank": 33,
+                "userCount": 1234567, "favoritesCount": 4321}"#,
+        )
+        .unwrap();
+        assert_eq!(attrs.popularity_rank, Some(27));
+        assert_eq!(attrs.rating_rank, Some(33));
+        assert_eq!(attrs.user_count, Some(1_234_567));
+        assert_eq!(attrs.favorites_count, Some(4321));
+        assert_eq!(
+            popularity_parts(&attrs),
+            [
+                "Popularity #27",
+                "Rating #33",
+                "Users: 1,234,567",
+                "Favorites: 4,321"
+            ]
+        );
+
+        let attrs: AnimeAttributes = serde_json::from_str(
+            r#"{"canonicalTitle": "Obscure", "popularityRank": null, "ratingRank": null,
+                "userCount": 12}"#,
+        )
+        .unwrap();
+        assert!(attrs.popularity_rank.is_none() && attrs.favorites_count.is_none());
+        assert_eq!(popularity_parts(&attrs), ["Users: 12"]);
+    }
+
+    #[test]
+    fn every_known_status_has_a_label_and_color() {
+        let theme = theme::current();
+        for (status, label, color) in [
+            ("finished", "Finished", theme.finished),
+            ("current", "Airing", theme.airing),
+            ("upcoming", "Upcoming", theme.upcoming),
+            ("tba", "TBA", theme.tba),
+            ("unreleased", "Unreleased", theme.unreleased),
+            ("cancelled", "Cancelled", theme.cancelled),
+            ("hiatus", "On hiatus", theme.hiatus),
+        ] {
+            assert_eq!(status_label(status), label);
+            let formatted = format_status(status);
+            assert_eq!(formatted.input, label);
+            assert_eq!(formatted.fgcolor, Some(color), "{}", status);
+        }
+    }
+
+    #[test]
+    fn unknown_statuses_show_as_sent() {
+        assert_eq!(status_label("postponed"), "postponed");
+        let formatted = format_status("postponed");
+        assert_eq!(formatted.input, "postponed");
+        assert_eq!(formatted.fgcolor, None);
+    }
+
+    #[test]
+    fn synopsis_chars_zero_small_and_large() {
+        let synopsis = "In the year 2071, humanity has colonized several of the planets.";
+        assert_eq!(truncate_chars(synopsis, 0), synopsis);
+        assert_eq!(truncate_chars(synopsis, 1), "I...");
+        assert_eq!(truncate_chars(synopsis, 16), "In the year...");
+        assert_eq!(truncate_chars(synopsis, 10_000), synopsis);
+
+        let Commands::Search { synopsis_chars, .. } = cli(&["search", "bebop"]).unwrap().command
+        else {
+            panic!("expected a search");
+        };
+        assert_eq!(synopsis_chars, 0);
+    }
+
+    #[test]
+    fn only_entries_marked_nsfw_are_dropped() {
+        let marked = |id, nsfw| {
+            let mut anime = anime(id, id);
+            anime.attributes.nsfw = nsfw;
+            anime
+        };
+        let mut results = vec![
+            marked("1", Some(false)),
+            marked("2", Some(true)),
+            marked("3", None),
+        ];
+        assert_eq!(filter_nsfw(&mut results), 1);
+        assert_eq!(ids(&results), ["1", "3"]);
+    }
+
+    #[test]
+    fn start_filter_is_inclusive_and_drops_unknown_dates() {
+        let started = |id, date: Option<&str>| {
+            let mut anime = anime(id, id);
+            anime.attributes.start_date = date.map(str::to_string);
+            anime
+        };
+        let mut results = vec![
+            started("1", Some("1997-12-31")),
+            started("2", Some("1998-01-01")),
+            started("3", Some("1999-12-31")),
+            started("4", Some("2000-01-01")),
+            started("5", None),
+            started("6", Some("soon")),
+        ];
+        let aired = dates::Range {
+            since: dates::parse("1998-01-01"),
+            until: dates::parse("1999-12-31"),
+        };
+        assert_eq!(filter_by_start(&mut results, aired), 4);
+        assert_eq!(ids(&results), ["2", "3"]);
+
+        let mut results = vec![started("5", None)];
+        assert_eq!(filter_by_start(&mut results, dates::Range::default()), 0);
+    }
+
+    #[test]
+    fn completeness_counts_the_filled_in_attributes() {
+        let full: AnimeAttributes = serde_json::from_str(
+            r#"{
+                "canonicalTitle": "Cowboy Bebop",
+                "synopsis": "Bounty hunters.",
+                "averageRating": "82.1",
+                "startDate": "1998-04-03",
+                "endDate": "1999-04-24",
+                "status": "finished",
+                "episodeCount": 26,
+                "chapterCount": 0,
+                "volumeCount": 0,
+                "subtype": "TV",
+                "ageRating": "R",
+                "ageRatingGuide": "17+",
+                "posterImage": {"original": "https://example.com/p.jpg"},
+                "coverImage": {"original": "https://example.com/c.jpg"}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(full.completeness_score(), COMPLETENESS_FIELDS);
+
+        let sparse: AnimeAttributes = serde_json::from_str(
+            r#"{"canonicalTitle": "Stub", "synopsis": null, "episodeCount": 12}"#,
+        )
+        .unwrap();
+        assert_eq!(sparse.completeness_score(), 1);
+        assert_eq!(AnimeAttributes::default().completeness_score(), 0);
+    }
+
+    #[test]
+    fn completeness_filters_and_sorts_stably() {
+        let filled = |id, fields: usize| {
+            let mut anime = anime(id, id);
+            let attrs = &mut anime.attributes;
+            if fields >= 1 {
+                attrs.synopsis = Some("...".to_string());
+            }
+            if fields >= 2 {
+                attrs.episode_count = Some(1);
+            }
+            if fields >= 3 {
+                attrs.status = Some("finished".to_string());
+            }
+            anime
+        };
+        let mut results = vec![
+            filled("1", 1),
+            filled("2", 3),
+            filled("3", 0),
+            filled("4", 3),
+        ];
+
+        sort_by_completeness(&mut results);
+        assert_eq!(ids(&results), ["2", "4", "1", "3"]);
+
+        assert_eq!(filter_by_completeness(&mut results, None), 0);
+        assert_eq!(filter_by_completeness(&mut results, Some(1)), 1);
+        assert_eq!(ids(&results), ["2", "4", "1"]);
+        assert_eq!(filter_by_completeness(&mut results, Some(3)), 1);
+        assert_eq!(ids(&results), ["2", "4"]);
+    }
+
+    #[test]
+    fn airing_filters_on_current_status() {
+        let options = SearchOptions {
+            airing: true,
+            subtype: Some(Subtype::Tv),
+            ..Default::default()
+        };
+        let url = search_url(KITSU_API_URL, "", &options).unwrap();
+        assert!(url.contains("filter%5Bstatus%5D=current"), "{}", url);
+        assert!(url.contains("filter%5Bsubtype%5D=TV"), "{}", url);
+        assert!(!url.contains("filter%5Btext%5D"), "{}", url);
+
+        let url = search_url(KITSU_API_URL, "mecha", &SearchOptions::default()).unwrap();
+        assert!(!url.contains("filter%5Bstatus%5D"), "{}", url);
+    }
+
+    #[test]
+    fn match_ranges_fall_on_char_boundaries() {
+        let title = "進撃の巨人 進撃";
+        assert_eq!(match_ranges(title, "進撃"), [0..6, 16..22]);
+        for range in match_ranges(title, "進撃") {
+            assert_eq!(&title[range], "進撃");
+        }
+
+        let title = "Pokémon POKÉMON";
+        assert_eq!(match_ranges(title, " pokémon "), [0..8, 9..17]);
+        assert_eq!(&title[9..17], "POKÉMON");
+        assert!(match_ranges(title, "pokemon").is_empty());
+        assert!(match_ranges(title, "   ").is_empty());
+    }
+
+    #[test]
+    fn highlighting_keeps_every_char_of_the_title() {
+        fn strip_ansi(text: &str) -> String {
+            let mut out = String::new();
+            let mut chars = text.chars();
+            while let Some(c) = chars.next() {
+                if c == '\x1b' {
+                    chars.by_ref().find(|&c| c == 'm');
+                } else {
+                    out.push(c);
+                }
+            }
+            out
+        }
+
+        let title = "カウボーイビバップ 天国の扉";
+        for query in [Some("ビバップ"), Some("扉"), Some("nothing"), None] {
+            let highlighted = highlight_matches(title, query, |part| part.normal());
+            assert_eq!(strip_ansi(&highlighted), title, "{:?}", query);
+        }
+    }
+
+    fn with_episodes(id: &str, episodes: Option<u16>) -> AnimeData {
+        let mut anime = anime(id, id);
+        anime.attributes.episode_count = episodes;
+        anime
+    }
+
+    #[test]
+    fn episode_bounds_are_inclusive() {
+        let mut results = vec![
+            with_episodes("1", Some(12)),
+            with_episodes("2", Some(13)),
+            with_episodes("3", Some(24)),
+            with_episodes("4", Some(25)),
+        ];
+        let range = EpisodeRange {
+            min: Some(13),
+            max: Some(24),
+            include_unknown: false,
+        };
+        assert_eq!(filter_by_episodes(&mut results, range), 2);
+        assert_eq!(ids(&results), ["2", "3"]);
+
+        let mut results = vec![with_episodes("1", Some(1)), with_episodes("2", Some(500))];
+        let at_least = EpisodeRange {
+            min: Some(24),
+            ..Default::default()
+        };
+        assert_eq!(filter_by_episodes(&mut results, at_least), 1);
+        assert_eq!(ids(&results), ["2"]);
+    }
+
+    #[test]
+    fn unknown_episode_counts_need_include_unknown() {
+        let unknown = || vec![with_episodes("1", None), with_episodes("2", Some(12))];
+        let short = EpisodeRange {
+            max: Some(13),
+            ..Default::default()
+        };
+
+        let mut results = unknown();
+        assert_eq!(filter_by_episodes(&mut results, short), 1);
+        assert_eq!(ids(&results), ["2"]);
+
+        let mut results = unknown();
+        let including = EpisodeRange {
+            include_unknown: true,
+            ..short
+        };
+        assert_eq!(filter_by_episodes(&mut results, including), 0);
+
+        // Without a bound there is nothing to be unknown about.
+        let mut results = unknown();
+        assert_eq!(filter_by_episodes(&mut results, EpisodeRange::default()), 0);
+    }
 
-        println!("{}", "-".repeat(width.min(100)));
+    #[test]
+    fn wrap_widths_below_the_minimum_are_rejected() {
+        let Cli { wrap_width, .. } = cli(&["search", "x", "--wrap-width", "40"]).unwrap();
+        assert_eq!(wrap_width, Some(MIN_WRAP_WIDTH));
+        let err = cli(&["search", "x", "--wrap-width", "39"])
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("39 is not in 40.."), "{}", err);
     }
 }
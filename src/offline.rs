@@ -0,0 +1,242 @@
+//! `--offline` mode: searches answered from a small bundled sample of Kitsu
+//! data, for demos and for trying the tool (or the TUI) without a network.
+
+use std::{
+    cmp::Ordering as SortOrder,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use anyhow::{Context, Result};
+
+use crate::{
+    AgeRating, AnimeAttributes, AnimeData, AnimeResponse, Media, PaginationLinks, RawAnimeResponse,
+    ResponseMeta, SearchOptions, Season, SortKey,
+};
+
+/// A Kitsu `/anime` response captured in the same shape the API sends.
+const SAMPLES: &str = include_str!("fixtures/anime.json");
+
+/// Set once at startup from `--offline` or `SABIKANI_OFFLINE=1`.
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+pub fn enable() {
+    OFFLINE.store(true, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    OFFLINE.load(Ordering::Relaxed)
+}
+
+/// Whether `SABIKANI_OFFLINE` asks for offline mode.
+pub fn requested_by_env() -> bool {
+    std::env::var("SABIKANI_OFFLINE").is_ok_and(|value| matches!(value.trim(), "1" | "true"))
+}
+
+fn samples() -> Result<Vec<AnimeData>> {
+    let response: RawAnimeResponse =
+        serde_json::from_str(SAMPLES).context("Bundled sample data is invalid")?;
+    Ok(response.into_response().data)
+}
+
+/// Filters the samples like Kitsu would: `query` against every title,
+/// `--type` against the subtype, `--airing` against the status, `--season`
+/// and `--year` against the start date and `--max-age-rating` against the
+/// age rating. Then sorts them by `--sort`, if given, and takes
+/// `options.page` as the window. The samples are all anime, so manga
+/// searches find nothing.
+pub fn search(query: &str, options: &SearchOptions) -> Result<AnimeResponse> {
+    let query = query.to_lowercase();
+    let mut matches: Vec<AnimeData> = if options.media == Media::Anime {
+        samples()?
+            .into_iter()
+            .filter(|anime| {
+                let attrs = &anime.attributes;
+                let titles = [
+                    Some(&attrs.canonical_title),
+                    attrs.titles.en.as_ref(),
+                    attrs.titles.en_jp.as_ref(),
+                    attrs.titles.ja_jp.as_ref(),
+                ];
+                titles
+                    .into_iter()
+                    .flatten()
+                    .any(|title| title.to_lowercase().contains(&query))
+            })
+            .filter(|anime| {
+                options.subtype.is_none_or(|subtype| {
+                    anime
+                        .attributes
+                        .subtype
+                        .as_deref()
+                        .is_some_and(|value| value.eq_ignore_ascii_case(subtype.api_value()))
+                })
+            })
+            .filter(|anime| {
+                !options.airing || anime.attributes.status.as_deref() == Some("current")
+            })
+            .filter(|anime| {
+                if options.season.is_none() && options.year.is_none() {
+                    return true;
+                }
+                season_of(&anime.attributes).is_some_and(|(season, year)| {
+                    options.season.is_none_or(|wanted| wanted == season)
+                        && options.year.is_none_or(|wanted| wanted == year)
+                })
+            })
+            .filter(|anime| {
+                options.max_age_rating.is_none_or(|max| {
+                    AgeRating::ALL
+                        .into_iter()
+                        .find(|rating| {
+                            anime.attributes.age_rating.as_deref() == Some(rating.api_value())
+                        })
+                        .is_some_and(|rating| rating <= max)
+                })
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+    if let Some(sort) = options.sort {
+        matches.sort_by(|a, b| compare(&a.attributes, &b.attributes, sort));
+    }
+
+    let total = matches.len();
+    let offset = options.page.offset as usize;
+    let data: Vec<AnimeData> = matches
+        .into_iter()
+        .skip(offset)
+        .take(options.page.limit as usize)
+        .collect();
+    let next = (offset + data.len() < total).then(|| "offline".to_string());
+
+    Ok(AnimeResponse {
+        data,
+        links: Some(PaginationLinks {
+            first: None,
+            prev: None,
+            next,
+            last: None,
+        }),
+        meta: Some(ResponseMeta {
+            count: Some(total as u64),
+        }),
+        skipped: 0,
+    })
+}
+
+/// The season and year an anime started in, going by its start date as
+/// Kitsu does: a December start counts towards the next year's winter.
+fn season_of(attrs: &AnimeAttributes) -> Option<(Season, u16)> {
+    let date = attrs.start_date.as_deref()?;
+    let year: u16 = date.get(..4)?.parse().ok()?;
+    let month: u8 = date.get(5..7)?.parse().ok()?;
+    Some(match month {
+        12 => (Season::Winter, year + 1),
+        1 | 2 => (Season::Winter, year),
+        3..=5 => (Season::Spring, year),
+        6..=8 => (Season::Summer, year),
+        9..=11 => (Season::Fall, year),
+        _ => return None,
+    })
+}
+
+/// Orders two samples by `sort`, with those missing the value last either
+/// way.
+fn compare(a: &AnimeAttributes, b: &AnimeAttributes, sort: SortKey) -> SortOrder {
+    fn known_first<T: PartialOrd>(a: Option<T>, b: Option<T>, descending: bool) -> SortOrder {
+        match (a, b) {
+            (Some(a), Some(b)) => {
+                let order = a.partial_cmp(&b).unwrap_or(SortOrder::Equal);
+                if descending { order.reverse() } else { order }
+            }
+            (Some(_), None) => SortOrder::Less,
+            (None, Some(_)) => SortOrder::Greater,
+            (None, None) => SortOrder::Equal,
+        }
+    }
+
+    match sort {
+        SortKey::Rating | SortKey::RatingAsc => {
+            known_first(a.rating_value(), b.rating_value(), sort == SortKey::Rating)
+        }
+        // Rank 1 is the most popular, so "most popular first" ascends.
+        SortKey::Popularity | SortKey::PopularityAsc => known_first(
+            a.popularity_rank,
+            b.popularity_rank,
+            sort == SortKey::PopularityAsc,
+        ),
+        SortKey::Date | SortKey::DateAsc => known_first(
+            a.start_date.as_deref(),
+            b.start_date.as_deref(),
+            sort == SortKey::Date,
+        ),
+    }
+}
+
+/// The sample with Kitsu id `id`, if there is one.
+pub fn find(id: &str) -> Result<Option<AnimeData>> {
+    Ok(samples()?.into_iter().find(|anime| anime.id == id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(options: SearchOptions) -> Vec<String> {
+        search("", &options)
+            .unwrap()
+            .data
+            .into_iter()
+            .map(|anime| anime.id)
+            .collect()
+    }
+
+    #[test]
+    fn seasons_and_years_go_by_the_start_date() {
+        let spring = SearchOptions {
+            season: Some(Season::Spring),
+            ..SearchOptions::default()
+        };
+        assert_eq!(ids(spring), ["1", "3936", "5646", "7442", "8271"]);
+        let year = SearchOptions {
+            year: Some(2009),
+            ..SearchOptions::default()
+        };
+        assert_eq!(ids(year), ["3936"]);
+
+        let december = AnimeAttributes {
+            start_date: Some("2005-12-01".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(season_of(&december), Some((Season::Winter, 2006)));
+    }
+
+    #[test]
+    fn age_ratings_above_the_maximum_are_left_out() {
+        let options = SearchOptions {
+            max_age_rating: Some(AgeRating::Pg),
+            ..SearchOptions::default()
+        };
+        assert_eq!(ids(options), ["11", "12", "5646", "176", "8271"]);
+    }
+
+    #[test]
+    fn sorting_puts_unknown_values_last() {
+        let options = SearchOptions {
+            sort: Some(SortKey::Rating),
+            ..SearchOptions::default()
+        };
+        assert_eq!(
+            ids(options),
+            [
+                "3936", "5646", "176", "7442", "1376", "12", "1", "11", "8271"
+            ]
+        );
+        let options = SearchOptions {
+            sort: Some(SortKey::Popularity),
+            ..SearchOptions::default()
+        };
+        assert_eq!(&ids(options)[..4], ["7442", "1376", "11", "3936"]);
+    }
+}
@@ -0,0 +1,176 @@
+//! Where rendered output goes: stdout, or the file named by `--output`.
+//!
+//! Everything meant for stdout is printed through `out!`/`outln!`, which
+//! write here. Write errors are remembered and reported once by `finish`,
+//! instead of panicking mid-listing like `println!` would, and stdout closing
+//! early (as when piping into `head`) quietly ends the run.
+
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{
+        Mutex, OnceLock,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use anyhow::{Context, Result};
+
+struct Sink {
+    path: PathBuf,
+    file: BufWriter<File>,
+    error: Option<io::Error>,
+}
+
+static SINK: Mutex<Option<Sink>> = Mutex::new(None);
+
+/// The first error writing to stdout, when there is no `--output` file.
+static STDOUT_ERROR: Mutex<Option<io::Error>> = Mutex::new(None);
+
+/// `--wrap-width`, when given.
+static WIDTH: OnceLock<usize> = OnceLock::new();
+
+/// Lays output out for `width` columns whatever the terminal is.
+pub fn fix_width(width: usize) {
+    let _ = WIDTH.set(width);
+}
+
+pub fn fixed_width() -> Option<usize> {
+    WIDTH.get().copied()
+}
+
+/// Whether titles are written as OSC 8 terminal hyperlinks.
+static HYPERLINKS: AtomicBool = AtomicBool::new(false);
+
+pub fn enable_hyperlinks() {
+    HYPERLINKS.store(true, Ordering::Relaxed);
+}
+
+/// `text` as an OSC 8 hyperlink to `url` when hyperlinks are on, else as-is.
+pub fn link(text: &str, url: &str) -> String {
+    if HYPERLINKS.load(Ordering::Relaxed) {
+        format!("\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", url, text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// Whether the terminal is one known to understand OSC 8. Terminals that
+/// don't may print the escape codes as garbage, so unknown ones are assumed
+/// not to.
+pub fn terminal_supports_hyperlinks() -> bool {
+    supports_hyperlinks(|name| std::env::var(name).ok())
+}
+
+/// `terminal_supports_hyperlinks`, reading the environment through `env`.
+fn supports_hyperlinks(env: impl Fn(&str) -> Option<String>) -> bool {
+    let var = |name: &str| env(name).unwrap_or_default();
+    if var("TERM") == "dumb" {
+        return false;
+    }
+    let vte = var("VTE_VERSION")
+        .parse::<u32>()
+        .is_ok_and(|version| version >= 5000);
+    vte || matches!(
+        var("TERM_PROGRAM").as_str(),
+        "iTerm.app" | "WezTerm" | "vscode" | "Hyper" | "ghostty"
+    ) || ["WT_SESSION", "KONSOLE_VERSION", "KITTY_WINDOW_ID"]
+        .iter()
+        .any(|name| !var(name).is_empty())
+        || matches!(var("TERM").as_str(), "xterm-kitty" | "foot" | "alacritty")
+}
+
+/// Sends all further output to `path`, creating its parent directories.
+pub fn redirect(path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let file =
+        File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+
+    *SINK.lock().unwrap() = Some(Sink {
+        path: path.to_path_buf(),
+        file: BufWriter::new(file),
+        error: None,
+    });
+    Ok(())
+}
+
+pub fn print(args: fmt::Arguments) {
+    match SINK.lock().unwrap().as_mut() {
+        Some(sink) => {
+            if sink.error.is_none()
+                && let Err(err) = sink.file.write_fmt(args)
+            {
+                sink.error = Some(err);
+            }
+        }
+        None => {
+            let mut error = STDOUT_ERROR.lock().unwrap();
+            if error.is_some() {
+                return;
+            }
+            match io::stdout().lock().write_fmt(args) {
+                // Whatever was reading has all it wanted.
+                Err(err) if err.kind() == io::ErrorKind::BrokenPipe => std::process::exit(0),
+                Err(err) => *error = Some(err),
+                Ok(()) => {}
+            }
+        }
+    }
+}
+
+/// Flushes the output, reporting the first error writing it.
+pub fn finish() -> Result<()> {
+    let Some(mut sink) = SINK.lock().unwrap().take() else {
+        let result = match STDOUT_ERROR.lock().unwrap().take() {
+            Some(err) => Err(err),
+            None => match io::stdout().flush() {
+                Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+                result => result,
+            },
+        };
+        return result.context("Failed to write to stdout");
+    };
+    let result = match sink.error.take() {
+        Some(err) => Err(err),
+        None => sink.file.flush(),
+    };
+    result.with_context(|| format!("Failed to write {}", sink.path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn supports_hyperlinks_with(vars: &[(&str, &str)]) -> bool {
+        supports_hyperlinks(|name| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| value.to_string())
+        })
+    }
+
+    #[test]
+    fn known_terminals_get_hyperlinks() {
+        assert!(supports_hyperlinks_with(&[("TERM_PROGRAM", "WezTerm")]));
+        assert!(supports_hyperlinks_with(&[("VTE_VERSION", "6003")]));
+        assert!(supports_hyperlinks_with(&[("WT_SESSION", "abc")]));
+        assert!(supports_hyperlinks_with(&[("TERM", "xterm-kitty")]));
+    }
+
+    #[test]
+    fn unknown_and_dumb_terminals_do_not() {
+        assert!(!supports_hyperlinks_with(&[]));
+        assert!(!supports_hyperlinks_with(&[("TERM", "xterm-256color")]));
+        assert!(!supports_hyperlinks_with(&[("VTE_VERSION", "4205")]));
+        assert!(!supports_hyperlinks_with(&[("WT_SESSION", "")]));
+        assert!(!supports_hyperlinks_with(&[
+            ("TERM", "dumb"),
+            ("TERM_PROGRAM", "iTerm.app")
+        ]));
+    }
+}
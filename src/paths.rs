@@ -0,0 +1,59 @@
+//! Platform-specific locations for the files sabikani keeps on disk.
+
+use std::{env, path::PathBuf};
+
+use anyhow::{Result, anyhow};
+
+const APP_DIR: &str = "sabikani";
+
+/// Directory for disposable data such as API response caches.
+pub fn cache_dir() -> Result<PathBuf> {
+    base_dir("XDG_CACHE_HOME", ".cache", "Library/Caches", "LOCALAPPDATA")
+}
+
+/// Directory for data the user would be upset to lose, like the watchlist.
+pub fn data_dir() -> Result<PathBuf> {
+    base_dir(
+        "XDG_DATA_HOME",
+        ".local/share",
+        "Library/Application Support",
+        "APPDATA",
+    )
+}
+
+/// Directory holding the user's configuration file.
+pub fn config_dir() -> Result<PathBuf> {
+    base_dir(
+        "XDG_CONFIG_HOME",
+        ".config",
+        "Library/Application Support",
+        "APPDATA",
+    )
+}
+
+fn base_dir(
+    xdg_var: &str,
+    home_fallback: &str,
+    macos_dir: &str,
+    windows_var: &str,
+) -> Result<PathBuf> {
+    let base = if cfg!(windows) {
+        env::var_os(windows_var).map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        home_dir().map(|home| home.join(macos_dir))
+    } else {
+        env::var_os(xdg_var)
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| home_dir().map(|home| home.join(home_fallback)))
+    };
+
+    base.map(|dir| dir.join(APP_DIR))
+        .ok_or_else(|| anyhow!("Could not determine the user's home directory"))
+}
+
+fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+}
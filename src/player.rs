@@ -0,0 +1,25 @@
+//! Hands a stream URL to an external media player for `watch --player`.
+
+use std::{io, process::Command};
+
+use anyhow::{Context, Result, bail};
+
+pub const DEFAULT_PLAYER: &str = "mpv";
+
+/// Runs `player url` and waits for it to exit. The player inherits the
+/// terminal so its own controls keep working.
+pub fn play(player: &str, url: &str) -> Result<()> {
+    let status = match Command::new(player).arg(url).status() {
+        Ok(status) => status,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => bail!(
+            "Couldn't find the player `{}`; install it or choose another with --player <command>",
+            player
+        ),
+        Err(err) => return Err(err).with_context(|| format!("Failed to start `{}`", player)),
+    };
+
+    if !status.success() {
+        bail!("`{}` exited with {}", player, status);
+    }
+    Ok(())
+}
@@ -0,0 +1,235 @@
+//! Saved searches (`query save/run/list`), kept as `queries.toml` next to the
+//! config file so they travel with it.
+//!
+//! Each query is a name and the `search` arguments it stands for, stored as
+//! an array of strings:
+//!
+//! ```toml
+//! winter = ["--season", "winter", "--year", "2024", "--sort", "-rating"]
+//! ```
+
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+use anyhow::{Context, Result, bail};
+
+use crate::{
+    config::{self, Value},
+    paths,
+};
+
+const QUERIES_FILE: &str = "queries.toml";
+
+#[derive(Debug)]
+pub struct SavedQueries {
+    path: PathBuf,
+    pub queries: BTreeMap<String, Vec<String>>,
+}
+
+impl SavedQueries {
+    /// Loads the saved queries. A missing file means there are none.
+    pub fn load() -> Result<SavedQueries> {
+        SavedQueries::open(paths::config_dir()?.join(QUERIES_FILE))
+    }
+
+    fn open(path: PathBuf) -> Result<SavedQueries> {
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(err) => {
+                return Err(err).with_context(|| format!("Failed to read {}", path.display()));
+            }
+        };
+
+        let queries = parse(&contents)
+            .with_context(|| format!("Invalid saved queries file {}", path.display()))?;
+        Ok(SavedQueries { path, queries })
+    }
+
+    /// The arguments saved as `name`.
+    pub fn get(&self, name: &str) -> Result<&[String]> {
+        match self.queries.get(name) {
+            Some(args) => Ok(args),
+            None => bail!(
+                "There is no saved query named `{}`; see `sabikani query list`",
+                name
+            ),
+        }
+    }
+
+    /// Saves `args` as `name`, returning whether it replaced an older query
+    /// of the same name.
+    pub fn insert(&mut self, name: &str, args: Vec<String>) -> Result<bool> {
+        validate_name(name)?;
+        Ok(self.queries.insert(name.to_string(), args).is_some())
+    }
+
+    /// Forgets `name`, returning whether it was saved.
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.queries.remove(name).is_some()
+    }
+
+    /// Writes to a temporary file and renames it into place, so a reader
+    /// never sees a half-written file.
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+
+        let mut contents =
+            String::from("# Saved searches; run one with `sabikani query run <name>`.\n");
+        for (name, args) in &self.queries {
+            let args: Vec<String> = args.iter().map(|arg| config::quote(arg)).collect();
+            contents.push_str(&format!("{} = [{}]\n", name, args.join(", ")));
+        }
+
+        let temp = self
+            .path
+            .with_extension(format!("toml.{}.tmp", std::process::id()));
+        fs::write(&temp, contents)
+            .with_context(|| format!("Failed to write saved queries to {}", temp.display()))?;
+        fs::rename(&temp, &self.path)
+            .with_context(|| format!("Failed to write saved queries to {}", self.path.display()))
+    }
+}
+
+fn parse(contents: &str) -> Result<BTreeMap<String, Vec<String>>> {
+    let mut queries = BTreeMap::new();
+    for (table, entries) in config::parse(contents)? {
+        if !table.is_empty() {
+            bail!("unexpected table `[{}]`", table);
+        }
+        for (name, value) in entries {
+            let Value::Array(items) = value else {
+                bail!("`{}` must be an array of strings", name);
+            };
+            let args = items
+                .into_iter()
+                .map(|item| match item {
+                    Value::String(arg) => Ok(arg),
+                    _ => bail!("`{}` must be an array of strings", name),
+                })
+                .collect::<Result<_>>()?;
+            queries.insert(name, args);
+        }
+    }
+    Ok(queries)
+}
+
+/// Names are written unquoted, so they are kept to what a bare TOML key
+/// allows.
+fn validate_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        bail!(
+            "Query name `{}` may only use letters, digits, `-` and `_`",
+            name
+        );
+    }
+    Ok(())
+}
+
+/// How a saved query reads on a command line, quoting arguments with spaces.
+pub fn expansion(args: &[String]) -> String {
+    let words: Vec<String> = args
+        .iter()
+        .map(|arg| {
+            if arg.is_empty() || arg.contains(char::is_whitespace) || arg.contains(['"', '\'']) {
+                format!("'{}'", arg.replace('\'', "'\\''"))
+            } else {
+                arg.clone()
+            }
+        })
+        .collect();
+    format!("sabikani search {}", words.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("sabikani-queries-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir.join(QUERIES_FILE)
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn saved_queries_survive_a_reload() {
+        let path = scratch("reload");
+        let mut saved = SavedQueries::open(path.clone()).unwrap();
+        assert!(saved.queries.is_empty());
+
+        let winter = args(&["--season", "winter", "--year", "2024"]);
+        let quoted = args(&["steins;gate \"zero\"", "--type", "tv"]);
+        assert!(!saved.insert("winter", winter.clone()).unwrap());
+        assert!(!saved.insert("sg-0", quoted.clone()).unwrap());
+        saved.save().unwrap();
+
+        let reloaded = SavedQueries::open(path).unwrap();
+        assert_eq!(reloaded.get("winter").unwrap(), winter);
+        assert_eq!(reloaded.get("sg-0").unwrap(), quoted);
+    }
+
+    #[test]
+    fn saving_a_name_again_replaces_it() {
+        let path = scratch("overwrite");
+        let mut saved = SavedQueries::open(path.clone()).unwrap();
+        saved.insert("bebop", args(&["bebop"])).unwrap();
+        assert!(
+            saved
+                .insert("bebop", args(&["bebop", "--limit", "1"]))
+                .unwrap()
+        );
+        saved.save().unwrap();
+
+        let reloaded = SavedQueries::open(path).unwrap();
+        assert_eq!(reloaded.queries.len(), 1);
+        assert_eq!(
+            reloaded.get("bebop").unwrap(),
+            args(&["bebop", "--limit", "1"])
+        );
+    }
+
+    #[test]
+    fn missing_and_invalid_names() {
+        let mut saved = SavedQueries::open(scratch("missing")).unwrap();
+        let err = saved.get("nope").unwrap_err().to_string();
+        assert!(err.contains("no saved query named `nope`"), "{}", err);
+        assert!(!saved.remove("nope"));
+
+        for name in ["", "two words", "a=b", "[x]"] {
+            assert!(saved.insert(name, args(&["x"])).is_err(), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn malformed_files_are_errors() {
+        for contents in [
+            "winter = \"--season\"\n",
+            "winter = [1, 2]\n",
+            "[x]\na = []\n",
+        ] {
+            let path = scratch("malformed");
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, contents).unwrap();
+            assert!(SavedQueries::open(path).is_err(), "{:?}", contents);
+        }
+    }
+
+    #[test]
+    fn expansion_quotes_what_a_shell_would_split() {
+        assert_eq!(
+            expansion(&args(&["cowboy bebop", "--limit", "5", "", "it's"])),
+            "sabikani search 'cowboy bebop' --limit 5 '' 'it'\\''s'"
+        );
+    }
+}
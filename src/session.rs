@@ -0,0 +1,72 @@
+//! The TUI's last search, saved on quit so `tui --resume` can pick it up.
+
+use std::fs;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{AnimeData, paths};
+
+const SESSION_FILE: &str = "tui-session.json";
+
+/// Bump whenever `Session` changes shape; files written with any other
+/// version are ignored instead of half-parsed.
+const SESSION_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Session {
+    version: u32,
+    pub input: String,
+    pub search_results: Vec<AnimeData>,
+    pub selected_anime_index: Option<usize>,
+}
+
+/// Just enough of a session file to check its version before parsing the rest.
+#[derive(Deserialize)]
+struct Header {
+    version: u32,
+}
+
+impl Session {
+    pub fn new(
+        input: String,
+        search_results: Vec<AnimeData>,
+        selected_anime_index: Option<usize>,
+    ) -> Session {
+        Session {
+            version: SESSION_VERSION,
+            input,
+            search_results,
+            selected_anime_index,
+        }
+    }
+
+    /// Loads the saved session. A missing, unreadable or incompatible file
+    /// just means there is nothing to resume.
+    pub fn load() -> Result<Option<Session>> {
+        let path = paths::cache_dir()?.join(SESSION_FILE);
+        let Ok(contents) = fs::read_to_string(&path) else {
+            return Ok(None);
+        };
+
+        let compatible = serde_json::from_str::<Header>(&contents)
+            .is_ok_and(|header| header.version == SESSION_VERSION);
+        if !compatible {
+            return Ok(None);
+        }
+
+        Ok(serde_json::from_str(&contents).ok())
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = paths::cache_dir()?.join(SESSION_FILE);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+
+        let contents = serde_json::to_string(self)?;
+        fs::write(&path, contents)
+            .with_context(|| format!("Failed to write TUI session to {}", path.display()))
+    }
+}
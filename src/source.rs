@@ -0,0 +1,409 @@
+//! Interchangeable search backends.
+
+use std::collections::HashSet;
+
+use anyhow::{Result, bail};
+use clap::ValueEnum;
+use colored::Colorize;
+use futures_util::future;
+
+use crate::{
+    AnimeResponse, SearchOptions, anilist::AniList, http::HttpClient, search_anime,
+    search_anime_requests,
+};
+
+/// A service that can answer anime searches in Kitsu's response shape.
+pub trait AnimeSource {
+    /// Short identifier used in cache keys and messages.
+    fn name(&self) -> &'static str;
+
+    async fn search(&self, query: &str, options: &SearchOptions) -> Result<AnimeResponse>;
+
+    /// The requests `search` would send, described for `--dry-run`.
+    fn describe_search(&self, query: &str, options: &SearchOptions) -> Result<Vec<String>>;
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum SourceKind {
+    #[default]
+    Kitsu,
+    #[value(name = "anilist")]
+    AniList,
+    /// Every backend at once, merged.
+    All,
+}
+
+impl SourceKind {
+    /// The backend's name, as used in cache keys.
+    pub fn name(self) -> &'static str {
+        match self {
+            SourceKind::Kitsu => "kitsu",
+            SourceKind::AniList => "anilist",
+            SourceKind::All => "all",
+        }
+    }
+}
+
+pub struct Kitsu {
+    client: HttpClient,
+    base_url: String,
+}
+
+impl Kitsu {
+    /// Talks to the Kitsu API at `base_url`, e.g. a local mock server.
+    pub fn new(client: HttpClient, base_url: impl Into<String>) -> Kitsu {
+        Kitsu {
+            client,
+            base_url: base_url.into(),
+        }
+    }
+}
+
+impl AnimeSource for Kitsu {
+    fn name(&self) -> &'static str {
+        "kitsu"
+    }
+
+    async fn search(&self, query: &str, options: &SearchOptions) -> Result<AnimeResponse> {
+        search_anime(&self.client, &self.base_url, query, options).await
+    }
+
+    fn describe_search(&self, query: &str, options: &SearchOptions) -> Result<Vec<String>> {
+        search_anime_requests(&self.client, &self.base_url, query, options)
+    }
+}
+
+/// Searches every backend concurrently and merges the results, Kitsu's
+/// first, dropping later results whose normalized title was already seen.
+pub struct AllSources {
+    kitsu: Kitsu,
+    anilist: AniList,
+}
+
+impl AnimeSource for AllSources {
+    fn name(&self) -> &'static str {
+        "all"
+    }
+
+    async fn search(&self, query: &str, options: &SearchOptions) -> Result<AnimeResponse> {
+        let (kitsu, anilist) = future::join(
+            self.kitsu.search(query, options),
+            self.anilist.search(query, options),
+        )
+        .await;
+
+        let mut responses = Vec::new();
+        for (name, result) in [(self.kitsu.name(), kitsu), (self.anilist.name(), anilist)] {
+            match result {
+                Ok(response) => responses.push((name, response)),
+                Err(err) => eprintln!(
+                    "{} {} search failed, showing other sources only: {:#}",
+                    "warning:".yellow().bold(),
+                    name,
+                    err
+                ),
+            }
+        }
+
+        if responses.is_empty() {
+            bail!("Every source failed to answer the search");
+        }
+        Ok(merge(responses))
+    }
+
+    fn describe_search(&self, query: &str, options: &SearchOptions) -> Result<Vec<String>> {
+        let mut requests = self.kitsu.describe_search(query, options)?;
+        requests.extend(self.anilist.describe_search(query, options)?);
+        Ok(requests)
+    }
+}
+
+/// Concatenates `responses` in order, tagging each result with its source
+/// and keeping only the first result for each normalized title.
+fn merge(responses: Vec<(&'static str, AnimeResponse)>) -> AnimeResponse {
+    let mut seen = HashSet::new();
+    let mut merged = AnimeResponse::default();
+
+    for (name, response) in responses {
+        merged.skipped += response.skipped;
+        // More pages on any source means more merged results too.
+        if merged.links.is_none() && response.has_next_page() {
+            merged.links = response.links;
+        }
+
+        for mut anime in response.data {
+            if seen.insert(normalize_title(&anime.attributes.canonical_title)) {
+                anime.source = Some(name.to_string());
+                merged.data.push(anime);
+            }
+        }
+    }
+
+    merged
+}
+
+/// Lowercased alphanumerics only, so "Cowboy Bebop" and "cowboy bebop!"
+/// count as the same title.
+fn normalize_title(title: &str) -> String {
+    title
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// The backend chosen on the command line.
+pub enum Backend {
+    Kitsu(Kitsu),
+    AniList(AniList),
+    All(AllSources),
+}
+
+impl Backend {
+    pub fn new(kind: SourceKind, client: HttpClient) -> Backend {
+        match kind {
+            SourceKind::Kitsu => {
+                let base_url = client.kitsu_api().to_string();
+                Backend::Kitsu(Kitsu::new(client, base_url))
+            }
+            SourceKind::AniList => Backend::AniList(AniList::new(client)),
+            SourceKind::All => Backend::All(AllSources {
+                kitsu: Kitsu::new(client.clone(), client.kitsu_api()),
+                anilist: AniList::new(client),
+            }),
+        }
+    }
+}
+
+impl AnimeSource for Backend {
+    fn name(&self) -> &'static str {
+        match self {
+            Backend::Kitsu(source) => source.name(),
+            Backend::AniList(source) => source.name(),
+            Backend::All(source) => source.name(),
+        }
+    }
+
+    async fn search(&self, query: &str, options: &SearchOptions) -> Result<AnimeResponse> {
+        match self {
+            Backend::Kitsu(source) => source.search(query, options).await,
+            Backend::AniList(source) => source.search(query, options).await,
+            Backend::All(source) => source.search(query, options).await,
+        }
+    }
+
+    fn describe_search(&self, query: &str, options: &SearchOptions) -> Result<Vec<String>> {
+        match self {
+            Backend::Kitsu(source) => source.describe_search(query, options),
+            Backend::AniList(source) => source.describe_search(query, options),
+            Backend::All(source) => source.describe_search(query, options),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{Page, kitsu_stub::KitsuStub};
+
+    const SEARCH_PAGE: &str = include_str!("../tests/fixtures/search_page.json");
+    const LAST_PAGE: &str = include_str!("../tests/fixtures/search_last_page.json");
+
+    async fn search(stub: &KitsuStub, offset: u32) -> AnimeResponse {
+        let client = HttpClient::new(Duration::from_secs(5), 0).unwrap();
+        let options = SearchOptions {
+            page: Page { limit: 2, offset },
+            ..Default::default()
+        };
+        Kitsu::new(client, stub.base_url.clone())
+            .search("bebop", &options)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn parses_filled_and_null_fields() {
+        let stub = KitsuStub::start(vec![("page%5Boffset%5D=0", SEARCH_PAGE)]).await;
+        let response = search(&stub, 0).await;
+
+        assert_eq!(response.skipped, 0);
+        assert_eq!(response.meta.and_then(|meta| meta.count), Some(3));
+        let [filled, sparse] = &response.data[..] else {
+            panic!("expected two results, got {:?}", response.data);
+        };
+
+        let attrs = &filled.attributes;
+        assert_eq!(filled.id, "1");
+        assert_eq!(attrs.canonical_title, "Cowboy Bebop");
+        assert_eq!(attrs.titles.ja_jp.as_deref(), Some("カウボーイビバップ"));
+        assert_eq!(attrs.average_rating.as_deref(), Some("82.1"));
+        assert_eq!(attrs.episode_count, Some(26));
+        let poster = attrs.poster_image.as_ref().unwrap();
+        assert_eq!(
+            poster.url(crate::ImageSize::Small),
+            Some("https://media.kitsu.io/anime/poster_images/1/original.jpg")
+        );
+
+        let attrs = &sparse.attributes;
+        assert_eq!(attrs.canonical_title, "Cowboy Bebop: Yose Atsume Blues");
+        assert!(attrs.titles.en.is_none() && attrs.titles.ja_jp.is_none());
+        assert!(attrs.average_rating.is_none());
+        assert!(attrs.episode_count.is_none());
+        assert!(attrs.poster_image.is_none());
+        assert!(attrs.synopsis.is_none());
+    }
+
+    #[tokio::test]
+    async fn reads_the_next_page_link() {
+        let stub = KitsuStub::start(vec![
+            ("page%5Boffset%5D=0", SEARCH_PAGE),
+            ("page%5Boffset%5D=2", LAST_PAGE),
+        ])
+        .await;
+
+        let first = search(&stub, 0).await;
+        assert!(first.has_next_page());
+        let next = first.links.and_then(|links| links.next).unwrap();
+        assert!(next.ends_with("page%5Boffset%5D=2"), "{}", next);
+
+        let last = search(&stub, 2).await;
+        assert!(!last.has_next_page());
+        assert_eq!(last.data[0].id, "5");
+
+        let requests = stub.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].starts_with(
+            "GET /api/edge/anime?filter%5Btext%5D=bebop&page%5Blimit%5D=2&page%5Boffset%5D=0 "
+        ));
+        assert!(requests[1].contains("page%5Boffset%5D=2 "));
+    }
+
+    fn anime(id: &str, title: &str) -> crate::AnimeData {
+        crate::AnimeData {
+            id: id.to_string(),
+            attributes: crate::AnimeAttributes {
+                canonical_title: title.to_string(),
+                ..Default::default()
+            },
+            categories: Vec::new(),
+            streamers: Vec::new(),
+            source: None,
+        }
+    }
+
+    fn response(results: Vec<crate::AnimeData>) -> AnimeResponse {
+        AnimeResponse {
+            data: results,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn normalize_title_ignores_case_and_punctuation() {
+        assert_eq!(normalize_title("Cowboy Bebop"), "cowboybebop");
+        assert_eq!(normalize_title("  COWBOY-BEBOP! "), "cowboybebop");
+        assert_eq!(
+            normalize_title("Steins;Gate"),
+            normalize_title("steins gate")
+        );
+        assert_eq!(
+            normalize_title("カウボーイ・ビバップ"),
+            "カウボーイビバップ"
+        );
+    }
+
+    #[test]
+    fn merge_keeps_the_first_source_for_overlapping_titles() {
+        let kitsu = response(vec![anime("1", "Cowboy Bebop"), anime("11", "Naruto")]);
+        let anilist = response(vec![
+            anime("1", "cowboy bebop!"),
+            anime("20", "Trigun"),
+            anime("30", "NARUTO"),
+        ]);
+
+        let merged = merge(vec![("kitsu", kitsu), ("anilist", anilist)]);
+        let found: Vec<(&str, &str)> = merged
+            .data
+            .iter()
+            .map(|anime| (anime.id.as_str(), anime.source.as_deref().unwrap()))
+            .collect();
+        assert_eq!(found, [("1", "kitsu"), ("11", "kitsu"), ("20", "anilist")]);
+    }
+
+    /// A Kitsu page of `count` results numbered from `first`, with a next
+    /// link when `more`.
+    fn numbered_page(first: usize, count: usize, more: bool) -> String {
+        let data: Vec<serde_json::Value> = (first..first + count)
+            .map(|id| {
+                serde_json::json!({
+                    "id": id.to_string(),
+                    "type": "anime",
+                    "attributes": { "canonicalTitle": format!("Anime {}", id) }
+                })
+            })
+            .collect();
+        let links = match more {
+            true => serde_json::json!({ "next": "https://kitsu.io/api/edge/anime?more" }),
+            false => serde_json::json!({}),
+        };
+        serde_json::json!({ "data": data, "links": links }).to_string()
+    }
+
+    async fn search_limit(stub: &KitsuStub, limit: u32) -> AnimeResponse {
+        let client = HttpClient::new(Duration::from_secs(5), 0).unwrap();
+        let options = SearchOptions {
+            page: Page { limit, offset: 0 },
+            ..Default::default()
+        };
+        Kitsu::new(client, stub.base_url.clone())
+            .search("anime", &options)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn large_limits_combine_pages() {
+        let stub = KitsuStub::start(vec![
+            (
+                "page%5Blimit%5D=20&page%5Boffset%5D=0",
+                numbered_page(1, 20, true),
+            ),
+            (
+                "page%5Blimit%5D=20&page%5Boffset%5D=20",
+                numbered_page(21, 20, true),
+            ),
+            (
+                "page%5Blimit%5D=5&page%5Boffset%5D=40",
+                numbered_page(41, 5, true),
+            ),
+        ])
+        .await;
+
+        let response = search_limit(&stub, 45).await;
+        let ids: Vec<usize> = response
+            .data
+            .iter()
+            .map(|anime| anime.id.parse().unwrap())
+            .collect();
+        assert_eq!(ids, (1..=45).collect::<Vec<_>>());
+        assert_eq!(stub.requests().len(), 3);
+        // The last page's links are the ones kept.
+        assert!(response.has_next_page());
+    }
+
+    #[tokio::test]
+    async fn combining_stops_where_kitsu_runs_out() {
+        let stub = KitsuStub::start(vec![
+            ("page%5Boffset%5D=0", numbered_page(1, 20, true)),
+            ("page%5Boffset%5D=20", numbered_page(21, 7, false)),
+        ])
+        .await;
+
+        let response = search_limit(&stub, 100).await;
+        assert_eq!(response.data.len(), 27);
+        assert!(!response.has_next_page());
+        assert_eq!(stub.requests().len(), 2);
+    }
+}
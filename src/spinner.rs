@@ -0,0 +1,68 @@
+//! A small terminal spinner shown while waiting on the network.
+
+use std::{
+    io::{self, IsTerminal, Write},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::glyphs;
+
+const TICK: Duration = Duration::from_millis(80);
+
+/// Animates `message` on stderr from a background thread until dropped, then
+/// erases the line so the output that follows starts clean.
+pub struct Spinner {
+    running: Arc<AtomicBool>,
+    ticker: Option<JoinHandle<()>>,
+}
+
+impl Spinner {
+    /// Starts spinning, unless output is piped or redirected, in which case
+    /// the spinner does nothing.
+    pub fn start(message: &str) -> Spinner {
+        let running = Arc::new(AtomicBool::new(true));
+
+        let ticker = (io::stdout().is_terminal() && io::stderr().is_terminal()).then(|| {
+            let running = Arc::clone(&running);
+            let message = message.to_string();
+            thread::spawn(move || {
+                let mut stderr = io::stderr();
+                for frame in glyphs::current().spinner.iter().cycle() {
+                    if !running.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    let _ = write!(stderr, "\r{} {}", frame, message);
+                    let _ = stderr.flush();
+                    thread::sleep(TICK);
+                }
+                // Clear to the end of the line.
+                let _ = write!(stderr, "\r\x1b[K");
+                let _ = stderr.flush();
+            })
+        });
+
+        Spinner { running, ticker }
+    }
+
+    /// Doing nothing, for callers that only sometimes want a spinner.
+    pub fn disabled() -> Spinner {
+        Spinner {
+            running: Arc::new(AtomicBool::new(false)),
+            ticker: None,
+        }
+    }
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(ticker) = self.ticker.take() {
+            let _ = ticker.join();
+        }
+    }
+}
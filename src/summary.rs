@@ -0,0 +1,177 @@
+//! Tallies for work made of many requests (batch searches, per-result
+//! lookups, `compare`), so that a few failures end in one line such as
+//! "8 succeeded, 2 failed (timeout, 404)" instead of aborting the rest or
+//! going unnoticed.
+
+use anyhow::Result;
+use colored::Colorize;
+
+use crate::http::StatusError;
+
+/// Why one item failed, as short as the summary shows it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    Timeout,
+    /// The API answered with this unsuccessful HTTP status.
+    Status(u16),
+    /// No answer at all: no connection, DNS, a cut-off response.
+    Network,
+    Other,
+}
+
+impl Failure {
+    pub fn classify(err: &anyhow::Error) -> Failure {
+        for cause in err.chain() {
+            if let Some(status) = cause.downcast_ref::<StatusError>() {
+                return Failure::Status(status.status.as_u16());
+            }
+            if let Some(err) = cause.downcast_ref::<reqwest::Error>() {
+                return if err.is_timeout() {
+                    Failure::Timeout
+                } else {
+                    Failure::Network
+                };
+            }
+        }
+        Failure::Other
+    }
+
+    fn label(self) -> String {
+        match self {
+            Failure::Timeout => "timeout".to_string(),
+            Failure::Status(code) => code.to_string(),
+            Failure::Network => "network".to_string(),
+            Failure::Other => "error".to_string(),
+        }
+    }
+}
+
+/// How each item of one operation went.
+#[derive(Debug, Default)]
+pub struct Summary {
+    succeeded: usize,
+    failures: Vec<Failure>,
+    /// Kept to fail with, with its whole chain, if nothing succeeds.
+    first_error: Option<anyhow::Error>,
+}
+
+impl Summary {
+    pub fn succeeded(&mut self) {
+        self.succeeded += 1;
+    }
+
+    pub fn failed(&mut self, err: anyhow::Error) {
+        self.failures.push(Failure::classify(&err));
+        self.first_error.get_or_insert(err);
+    }
+
+    /// "8 succeeded, 2 failed (timeout, 404)".
+    pub fn line(&self) -> String {
+        format!(
+            "{} succeeded, {} failed ({})",
+            self.succeeded,
+            self.failures.len(),
+            self.reasons()
+        )
+    }
+
+    /// Each kind of failure once, in the order first seen, with a count when
+    /// it happened more than once: "3× timeout, 404".
+    fn reasons(&self) -> String {
+        let mut kinds: Vec<(Failure, usize)> = Vec::new();
+        for failure in &self.failures {
+            match kinds.iter_mut().find(|(kind, _)| kind == failure) {
+                Some((_, count)) => *count += 1,
+                None => kinds.push((*failure, 1)),
+            }
+        }
+        let reasons: Vec<String> = kinds
+            .iter()
+            .map(|(kind, count)| match count {
+                1 => kind.label(),
+                _ => format!("{}× {}", count, kind.label()),
+            })
+            .collect();
+        reasons.join(", ")
+    }
+
+    /// Ends the operation: quietly when everything worked, with the summary
+    /// on stderr when only some of it did, and with the first error (and
+    /// the summary as context) when every item of `what` failed.
+    pub fn finish(self, what: &str) -> Result<()> {
+        if self.failures.is_empty() {
+            return Ok(());
+        }
+        if self.succeeded == 0 {
+            let failed = format!("All {} failed ({})", what, self.reasons());
+            if let Some(err) = self.first_error {
+                return Err(err.context(failed));
+            }
+        }
+        eprintln!("{} {}: {}", "warning:".yellow().bold(), what, self.line());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use anyhow::anyhow;
+
+    use super::*;
+    use crate::{
+        http::HttpClient,
+        kitsu_stub::{KitsuStub, Reply},
+    };
+
+    /// A real `StatusError` for `status`, as the client reports it.
+    async fn status_error(status: u16) -> anyhow::Error {
+        let stub = KitsuStub::replies(vec![Reply::json(status, "")]).await;
+        HttpClient::new(Duration::from_secs(5), 0)
+            .unwrap()
+            .fetch_with_retry(&format!("{}/anime/1", stub.base_url))
+            .await
+            .unwrap_err()
+    }
+
+    #[tokio::test]
+    async fn failures_are_classified_through_the_chain() {
+        let not_found = status_error(404).await.context("couldn't fetch 1");
+        assert_eq!(Failure::classify(&not_found), Failure::Status(404));
+        assert_eq!(Failure::classify(&anyhow!("bad JSON")), Failure::Other);
+    }
+
+    #[tokio::test]
+    async fn mixed_results_are_summarized_and_not_fatal() {
+        let mut summary = Summary::default();
+        summary.succeeded();
+        summary.failed(anyhow!("bad JSON"));
+        summary.failed(status_error(404).await);
+        summary.succeeded();
+        summary.failed(anyhow!("bad JSON again"));
+
+        assert_eq!(summary.line(), "2 succeeded, 3 failed (2× error, 404)");
+        assert!(summary.finish("lookups").is_ok());
+    }
+
+    #[tokio::test]
+    async fn all_failing_is_an_error_with_the_first_cause() {
+        let mut summary = Summary::default();
+        summary.failed(status_error(503).await.context("couldn't fetch 7"));
+        summary.failed(anyhow!("bad JSON"));
+
+        let err = summary.finish("lookups").unwrap_err();
+        assert_eq!(err.to_string(), "All lookups failed (503, error)");
+        let chain: Vec<String> = err.chain().map(|cause| cause.to_string()).collect();
+        assert_eq!(chain[1], "couldn't fetch 7");
+    }
+
+    #[test]
+    fn nothing_failing_finishes_quietly() {
+        let mut summary = Summary::default();
+        summary.succeeded();
+        assert!(summary.finish("lookups").is_ok());
+        assert!(Summary::default().finish("lookups").is_ok());
+    }
+}
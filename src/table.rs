@@ -0,0 +1,146 @@
+//! `--format table`: one aligned row per result, for scanning many results
+//! at once. Only the title column gives way when the terminal is narrow.
+
+use colored::Colorize;
+use unicode_width::UnicodeWidthStr;
+
+use crate::{
+    AnimeData, DisplayOptions, RatingBand, compare::fit, format_status, highlight_matches, output,
+    status_label, theme,
+};
+
+/// The narrowest the title column gets, however little room is left.
+const MIN_TITLE_WIDTH: usize = 10;
+
+/// Prints `anime_list` as a table of number, title, rating, episodes, status
+/// and year, fitted to `width` columns.
+pub fn display(anime_list: &[AnimeData], options: &DisplayOptions, width: usize) {
+    let theme = theme::current();
+    let rows: Vec<Row> = anime_list
+        .iter()
+        .map(|anime| Row::new(anime, options))
+        .collect();
+
+    let column = |header: &str, cell: fn(&Row) -> &str| {
+        rows.iter()
+            .map(|row| cell(row).width())
+            .chain(std::iter::once(header.width()))
+            .max()
+            .unwrap_or(0)
+    };
+    let number = anime_list.len().to_string().width().max(1);
+    let rating = column("Rating", |row| &row.rating);
+    let episodes = column("Episodes", |row| &row.episodes);
+    let status = column("Status", |row| &row.status);
+    let year = column("Year", |row| &row.year);
+    // Two spaces between each of the six columns.
+    let fixed = number + rating + episodes + status + year + 5 * 2;
+    let title = width
+        .saturating_sub(fixed)
+        .min(column("Title", |row| &row.title))
+        .max(MIN_TITLE_WIDTH);
+
+    let header = format!(
+        "{}  {}  {}  {}  {}  {}",
+        fit("#", number),
+        fit("Title", title),
+        fit("Rating", rating),
+        fit("Episodes", episodes),
+        fit("Status", status),
+        fit("Year", year),
+    );
+    outln!("{}", header.trim_end().color(theme.header).bold());
+    outln!("{}", theme.separator_line(width.min(fixed + title)));
+
+    for (i, (row, anime)) in rows.iter().zip(anime_list).enumerate() {
+        let title_cell = highlight_matches(
+            &fit(&row.title, title),
+            options.highlight.as_deref(),
+            |part| part.color(theme.title),
+        );
+        // Merged results from AniList carry AniList IDs.
+        let title_cell = match options.web_url {
+            Some(web_url) if anime.source.as_deref() != Some("anilist") => {
+                output::link(&title_cell, &format!("{}/{}", web_url, anime.id))
+            }
+            _ => title_cell,
+        };
+        let rating_cell = match row.rating_band {
+            Some(band) => fit(&row.rating, rating).color(theme.rating(band)),
+            None => fit(&row.rating, rating).dimmed(),
+        };
+        let status_cell = match &anime.attributes.status {
+            Some(value) => {
+                let mut cell = format_status(value);
+                cell.input = fit(&cell.input, status);
+                cell
+            }
+            None => fit(&row.status, status).dimmed(),
+        };
+        let line = format!(
+            "{}  {}  {}  {}  {}  {}",
+            format!("{:>number$}", i + 1).yellow().bold(),
+            title_cell,
+            rating_cell,
+            fit(&row.episodes, episodes),
+            status_cell,
+            row.year,
+        );
+        outln!("{}", line.trim_end());
+    }
+}
+
+/// One result's cells as plain text, measured before any coloring.
+struct Row {
+    title: String,
+    rating: String,
+    rating_band: Option<RatingBand>,
+    episodes: String,
+    status: String,
+    year: String,
+}
+
+impl Row {
+    fn new(anime: &AnimeData, options: &DisplayOptions) -> Row {
+        let attrs = &anime.attributes;
+        let rating = attrs
+            .rating_value()
+            .filter(|value| (0.0..=100.0).contains(value));
+        Row {
+            title: attrs.display_title(options.title_language).to_string(),
+            rating: rating.map_or_else(|| "N/A".to_string(), |value| value.to_string()),
+            rating_band: rating.map(|value| options.rating_bands.band(value as f32)),
+            episodes: attrs
+                .episode_count
+                .map_or_else(|| "?".to_string(), |count| count.to_string()),
+            status: attrs
+                .status
+                .as_deref()
+                .map_or("?", status_label)
+                .to_string(),
+            year: attrs
+                .start_date
+                .as_deref()
+                .and_then(|date| date.get(..4))
+                .unwrap_or("?")
+                .to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cells_are_cut_to_their_column_on_char_boundaries() {
+        assert_eq!(fit("Cowboy Bebop", 8), "Cowboy …");
+        assert_eq!(fit("Trigun", 8), "Trigun  ");
+        assert_eq!(fit("Trigun", 6), "Trigun");
+        // Wide characters take two columns each; one that would straddle
+        // the edge is dropped and padded for instead.
+        assert_eq!(fit("カウボーイビバップ", 8), "カウボ… ");
+        assert_eq!(fit("カウボーイビバップ", 8).width(), 8);
+        assert_eq!(fit("Ééé", 2), "É…");
+    }
+}
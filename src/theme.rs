@@ -0,0 +1,238 @@
+//! Colors and separator rules for the text output, from a built-in theme
+//! picked with `--theme` (or config `theme`) plus any `[theme]` overrides.
+//!
+//! ```toml
+//! theme = "pastel"
+//!
+//! [theme]
+//! rule = "━"
+//! title = "#88c0d0"
+//! rating_low = "bright red"
+//! ```
+//!
+//! Colors are either a name `colored` knows ("cyan", "bright black", ...) or
+//! a `#rrggbb` truecolor hex code.
+
+use std::{collections::BTreeMap, sync::OnceLock};
+
+use anyhow::{Result, anyhow, bail};
+use clap::ValueEnum;
+use colored::Color;
+use unicode_width::UnicodeWidthChar;
+
+use crate::{RatingBand, config::Value};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ThemeName {
+    /// The classic green headers and cyan titles.
+    #[default]
+    Default,
+    /// Soft truecolor tones with box-drawing rules.
+    Pastel,
+    /// Greys only, for terminals where colors clash.
+    Mono,
+}
+
+#[derive(Debug, Clone)]
+pub struct Theme {
+    /// Repeated above a listing.
+    pub rule: char,
+    /// Repeated between entries.
+    pub separator: char,
+    pub header: Color,
+    pub title: Color,
+    /// The part of a title that matches the search query.
+    pub highlight: Color,
+    pub rating_low: Color,
+    pub rating_mid: Color,
+    pub rating_high: Color,
+    pub finished: Color,
+    pub airing: Color,
+    pub upcoming: Color,
+    /// Announced without a date ("tba").
+    pub tba: Color,
+    pub unreleased: Color,
+    pub cancelled: Color,
+    pub hiatus: Color,
+    pub synopsis: Color,
+}
+
+impl Theme {
+    pub fn builtin(name: ThemeName) -> Theme {
+        match name {
+            ThemeName::Default => Theme {
+                rule: '=',
+                separator: '-',
+                header: Color::Green,
+                title: Color::Cyan,
+                highlight: Color::Yellow,
+                rating_low: Color::Red,
+                rating_mid: Color::Yellow,
+                rating_high: Color::Green,
+                finished: Color::Green,
+                airing: Color::Cyan,
+                upcoming: Color::Yellow,
+                tba: Color::Magenta,
+                unreleased: Color::BrightBlack,
+                cancelled: Color::Red,
+                hiatus: Color::Blue,
+                synopsis: rgb(200, 200, 200),
+            },
+            ThemeName::Pastel => Theme {
+                rule: '━',
+                separator: '─',
+                header: rgb(163, 190, 140),
+                title: rgb(136, 192, 208),
+                highlight: rgb(235, 203, 139),
+                rating_low: rgb(191, 97, 106),
+                rating_mid: rgb(235, 203, 139),
+                rating_high: rgb(163, 190, 140),
+                finished: rgb(163, 190, 140),
+                airing: rgb(143, 188, 187),
+                upcoming: rgb(235, 203, 139),
+                tba: rgb(180, 142, 173),
+                unreleased: rgb(129, 161, 193),
+                cancelled: rgb(191, 97, 106),
+                hiatus: rgb(208, 135, 112),
+                synopsis: rgb(216, 222, 233),
+            },
+            ThemeName::Mono => Theme {
+                rule: '=',
+                separator: '-',
+                header: Color::BrightWhite,
+                title: Color::BrightWhite,
+                highlight: Color::BrightWhite,
+                rating_low: Color::BrightBlack,
+                rating_mid: Color::White,
+                rating_high: Color::BrightWhite,
+                finished: Color::White,
+                airing: Color::BrightWhite,
+                upcoming: Color::BrightBlack,
+                tba: Color::BrightBlack,
+                unreleased: Color::BrightBlack,
+                cancelled: Color::BrightBlack,
+                hiatus: Color::White,
+                synopsis: Color::White,
+            },
+        }
+    }
+
+    /// Applies the `[theme]` table from the config file on top of this theme.
+    pub fn customize(&mut self, table: &BTreeMap<String, Value>) -> Result<()> {
+        for (key, value) in table {
+            let Value::String(text) = value else {
+                bail!("`theme.{}` must be a string", key);
+            };
+            match key.as_str() {
+                "rule" => self.rule = rule_char(key, text)?,
+                "separator" => self.separator = rule_char(key, text)?,
+                _ => {
+                    *self.color_mut(key)? =
+                        parse_color(text).map_err(|err| anyhow!("`theme.{}`: {}", key, err))?
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn color_mut(&mut self, key: &str) -> Result<&mut Color> {
+        Ok(match key {
+            "header" => &mut self.header,
+            "title" => &mut self.title,
+            "highlight" => &mut self.highlight,
+            "rating_low" => &mut self.rating_low,
+            "rating_mid" => &mut self.rating_mid,
+            "rating_high" => &mut self.rating_high,
+            "finished" => &mut self.finished,
+            "airing" => &mut self.airing,
+            "upcoming" => &mut self.upcoming,
+            "tba" => &mut self.tba,
+            "unreleased" => &mut self.unreleased,
+            "cancelled" => &mut self.cancelled,
+            "hiatus" => &mut self.hiatus,
+            "synopsis" => &mut self.synopsis,
+            _ => bail!("unknown setting `theme.{}`", key),
+        })
+    }
+
+    pub fn rating(&self, band: RatingBand) -> Color {
+        match band {
+            RatingBand::Low => self.rating_low,
+            RatingBand::Mid => self.rating_mid,
+            RatingBand::High => self.rating_high,
+        }
+    }
+
+    /// Falls back to `=` and `-` for rules an ASCII-only terminal can't draw.
+    pub fn ascii_rules(&mut self) {
+        if !self.rule.is_ascii() {
+            self.rule = '=';
+        }
+        if !self.separator.is_ascii() {
+            self.separator = '-';
+        }
+    }
+
+    /// The rule drawn above a listing, `width` columns wide.
+    pub fn rule_line(&self, width: usize) -> String {
+        self.rule.to_string().repeat(width)
+    }
+
+    /// The rule drawn between entries, `width` columns wide.
+    pub fn separator_line(&self, width: usize) -> String {
+        self.separator.to_string().repeat(width)
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme::builtin(ThemeName::Default)
+    }
+}
+
+fn rgb(r: u8, g: u8, b: u8) -> Color {
+    Color::TrueColor { r, g, b }
+}
+
+/// Parses a color name or `#rrggbb`.
+pub fn parse_color(text: &str) -> Result<Color> {
+    let text = text.trim();
+    if let Some(hex) = text.strip_prefix('#') {
+        let channel = |i: usize| {
+            hex.get(i..i + 2)
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+        };
+        return match (hex.len(), channel(0), channel(2), channel(4)) {
+            (6, Some(r), Some(g), Some(b)) => Ok(rgb(r, g, b)),
+            _ => bail!("\"{}\" is not a #rrggbb color", text),
+        };
+    }
+    text.parse().map_err(|()| {
+        anyhow!(
+            "unknown color \"{}\"; use a name like \"cyan\" or \"bright black\", or #rrggbb",
+            text
+        )
+    })
+}
+
+/// Rules repeat one character per column, so it must be exactly one column
+/// wide.
+fn rule_char(key: &str, text: &str) -> Result<char> {
+    let mut chars = text.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.width() == Some(1) => Ok(c),
+        _ => bail!("`theme.{}` must be a single one-column character", key),
+    }
+}
+
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+/// Sets the theme for the rest of the run. Only the first call takes effect.
+pub fn set(theme: Theme) {
+    let _ = THEME.set(theme);
+}
+
+/// The theme set at startup, or the default one.
+pub fn current() -> &'static Theme {
+    THEME.get_or_init(Theme::default)
+}
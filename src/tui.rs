@@ -0,0 +1,1891 @@
+use std::{
+    cell::Cell,
+    collections::{HashMap, HashSet},
+    io,
+    process::{Command, Stdio},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+use anyhow::{Result, bail};
+use crossterm::{
+    cursor::Show,
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute,
+    terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
+};
+use futures_util::{StreamExt, stream};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+use tui::{
+    Frame, Terminal,
+    backend::{Backend, CrosstermBackend},
+    buffer::Buffer,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, Clear, List, ListItem, Paragraph, Tabs, Widget},
+};
+use unicode_width::UnicodeWidthStr;
+
+use crate::{
+    AnimeData, CATEGORIES_TTL, DEFAULT_CONCURRENCY, ImageSize, KITSU_WEB_URL, Media, Page,
+    RatingBand, RatingBands, SearchOptions, TitleLanguage, cache::CategoriesCache, compare,
+    console, enrich::Enrichments, fetch_categories, fetch_streaming_links, filter_nsfw,
+    format_status, glyphs, history::History, http::HttpClient, info_parts, offline,
+    popularity_parts, search_anime, session::Session, watchlist::Watchlist,
+};
+
+const SEARCH_HELP: &str =
+    "Press 'e' to enter search mode and type your query; results update as you type.";
+const NO_RESULTS_HELP: &str = "No results found. Try a different search term.";
+const NO_FAVORITES_HELP: &str =
+    "No favorites yet. Select an anime in your search results and press 'f' to add it.";
+
+/// Key bindings listed in the '?' help overlay.
+const HELP_LINES: [(&str, &str); 22] = [
+    ("e", "edit the search query"),
+    ("/", "filter results by title"),
+    ("Enter", "finish editing or filtering"),
+    ("Esc", "stop editing / clear filter"),
+    ("Up/Down", "select a result / recall past searches"),
+    ("Tab", "switch between tabs"),
+    ("1 / 2", "go to search / details tab"),
+    ("v", "toggle side-by-side results and details"),
+    ("j / k", "scroll the synopsis"),
+    ("PgUp/PgDn", "scroll the synopsis a page"),
+    ("w", "toggle synopsis wrapping"),
+    ("t", "toggle trimming wrapped lines"),
+    ("o", "open the selection on Kitsu"),
+    ("P", "pin the selection to compare against"),
+    ("C", "compare the selection with the pinned one"),
+    ("f", "add / remove the selection from favorites"),
+    ("F", "show your favorites"),
+    ("n / p", "next / previous page of results"),
+    ("r", "re-run the last search"),
+    ("c", "clear the query and results"),
+    ("?", "toggle this help"),
+    ("q / Ctrl-C", "quit"),
+];
+
+/// How long typing must pause before the query is searched.
+const SEARCH_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Lines moved by PageUp/PageDown in the details synopsis.
+const SYNOPSIS_PAGE: i32 = 5;
+
+/// Terminals at least this wide show results and details side by side unless
+/// the user picked a layout with 'v'.
+const SPLIT_MIN_WIDTH: u16 = 140;
+
+enum InputMode {
+    Normal,
+    Editing,
+    Filtering,
+}
+
+enum Tab {
+    Search,
+    Details,
+}
+
+/// Whether results and details share the screen or take turns as tabs.
+#[derive(Clone, Copy)]
+enum LayoutMode {
+    /// Split on wide terminals and tabbed on narrow ones.
+    Auto,
+    Split,
+    Tabbed,
+}
+
+struct App {
+    client: HttpClient,
+    rating_bands: RatingBands,
+    title_language: TitleLanguage,
+    input: String,
+    input_mode: InputMode,
+    active_tab: Tab,
+    layout: LayoutMode,
+    /// Whether the last frame was drawn split, which decides (for `Auto`)
+    /// whether the details keys apply on the search tab.
+    split_shown: Cell<bool>,
+    search_results: Vec<AnimeData>,
+    selected_anime_index: Option<usize>,
+    loading: bool,
+    error: Option<String>,
+    /// Local fuzzy filter over the titles in `search_results`.
+    filter: String,
+    /// Lines scrolled past at the top of the details synopsis.
+    synopsis_scroll: u16,
+    /// Largest useful `synopsis_scroll`, measured when the synopsis is drawn.
+    synopsis_max_scroll: Cell<u16>,
+    /// Whether the synopsis wraps to the box width or runs off the edge.
+    synopsis_wrap: bool,
+    /// Whether wrapped lines drop their leading whitespace. Off keeps the
+    /// synopsis's own indentation and blank-line structure intact.
+    synopsis_trim: bool,
+    /// One-off message shown in the status bar until the next key press.
+    status: Option<String>,
+    /// Whether the '?' key binding overlay is open.
+    show_help: bool,
+    /// The anime pinned with 'P', kept even when it drops out of the results.
+    pinned: Option<AnimeData>,
+    /// The selection being compared with `pinned`, while 'C' shows it.
+    comparing: Option<AnimeData>,
+    /// Past queries, most recent first, recalled with Up/Down while editing.
+    history: Vec<String>,
+    /// Which `history` entry is in the input, if one was recalled.
+    history_position: Option<usize>,
+    /// What was typed before recalling history, restored by Down.
+    draft: String,
+    /// Bumped for every new query; a search only applies its results if the
+    /// generation it started with is still current.
+    search_generation: u64,
+    /// The query most recently sent to Kitsu, re-run by 'r'.
+    last_query: Option<String>,
+    /// Which page of `last_query`'s results is shown, from 1.
+    page: u32,
+    /// Matches across every page, when Kitsu says.
+    total_results: Option<u64>,
+    /// Whether Kitsu has a page after this one, which gates 'n'.
+    has_next_page: bool,
+    /// Streaming links per anime id, fetched the first time its details are
+    /// shown.
+    streaming: HashMap<String, Streaming>,
+    /// "5/12" per watchlist anime id with any progress recorded.
+    progress: HashMap<String, String>,
+    /// Ids of the anime starred with 'f'.
+    favorites: HashSet<String>,
+    /// Whether the results are the favorites from 'F' rather than a search.
+    showing_favorites: bool,
+    /// Lookups to run for every page of results as soon as it is shown.
+    enrichments: Enrichments,
+    /// Whether searches keep results marked as adult content.
+    include_nsfw: bool,
+    /// Ids of results whose genres are still being looked up.
+    genres_loading: HashSet<String>,
+    /// Where background lookups send what they found, for the event loop to
+    /// apply between frames.
+    updates: UnboundedSender<Update>,
+}
+
+/// A finished background lookup, tagged with the search generation it was
+/// started for so that one outlived by a newer query can be dropped.
+enum Update {
+    Genres {
+        generation: u64,
+        id: String,
+        genres: Vec<String>,
+    },
+    Streaming {
+        generation: u64,
+        id: String,
+        streaming: Streaming,
+    },
+}
+
+/// Where a details-tab streaming lookup stands.
+enum Streaming {
+    Loading,
+    /// `(streamer, url)` pairs.
+    Loaded(Vec<(String, String)>),
+    Failed(String),
+}
+
+impl App {
+    fn new(
+        client: HttpClient,
+        rating_bands: RatingBands,
+        title_language: TitleLanguage,
+        enrichments: Enrichments,
+        updates: UnboundedSender<Update>,
+    ) -> App {
+        App {
+            client,
+            rating_bands,
+            title_language,
+            input: String::new(),
+            input_mode: InputMode::Normal,
+            active_tab: Tab::Search,
+            layout: LayoutMode::Auto,
+            split_shown: Cell::new(false),
+            search_results: Vec::new(),
+            selected_anime_index: None,
+            loading: false,
+            error: None,
+            filter: String::new(),
+            synopsis_scroll: 0,
+            synopsis_max_scroll: Cell::new(0),
+            synopsis_wrap: true,
+            synopsis_trim: true,
+            status: None,
+            show_help: false,
+            pinned: None,
+            comparing: None,
+            history: Vec::new(),
+            history_position: None,
+            draft: String::new(),
+            search_generation: 0,
+            last_query: None,
+            page: 1,
+            total_results: None,
+            has_next_page: false,
+            streaming: HashMap::new(),
+            progress: HashMap::new(),
+            favorites: HashSet::new(),
+            showing_favorites: false,
+            enrichments,
+            include_nsfw: false,
+            genres_loading: HashSet::new(),
+            updates,
+        }
+    }
+
+    /// Changes the selection, starting the synopsis from the top whenever a
+    /// different anime is picked.
+    fn select(&mut self, index: Option<usize>) {
+        if index != self.selected_anime_index {
+            self.synopsis_scroll = 0;
+        }
+        self.selected_anime_index = index;
+    }
+
+    fn scroll_synopsis(&mut self, delta: i32) {
+        let max = i32::from(self.synopsis_max_scroll.get());
+        self.synopsis_scroll = (i32::from(self.synopsis_scroll) + delta).clamp(0, max) as u16;
+    }
+
+    /// Indices into `search_results` that pass the current filter.
+    fn visible_indices(&self) -> Vec<usize> {
+        self.search_results
+            .iter()
+            .enumerate()
+            .filter(|(_, anime)| {
+                let title = anime.attributes.display_title(self.title_language);
+                fuzzy_match(&self.filter, title).is_some()
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Keeps the selection on a visible entry after the filter changes.
+    fn refresh_filter(&mut self) {
+        let visible = self.visible_indices();
+        if !self
+            .selected_anime_index
+            .is_some_and(|i| visible.contains(&i))
+        {
+            self.select(visible.first().copied());
+        }
+    }
+
+    fn select_next(&mut self) {
+        let visible = self.visible_indices();
+        let position = self
+            .selected_anime_index
+            .and_then(|i| visible.iter().position(|&v| v == i));
+        self.select(match position {
+            Some(p) => visible.get(p + 1).or(visible.get(p)).copied(),
+            None => visible.first().copied(),
+        });
+    }
+
+    fn select_previous(&mut self) {
+        let visible = self.visible_indices();
+        let position = self
+            .selected_anime_index
+            .and_then(|i| visible.iter().position(|&v| v == i));
+        self.select(match position {
+            Some(p) => visible.get(p.saturating_sub(1)).copied(),
+            None => visible.first().copied(),
+        });
+    }
+
+    /// Steps through `history`, towards older queries when `older`, and back
+    /// to the draft past the newest. Returns whether the input changed.
+    fn recall_history(&mut self, older: bool) -> bool {
+        let next = match (self.history_position, older) {
+            (None, true) if !self.history.is_empty() => Some(0),
+            (Some(i), true) => Some((i + 1).min(self.history.len() - 1)),
+            (Some(0), false) => None,
+            (Some(i), false) => Some(i - 1),
+            _ => return false,
+        };
+
+        if self.history_position.is_none() {
+            self.draft = self.input.clone();
+        }
+        self.input = match next {
+            Some(i) => self.history[i].clone(),
+            None => std::mem::take(&mut self.draft),
+        };
+        self.history_position = next;
+        true
+    }
+
+    /// Picks up where `session` left off, ignoring a stale selection.
+    fn restore(&mut self, session: Session) {
+        self.input = session.input;
+        self.last_query = (!self.input.trim().is_empty()).then(|| self.input.trim().to_string());
+        self.search_results = session.search_results;
+        self.select(
+            session
+                .selected_anime_index
+                .filter(|&i| i < self.search_results.len()),
+        );
+    }
+
+    fn session(&self) -> Session {
+        Session::new(
+            self.input.clone(),
+            self.search_results.clone(),
+            self.selected_anime_index,
+        )
+    }
+
+    fn selected_anime(&self) -> Option<&AnimeData> {
+        self.selected_anime_index
+            .and_then(|i| self.search_results.get(i))
+    }
+
+    /// Opens the selected anime's Kitsu page, reporting the outcome in the
+    /// status line.
+    fn open_selected(&mut self) {
+        let Some(anime) = self.selected_anime() else {
+            self.status = Some("Select an anime to open first.".to_string());
+            return;
+        };
+        let url = format!("{}/{}", KITSU_WEB_URL, anime.id);
+
+        self.status = Some(match open_in_browser(&url) {
+            Ok(()) => format!("Opened {}", url),
+            Err(err) => format!("Couldn't open a browser ({}); visit {}", err, url),
+        });
+    }
+
+    /// Pins the selection for 'C' to compare with, or unpins it if it
+    /// already is.
+    fn pin_selected(&mut self) {
+        let Some(anime) = self.selected_anime().cloned() else {
+            self.status = Some("Select an anime to pin first.".to_string());
+            return;
+        };
+        let title = anime
+            .attributes
+            .display_title(self.title_language)
+            .to_string();
+        if self
+            .pinned
+            .as_ref()
+            .is_some_and(|pinned| pinned.id == anime.id)
+        {
+            self.pinned = None;
+            self.status = Some(format!("Unpinned {}", title));
+        } else {
+            self.pinned = Some(anime);
+            self.status = Some(format!(
+                "Pinned {}; select another and press C to compare",
+                title
+            ));
+        }
+    }
+
+    /// Opens the comparison of the pinned anime with the selection.
+    fn compare_selected(&mut self) {
+        let Some(pinned) = &self.pinned else {
+            self.status = Some("Pin an anime with 'P' first.".to_string());
+            return;
+        };
+        match self.selected_anime() {
+            Some(anime) if anime.id != pinned.id => self.comparing = Some(anime.clone()),
+            Some(_) => self.status = Some("Select a different anime to compare.".to_string()),
+            None => self.status = Some("Select an anime to compare first.".to_string()),
+        }
+    }
+
+    /// Stars or unstars the selection, saving it to the watchlist.
+    fn favorite_selected(&mut self) {
+        let Some(anime) = self.selected_anime().cloned() else {
+            self.status = Some("Select an anime to favorite first.".to_string());
+            return;
+        };
+        let title = anime
+            .attributes
+            .display_title(self.title_language)
+            .to_string();
+        let saved = Watchlist::load().and_then(|mut watchlist| {
+            let favorite = watchlist.toggle_favorite(&anime);
+            watchlist.save().map(|()| favorite)
+        });
+        self.status = Some(match saved {
+            Ok(true) => {
+                self.favorites.insert(anime.id);
+                format!("Added {} to favorites", title)
+            }
+            Ok(false) => {
+                self.favorites.remove(&anime.id);
+                format!(
+                    "Removed {} from favorites (it stays on your watchlist)",
+                    title
+                )
+            }
+            Err(err) => format!("Couldn't save favorites: {:#}", err),
+        });
+    }
+
+    /// Replaces the results with the favorites saved in the watchlist, as
+    /// of their last fetch, without searching. Any search in flight is
+    /// cancelled.
+    fn show_favorites(&mut self) {
+        let watchlist = match Watchlist::load() {
+            Ok(watchlist) => watchlist,
+            Err(err) => {
+                self.status = Some(format!("Couldn't load favorites: {:#}", err));
+                return;
+            }
+        };
+        self.clear();
+        self.showing_favorites = true;
+        self.search_results = watchlist
+            .entries
+            .into_iter()
+            .filter(|entry| entry.favorite)
+            .filter_map(|entry| {
+                Some(AnimeData {
+                    id: entry.id,
+                    attributes: entry.attributes?,
+                    categories: Vec::new(),
+                    streamers: Vec::new(),
+                    source: None,
+                })
+            })
+            .collect();
+        self.select((!self.search_results.is_empty()).then_some(0));
+        self.active_tab = Tab::Search;
+    }
+
+    /// Empties the query and results, cancelling any search in flight.
+    fn clear(&mut self) {
+        self.search_generation += 1;
+        self.showing_favorites = false;
+        self.loading = false;
+        self.error = None;
+        self.input.clear();
+        self.filter.clear();
+        self.search_results.clear();
+        self.select(None);
+        self.last_query = None;
+        self.page = 1;
+        self.total_results = None;
+        self.has_next_page = false;
+    }
+
+    /// "Page 2 of 35", or just "Page 2" when the total is unknown.
+    fn page_label(&self) -> String {
+        let limit = u64::from(Page::default().limit);
+        match self.total_results {
+            Some(total) => format!("Page {} of {}", self.page, total.div_ceil(limit).max(1)),
+            None => format!("Page {}", self.page),
+        }
+    }
+
+    fn split(&self, width: u16) -> bool {
+        match self.layout {
+            LayoutMode::Auto => width >= SPLIT_MIN_WIDTH,
+            LayoutMode::Split => true,
+            LayoutMode::Tabbed => false,
+        }
+    }
+
+    /// Whether the details of the selection are on screen.
+    fn details_shown(&self) -> bool {
+        matches!(self.active_tab, Tab::Details) || self.split_shown.get()
+    }
+
+    /// Switches to whichever layout isn't showing, which sticks from then on
+    /// whatever the terminal width.
+    fn toggle_layout(&mut self) {
+        if self.split_shown.get() {
+            self.layout = LayoutMode::Tabbed;
+            self.status = Some("Tabbed layout".to_string());
+        } else {
+            self.layout = LayoutMode::Split;
+            self.status = Some("Side-by-side layout".to_string());
+        }
+    }
+
+    fn toggle_tab(&mut self) {
+        self.active_tab = match self.active_tab {
+            Tab::Search => Tab::Details,
+            Tab::Details => Tab::Search,
+        };
+    }
+}
+
+/// Restores the terminal when dropped, so an early return or panic inside the
+/// event loop never leaves the user's shell in raw mode.
+///
+/// Invariant: a guard exists exactly while raw mode may be on. It is created
+/// as soon as raw mode is, before anything else that can fail, and
+/// `restore_terminal` is safe to run more than once (the panic hook and
+/// `Drop` may both run it).
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn new() -> Result<TerminalGuard> {
+        if !console::enable_ansi() {
+            bail!(console::LEGACY_CONSOLE);
+        }
+        enable_raw_mode()?;
+        let guard = TerminalGuard;
+        execute!(io::stdout(), EnterAlternateScreen)?;
+
+        // The default hook prints the panic message before unwinding reaches
+        // the guard, so restore first or the message is lost on the
+        // alternate screen.
+        let default_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            restore_terminal();
+            default_hook(info);
+        }));
+
+        Ok(guard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore_terminal();
+    }
+}
+
+fn restore_terminal() {
+    let _ = disable_raw_mode();
+    let _ = execute!(io::stdout(), LeaveAlternateScreen, Show);
+}
+
+/// Runs the TUI until the user quits, then saves the session. With `resume`,
+/// the previously saved session is restored first.
+pub async fn run(
+    client: HttpClient,
+    resume: bool,
+    rating_bands: RatingBands,
+    title_language: TitleLanguage,
+    enrichments: Enrichments,
+    include_nsfw: bool,
+) -> Result<()> {
+    let (updates, mut received) = unbounded_channel();
+    let mut app = App::new(client, rating_bands, title_language, enrichments, updates);
+    app.include_nsfw = include_nsfw;
+    app.history = History::load()?
+        .entries
+        .into_iter()
+        .rev()
+        .map(|entry| entry.query)
+        .collect();
+    let watchlist = Watchlist::load()?;
+    app.progress = watchlist
+        .entries
+        .iter()
+        .filter(|entry| entry.progress > 0)
+        .map(|entry| (entry.id.clone(), entry.progress_label()))
+        .collect();
+    app.favorites = watchlist
+        .entries
+        .iter()
+        .filter(|entry| entry.favorite)
+        .map(|entry| entry.id.clone())
+        .collect();
+    if resume && let Some(session) = Session::load()? {
+        app.restore(session);
+    }
+    let app = Arc::new(Mutex::new(app));
+
+    // Raw mode turns Ctrl-C into a key press (see `handle_key`), but a SIGINT
+    // sent from elsewhere still arrives as a signal. Either way, quit through
+    // the normal path so the terminal is restored and the session saved.
+    let interrupted = Arc::new(AtomicBool::new(false));
+    {
+        let interrupted = Arc::clone(&interrupted);
+        tokio::spawn(async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                interrupted.store(true, Ordering::Relaxed);
+            }
+        });
+    }
+
+    let _guard = TerminalGuard::new()?;
+
+    let backend = CrosstermBackend::new(io::stdout());
+    let mut terminal = Terminal::new(backend)?;
+
+    while !interrupted.load(Ordering::Relaxed) {
+        apply_updates(&app, &mut received);
+        load_streaming_links(&app);
+        terminal.draw(|f| ui(f, &app))?;
+
+        if event::poll(Duration::from_millis(100))?
+            && let Event::Key(key) = event::read()?
+            && key.kind == KeyEventKind::Press
+            && handle_key(&app, key)
+        {
+            break;
+        }
+    }
+
+    let session = app.lock().unwrap().session();
+    session.save()
+}
+
+/// Applies a key press to the app state. Returns `true` when the user asked
+/// to quit.
+fn handle_key(app: &Arc<Mutex<App>>, key: KeyEvent) -> bool {
+    if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+        return true;
+    }
+
+    let mut state = app.lock().unwrap();
+    state.status = None;
+
+    if state.show_help {
+        // The overlay swallows keys until it is closed.
+        if let KeyCode::Char('?') | KeyCode::Esc = key.code {
+            state.show_help = false;
+        }
+        return false;
+    }
+    if state.comparing.is_some() {
+        if let KeyCode::Char('C') | KeyCode::Esc = key.code {
+            state.comparing = None;
+        }
+        return false;
+    }
+
+    match state.input_mode {
+        InputMode::Normal => match key.code {
+            KeyCode::Char('q') => return true,
+            KeyCode::Char('o') => state.open_selected(),
+            KeyCode::Char('P') => state.pin_selected(),
+            KeyCode::Char('C') => state.compare_selected(),
+            KeyCode::Char('f') => state.favorite_selected(),
+            KeyCode::Char('F') => state.show_favorites(),
+            KeyCode::Char('r') => match state.last_query.clone() {
+                Some(query) => {
+                    state.input = query;
+                    drop(state);
+                    start_search(app, SearchTrigger::Refresh);
+                }
+                None => state.status = Some("Nothing to refresh yet.".to_string()),
+            },
+            KeyCode::Char('n') => {
+                drop(state);
+                change_page(app, 1);
+            }
+            KeyCode::Char('p') => {
+                drop(state);
+                change_page(app, -1);
+            }
+            KeyCode::Char('c') => state.clear(),
+            KeyCode::Char('?') => state.show_help = true,
+            KeyCode::Char('e') => state.input_mode = InputMode::Editing,
+            KeyCode::Char('/') => state.input_mode = InputMode::Filtering,
+            KeyCode::Char('1') => state.active_tab = Tab::Search,
+            KeyCode::Char('2') => state.active_tab = Tab::Details,
+            KeyCode::Tab => state.toggle_tab(),
+            KeyCode::Char('v') => state.toggle_layout(),
+            KeyCode::Down => state.select_next(),
+            KeyCode::Up => state.select_previous(),
+            KeyCode::Char('j') if state.details_shown() => state.scroll_synopsis(1),
+            KeyCode::Char('k') if state.details_shown() => state.scroll_synopsis(-1),
+            KeyCode::PageDown if state.details_shown() => state.scroll_synopsis(SYNOPSIS_PAGE),
+            KeyCode::PageUp if state.details_shown() => state.scroll_synopsis(-SYNOPSIS_PAGE),
+            KeyCode::Char('w') if state.details_shown() => {
+                state.synopsis_wrap = !state.synopsis_wrap;
+                state.status = Some(format!("Synopsis wrapping {}", on_off(state.synopsis_wrap)));
+            }
+            KeyCode::Char('t') if state.details_shown() => {
+                state.synopsis_trim = !state.synopsis_trim;
+                state.status = Some(format!("Synopsis trimming {}", on_off(state.synopsis_trim)));
+            }
+            _ => {}
+        },
+        InputMode::Editing => match key.code {
+            KeyCode::Enter => {
+                state.input_mode = InputMode::Normal;
+                state.history_position = None;
+                drop(state);
+                start_search(app, SearchTrigger::Submitted);
+            }
+            KeyCode::Esc => state.input_mode = InputMode::Normal,
+            KeyCode::Up | KeyCode::Down if state.recall_history(key.code == KeyCode::Up) => {
+                drop(state);
+                start_search(app, SearchTrigger::Typing);
+            }
+            KeyCode::Backspace => {
+                state.input.pop();
+                state.history_position = None;
+                drop(state);
+                start_search(app, SearchTrigger::Typing);
+            }
+            KeyCode::Char(c) => {
+                state.input.push(c);
+                state.history_position = None;
+                drop(state);
+                start_search(app, SearchTrigger::Typing);
+            }
+            _ => {}
+        },
+        InputMode::Filtering => match key.code {
+            KeyCode::Enter => state.input_mode = InputMode::Normal,
+            KeyCode::Esc => {
+                state.input_mode = InputMode::Normal;
+                state.filter.clear();
+                state.refresh_filter();
+            }
+            KeyCode::Backspace => {
+                state.filter.pop();
+                state.refresh_filter();
+            }
+            KeyCode::Char(c) => {
+                state.filter.push(c);
+                state.refresh_filter();
+            }
+            _ => {}
+        },
+    }
+
+    false
+}
+
+fn on_off(enabled: bool) -> &'static str {
+    if enabled { "on" } else { "off" }
+}
+
+/// Hands `url` to the platform's opener. Output is discarded so it can't
+/// scribble over the TUI.
+fn open_in_browser(url: &str) -> io::Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        // The empty argument is `start`'s window title.
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+
+    let status = command
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("opener exited with {}", status)))
+    }
+}
+
+/// Why a search is starting, which decides how long it waits and whether it
+/// is remembered in the search history.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SearchTrigger {
+    /// Debounced while the query is still being edited.
+    Typing,
+    /// Enter was pressed: search right away and record the query.
+    Submitted,
+    /// 'r' was pressed: search again right away, keeping the current results
+    /// if it fails.
+    Refresh,
+    /// 'n' or 'p' was pressed: fetch another page of the last query, keeping
+    /// the current one if that fails.
+    Page,
+}
+
+/// Searches for the current input, superseding any search that is still
+/// waiting or in flight.
+fn start_search(app: &Arc<Mutex<App>>, trigger: SearchTrigger) {
+    let mut state = app.lock().unwrap();
+    state.search_generation += 1;
+    let generation = state.search_generation;
+
+    let query = state.input.trim().to_string();
+    if query.is_empty() {
+        // Nothing to search for, but any older search is still cancelled.
+        state.loading = false;
+        return;
+    }
+
+    // A refresh stays on the page it was on; a new query starts over.
+    let page = if trigger == SearchTrigger::Refresh {
+        state.page
+    } else {
+        1
+    };
+    state.last_query = Some(query.clone());
+    let client = state.client.clone();
+    drop(state);
+    spawn_search(Arc::clone(app), client, query, page, generation, trigger);
+}
+
+/// Fetches the page `delta` away from the current one of the last query,
+/// unless that would go past either end or a search is still loading.
+fn change_page(app: &Arc<Mutex<App>>, delta: i32) {
+    let mut state = app.lock().unwrap();
+    let Some(query) = state.last_query.clone() else {
+        state.status = Some("Search for something first.".to_string());
+        return;
+    };
+    if state.loading {
+        state.status = Some("Still loading; try again in a moment.".to_string());
+        return;
+    }
+    let page = state.page.saturating_add_signed(delta);
+    if page < 1 || (delta > 0 && !state.has_next_page) {
+        let end = if delta > 0 { "last" } else { "first" };
+        state.status = Some(format!("Already on the {} page.", end));
+        return;
+    }
+
+    state.search_generation += 1;
+    let generation = state.search_generation;
+    let client = state.client.clone();
+    drop(state);
+    spawn_search(
+        Arc::clone(app),
+        client,
+        query,
+        page,
+        generation,
+        SearchTrigger::Page,
+    );
+}
+
+fn spawn_search(
+    app: Arc<Mutex<App>>,
+    client: HttpClient,
+    query: String,
+    page: u32,
+    generation: u64,
+    trigger: SearchTrigger,
+) {
+    tokio::spawn(async move {
+        if trigger == SearchTrigger::Typing {
+            tokio::time::sleep(SEARCH_DEBOUNCE).await;
+        }
+        let include_nsfw = {
+            let mut state = app.lock().unwrap();
+            if state.search_generation != generation {
+                return;
+            }
+            state.loading = true;
+            state.error = None;
+            state.include_nsfw
+        };
+
+        let options = SearchOptions {
+            page: Page::numbered(page, Page::default().limit),
+            ..SearchOptions::default()
+        };
+        let mut result = search_anime(&client, client.kitsu_api(), &query, &options).await;
+        let hidden_nsfw = match &mut result {
+            Ok(response) if !include_nsfw => filter_nsfw(&mut response.data),
+            _ => 0,
+        };
+
+        let mut state = app.lock().unwrap();
+        if state.search_generation != generation {
+            // A newer query owns `loading` and the results now.
+            return;
+        }
+        state.loading = false;
+        match result {
+            Ok(response) => {
+                if trigger == SearchTrigger::Submitted {
+                    if let Err(err) = History::append(&query, response.data.len()) {
+                        state.status = Some(format!("Couldn't save search history: {:#}", err));
+                    }
+                    state.history.retain(|past| *past != query);
+                    state.history.insert(0, query);
+                }
+                if response.skipped > 0 {
+                    state.status = Some(format!(
+                        "Skipped {} result(s) that came back in an unexpected format",
+                        response.skipped
+                    ));
+                }
+                if hidden_nsfw > 0 {
+                    state.status = Some(format!(
+                        "{} adult result(s) hidden; run with --include-nsfw to show them",
+                        hidden_nsfw
+                    ));
+                }
+                state.page = page;
+                state.total_results = response.meta.as_ref().and_then(|meta| meta.count);
+                state.has_next_page = response.has_next_page();
+                state.selected_anime_index = None;
+                state.select((!response.data.is_empty()).then_some(0));
+                state.search_results = response.data;
+                state.showing_favorites = false;
+                state.filter.clear();
+                enrich_results(&mut state, generation);
+            }
+            Err(err) if trigger == SearchTrigger::Refresh => {
+                state.status = Some(format!("Refresh failed: {:#}", err));
+            }
+            Err(err) if trigger == SearchTrigger::Page => {
+                state.status = Some(format!("Couldn't load page {}: {:#}", page, err));
+            }
+            Err(err) => {
+                state.search_results.clear();
+                state.selected_anime_index = None;
+                state.error = Some(format!("Search failed: {:#}", err));
+            }
+        }
+    });
+}
+
+/// Starts fetching the streaming links of the anime shown on the details
+/// tab, unless they were already looked up.
+fn load_streaming_links(app: &Arc<Mutex<App>>) {
+    let mut state = app.lock().unwrap();
+    if !state.details_shown() {
+        return;
+    }
+    let Some(id) = state.selected_anime().map(|anime| anime.id.clone()) else {
+        return;
+    };
+    if state.streaming.contains_key(&id) {
+        return;
+    }
+    state.streaming.insert(id.clone(), Streaming::Loading);
+    let generation = state.search_generation;
+    let client = state.client.clone();
+    let updates = state.updates.clone();
+    drop(state);
+
+    tokio::spawn(async move {
+        let streaming = lookup_streaming(&client, &id).await;
+        let _ = updates.send(Update::Streaming {
+            generation,
+            id,
+            streaming,
+        });
+    });
+}
+
+async fn lookup_streaming(client: &HttpClient, id: &str) -> Streaming {
+    match fetch_streaming_links(client, id).await {
+        Ok(links) => Streaming::Loaded(
+            links
+                .data
+                .iter()
+                .map(|link| {
+                    (
+                        links.streamer_name(link).to_string(),
+                        link.attributes.url.clone(),
+                    )
+                })
+                .collect(),
+        ),
+        Err(err) => Streaming::Failed(format!("{:#}", err)),
+    }
+}
+
+/// Starts the `--with-genres`/`--with-streaming` lookups for the results
+/// just shown. They run in the background, a few at a time, and each one is
+/// drawn as soon as it arrives rather than after all of them.
+fn enrich_results(state: &mut App, generation: u64) {
+    let wanted = state.enrichments;
+    state.genres_loading.clear();
+    if !wanted.any() {
+        return;
+    }
+    let mut cache = match CategoriesCache::load() {
+        Ok(cache) => cache,
+        Err(err) => {
+            state.status = Some(format!("Couldn't open the genres cache: {:#}", err));
+            return;
+        }
+    };
+    let key = |id: &str| format!("{}/{}", Media::Anime.path(), id);
+
+    let mut genres = Vec::new();
+    let mut streaming = Vec::new();
+    for i in 0..state.search_results.len() {
+        let id = state.search_results[i].id.clone();
+        // Results that already carry genres (from AniList) keep them.
+        if wanted.genres && state.search_results[i].categories.is_empty() {
+            match cache.get(&key(&id), CATEGORIES_TTL) {
+                Some(cached) => state.search_results[i].categories = cached.clone(),
+                None => {
+                    state.genres_loading.insert(id.clone());
+                    genres.push(id.clone());
+                }
+            }
+        }
+        if wanted.streaming && !state.streaming.contains_key(&id) {
+            state.streaming.insert(id.clone(), Streaming::Loading);
+            streaming.push(id);
+        }
+    }
+    if genres.is_empty() && streaming.is_empty() {
+        return;
+    }
+
+    let client = state.client.clone();
+    let updates = state.updates.clone();
+    tokio::spawn(async move {
+        let client = &client;
+        let lookups = genres
+            .into_iter()
+            .map(|id| (id, true))
+            .chain(streaming.into_iter().map(|id| (id, false)));
+        let mut found = stream::iter(lookups)
+            .map(|(id, genres)| async move {
+                if genres {
+                    let genres = fetch_categories(client, Media::Anime, &id)
+                        .await
+                        .unwrap_or_default();
+                    Update::Genres {
+                        generation,
+                        id,
+                        genres,
+                    }
+                } else {
+                    let streaming = lookup_streaming(client, &id).await;
+                    Update::Streaming {
+                        generation,
+                        id,
+                        streaming,
+                    }
+                }
+            })
+            .buffer_unordered(DEFAULT_CONCURRENCY as usize);
+
+        while let Some(update) = found.next().await {
+            if let Update::Genres { id, genres, .. } = &update
+                && !genres.is_empty()
+            {
+                cache.insert(key(id), genres.clone());
+            }
+            if updates.send(update).is_err() {
+                // The TUI has quit.
+                break;
+            }
+        }
+        let _ = cache.save();
+    });
+}
+
+/// Applies whatever background lookups have finished since the last frame,
+/// dropping those started for a search that has since been replaced.
+fn apply_updates(app: &Arc<Mutex<App>>, received: &mut UnboundedReceiver<Update>) {
+    let mut state = app.lock().unwrap();
+    while let Ok(update) = received.try_recv() {
+        let current = state.search_generation;
+        match update {
+            Update::Genres {
+                generation,
+                id,
+                genres,
+            } => {
+                state.genres_loading.remove(&id);
+                if generation != current {
+                    continue;
+                }
+                for anime in state
+                    .search_results
+                    .iter_mut()
+                    .filter(|anime| anime.id == id)
+                {
+                    anime.categories = genres.clone();
+                }
+            }
+            Update::Streaming {
+                generation,
+                id,
+                streaming,
+            } => {
+                if generation == current {
+                    state.streaming.insert(id, streaming);
+                } else if matches!(state.streaming.get(&id), Some(Streaming::Loading)) {
+                    // Let the details tab look it up again if it's shown.
+                    state.streaming.remove(&id);
+                }
+            }
+        }
+    }
+}
+
+fn ui<B: Backend>(f: &mut Frame<B>, app: &Arc<Mutex<App>>) {
+    let app = app.lock().unwrap();
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(2)
+        .constraints(
+            [
+                Constraint::Length(3),
+                Constraint::Length(3),
+                Constraint::Min(0),
+                Constraint::Length(1),
+            ]
+            .as_ref(),
+        )
+        .split(f.size());
+
+    let split = app.split(chunks[2].width);
+    app.split_shown.set(split);
+
+    let titles: &[&str] = if split {
+        &["Results + Details [v]"]
+    } else {
+        &["Search [1]", "Details [2]"]
+    };
+    let tabs = Tabs::new(
+        titles
+            .iter()
+            .map(|t| Spans::from(Span::styled(*t, Style::default().fg(Color::Green))))
+            .collect(),
+    )
+    .block(Block::default().borders(Borders::ALL).title("Tabs"))
+    .highlight_style(
+        Style::default()
+            .fg(Color::Yellow)
+            .add_modifier(Modifier::BOLD),
+    )
+    .select(match app.active_tab {
+        Tab::Details if !split => 1,
+        _ => 0,
+    });
+    f.render_widget(tabs, chunks[0]);
+
+    let (input_text, input_title) = match app.input_mode {
+        InputMode::Filtering => (app.filter.as_str(), "Filter"),
+        _ => (app.input.as_str(), "Search"),
+    };
+    let input = Paragraph::new(input_text)
+        .style(match app.input_mode {
+            InputMode::Normal => Style::default(),
+            InputMode::Editing | InputMode::Filtering => Style::default().fg(Color::Yellow),
+        })
+        .block(Block::default().borders(Borders::ALL).title(input_title));
+    f.render_widget(input, chunks[1]);
+
+    if let InputMode::Editing | InputMode::Filtering = app.input_mode {
+        f.set_cursor(chunks[1].x + input_text.width() as u16 + 1, chunks[1].y + 1);
+    }
+
+    if split {
+        let panes = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(45), Constraint::Percentage(55)].as_ref())
+            .split(chunks[2]);
+        render_search_tab(f, panes[0], &app);
+        render_details_tab(f, panes[1], &app);
+    } else {
+        match app.active_tab {
+            Tab::Search => {
+                render_search_tab(f, chunks[2], &app);
+            }
+            Tab::Details => {
+                render_details_tab(f, chunks[2], &app);
+            }
+        }
+    }
+
+    render_status_bar(f, chunks[3], &app);
+
+    if let (Some(pinned), Some(selected)) = (&app.pinned, &app.comparing) {
+        render_comparison(f, f.size(), pinned, selected, app.title_language);
+    }
+    if app.show_help {
+        render_help(f, f.size());
+    }
+    if glyphs::ascii_only() {
+        f.render_widget(AsciiLines, f.size());
+    }
+}
+
+/// Drawn last in ASCII mode: swaps every box-drawing character already in
+/// the frame (borders, the tab divider) for its ASCII stand-in.
+struct AsciiLines;
+
+impl Widget for AsciiLines {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        for y in area.top()..area.bottom() {
+            for x in area.left()..area.right() {
+                let cell = buf.get_mut(x, y);
+                if let Some(ascii) = glyphs::ascii_line(&cell.symbol) {
+                    cell.set_symbol(ascii);
+                }
+            }
+        }
+    }
+}
+
+/// The pinned anime next to the selection, centered over the UI, with the
+/// winner of each numeric row highlighted.
+fn render_comparison<B: Backend>(
+    f: &mut Frame<B>,
+    area: Rect,
+    pinned: &AnimeData,
+    selected: &AnimeData,
+    language: TitleLanguage,
+) {
+    let rows = compare::rows(pinned, selected, language);
+    let width = 90.min(area.width);
+    let height = (rows.len() as u16 + 2).min(area.height);
+    let column = usize::from(width.saturating_sub(2 + 12 + 4)) / 2;
+
+    let cell = |text: &str, wins: bool| {
+        let style = if wins {
+            Style::default()
+                .fg(Color::Green)
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+        };
+        Span::styled(compare::fit(text, column), style)
+    };
+    let lines: Vec<Spans> = rows
+        .iter()
+        .map(|row| {
+            Spans::from(vec![
+                Span::styled(
+                    compare::fit(row.label, 12),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ),
+                Span::raw("  "),
+                cell(&row.left, row.winner == compare::Winner::Left),
+                Span::raw("  "),
+                cell(&row.right, row.winner == compare::Winner::Right),
+            ])
+        })
+        .collect();
+
+    let popup = Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    );
+    let comparison = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Compare (C or Esc to close)"),
+    );
+    f.render_widget(Clear, popup);
+    f.render_widget(comparison, popup);
+}
+
+/// The bottom line: the latest status message, or hints for the keys that
+/// do something right now.
+fn render_status_bar<B: Backend>(f: &mut Frame<B>, area: Rect, app: &App) {
+    let line = match &app.status {
+        Some(status) => Span::styled(status.as_str(), Style::default().fg(Color::Yellow)),
+        None => {
+            let mut hints = key_hints(app).replace("↑↓", glyphs::current().arrows);
+            if !app.search_results.is_empty() {
+                hints = format!("{}  {}", app.page_label(), hints);
+            }
+            Span::styled(hints, Style::default().fg(Color::DarkGray))
+        }
+    };
+    f.render_widget(Paragraph::new(Spans::from(line)), area);
+}
+
+fn key_hints(app: &App) -> &'static str {
+    match (&app.input_mode, &app.active_tab) {
+        (InputMode::Editing, _) => "type to search  ↑↓: history  Enter: done  Esc: stop editing",
+        (InputMode::Filtering, _) => "type to filter  Enter: keep filter  Esc: clear filter",
+        (InputMode::Normal, _) if app.split_shown.get() => {
+            "e: search  /: filter  ↑↓: navigate  j/k: scroll  w/t: wrap/trim  o: open  v: tabs  ?: help  q: quit"
+        }
+        (InputMode::Normal, Tab::Search) => {
+            "e: search  /: filter  ↑↓: navigate  Tab: details  o: open  n/p: page  f/F: favorites  r: refresh  c: clear  ?: help  q: quit"
+        }
+        (InputMode::Normal, Tab::Details) => {
+            "j/k: scroll  PgUp/PgDn: page  w/t: wrap/trim  ↑↓: navigate  Tab: results  o: open  ?: help  q: quit"
+        }
+    }
+}
+
+/// Every key binding, shown centered over the UI while '?' is toggled on.
+fn render_help<B: Backend>(f: &mut Frame<B>, area: Rect) {
+    let lines: Vec<Spans> = HELP_LINES
+        .iter()
+        .map(|(key, action)| {
+            Spans::from(vec![
+                Span::styled(
+                    format!("{:>10}  ", key),
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(*action),
+            ])
+        })
+        .collect();
+
+    let width = 50.min(area.width);
+    let height = (HELP_LINES.len() as u16 + 2).min(area.height);
+    let popup = Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    );
+
+    let help = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Help (? or Esc to close)"),
+    );
+    f.render_widget(Clear, popup);
+    f.render_widget(help, popup);
+}
+
+fn render_search_tab<B: Backend>(f: &mut Frame<B>, area: Rect, app: &App) {
+    if app.loading {
+        let loading_text = Paragraph::new("Loading...")
+            .style(Style::default().fg(tui::style::Color::Yellow))
+            .block(Block::default().borders(Borders::ALL).title("Results"));
+        f.render_widget(loading_text, area);
+        return;
+    }
+
+    if let Some(error) = &app.error {
+        let error_text = Paragraph::new(error.as_str())
+            .style(Style::default().fg(tui::style::Color::Red))
+            .block(Block::default().borders(Borders::ALL).title("Results"));
+        f.render_widget(error_text, area);
+        return;
+    }
+
+    if app.search_results.is_empty() {
+        let help_message = if app.showing_favorites {
+            NO_FAVORITES_HELP
+        } else if app.input.is_empty() {
+            SEARCH_HELP
+        } else {
+            NO_RESULTS_HELP
+        };
+
+        let help_text = Paragraph::new(help_message)
+            .style(Style::default().fg(tui::style::Color::Gray))
+            .block(Block::default().borders(Borders::ALL).title("Results"));
+        f.render_widget(help_text, area);
+        return;
+    }
+
+    let visible = app.visible_indices();
+    let items: Vec<ListItem> = visible
+        .iter()
+        .map(|&i| {
+            let anime = &app.search_results[i];
+            let title = anime.attributes.display_title(app.title_language);
+            let rating = anime
+                .attributes
+                .average_rating
+                .as_ref()
+                .map(|r| Span::styled(format!(" ({}*)", r), rating_style(anime, &app.rating_bands)))
+                .unwrap_or_else(|| Span::raw(""));
+
+            let matched = fuzzy_match(&app.filter, title).unwrap_or_default();
+            let mut spans = Vec::new();
+            if app.favorites.contains(&anime.id) {
+                spans.push(Span::styled(
+                    format!("{} ", glyphs::current().star_full),
+                    Style::default().fg(Color::Yellow),
+                ));
+            }
+            spans.extend(title.chars().enumerate().map(|(ci, c)| {
+                let style = if matched.contains(&ci) {
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::UNDERLINED)
+                } else {
+                    Style::default()
+                };
+                Span::styled(c.to_string(), style)
+            }));
+            spans.push(rating);
+
+            ListItem::new(Spans::from(spans))
+        })
+        .collect();
+
+    let label = if app.showing_favorites {
+        "Favorites"
+    } else if offline::enabled() {
+        "Results (offline samples)"
+    } else {
+        "Results"
+    };
+    let results_title = if app.filter.is_empty() {
+        label.to_string()
+    } else {
+        format!(
+            "{} (filter \"{}\": {}/{})",
+            label,
+            app.filter,
+            visible.len(),
+            app.search_results.len()
+        )
+    };
+
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(results_title))
+        .highlight_style(
+            Style::default()
+                .fg(tui::style::Color::Cyan)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol(glyphs::current().highlight);
+
+    let mut state = tui::widgets::ListState::default();
+    state.select(
+        app.selected_anime_index
+            .and_then(|selected| visible.iter().position(|&i| i == selected)),
+    );
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn render_details_tab<B: Backend>(f: &mut Frame<B>, area: Rect, app: &App) {
+    if let Some(selected) = app.selected_anime_index {
+        if selected < app.search_results.len() {
+            let anime = &app.search_results[selected];
+            let attrs = &anime.attributes;
+            let popularity = popularity_parts(attrs);
+            // Extra info lines past the first: popularity and genres, when known.
+            let extra_lines =
+                u16::from(!popularity.is_empty()) + u16::from(genres_line(anime, app).is_some());
+
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints(
+                    [
+                        Constraint::Length(3),
+                        Constraint::Length(3 + extra_lines),
+                        Constraint::Length(3),
+                        Constraint::Min(0),
+                        Constraint::Length(streaming_height(app.streaming.get(&anime.id))),
+                    ]
+                    .as_ref(),
+                )
+                .split(area);
+            // Title
+            let title = Paragraph::new(attrs.display_title(app.title_language).to_string())
+                .style(
+                    Style::default()
+                        .fg(tui::style::Color::Cyan)
+                        .add_modifier(Modifier::BOLD),
+                )
+                .block(Block::default().borders(Borders::ALL).title("Title"));
+            f.render_widget(title, chunks[0]);
+
+            //Info
+            let mut info = vec![];
+            if let Some(rating) = &attrs.average_rating {
+                info.push(Span::styled(
+                    format!("Rating: {}/100", rating),
+                    rating_style(anime, &app.rating_bands),
+                ));
+            }
+            let status = attrs.status.as_deref().map(format_status);
+            for part in info_parts(attrs) {
+                if !info.is_empty() {
+                    info.push(Span::raw(" | "));
+                }
+                match &status {
+                    Some(status) if part == format!("Status: {}", status.input) => {
+                        info.push(Span::raw("Status: "));
+                        info.push(Span::styled(
+                            status.input.clone(),
+                            Style::default().fg(tui_color(status.fgcolor)),
+                        ));
+                    }
+                    _ => info.push(Span::raw(part)),
+                }
+            }
+
+            if let Some(progress) = app.progress.get(&anime.id) {
+                if !info.is_empty() {
+                    info.push(Span::raw(" | "));
+                }
+                info.push(Span::raw(format!("Progress: {}", progress)));
+            }
+
+            let mut info_lines = vec![Spans::from(info)];
+            if !popularity.is_empty() {
+                info_lines.push(Spans::from(popularity.join(" | ")));
+            }
+            if let Some(genres) = genres_line(anime, app) {
+                info_lines.push(Spans::from(genres));
+            }
+            let info_text = Paragraph::new(info_lines)
+                .block(Block::default().borders(Borders::ALL).title("Info"));
+            f.render_widget(info_text, chunks[1]);
+
+            let poster = attrs
+                .poster_image
+                .as_ref()
+                .and_then(|p| p.url(ImageSize::Medium))
+                .unwrap_or("No poster available.");
+            let poster_text = Paragraph::new(poster)
+                .block(Block::default().borders(Borders::ALL).title("Poster"));
+            f.render_widget(poster_text, chunks[2]);
+
+            let synopsis = attrs
+                .synopsis
+                .clone()
+                .unwrap_or_else(|| "No synopsis available.".to_string());
+
+            // Inside the borders.
+            let inner_width = chunks[3].width.saturating_sub(2);
+            let inner_height = chunks[3].height.saturating_sub(2);
+            let line_count = if app.synopsis_wrap {
+                wrapped_line_count(&synopsis, inner_width)
+            } else {
+                synopsis.lines().count() as u16
+            };
+            let max_scroll = line_count.saturating_sub(inner_height);
+            app.synopsis_max_scroll.set(max_scroll);
+
+            let mut synopsis_text = Paragraph::new(synopsis)
+                .block(Block::default().borders(Borders::ALL).title("Synopsis"))
+                .scroll((app.synopsis_scroll.min(max_scroll), 0));
+            if app.synopsis_wrap {
+                synopsis_text = synopsis_text.wrap(tui::widgets::Wrap {
+                    trim: app.synopsis_trim,
+                });
+            }
+            f.render_widget(synopsis_text, chunks[3]);
+
+            let links = streaming_lines(app.streaming.get(&anime.id));
+            let links_text = Paragraph::new(links).block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("Where to watch"),
+            );
+            f.render_widget(links_text, chunks[4]);
+        }
+    } else {
+        let message = Paragraph::new("No anime selected.")
+            .style(Style::default().fg(tui::style::Color::Gray))
+            .block(Block::default().borders(Borders::ALL).title("Details"));
+        f.render_widget(message, area);
+    }
+}
+
+/// "Genres: Action, Adventure", or a placeholder while they're looked up.
+fn genres_line(anime: &AnimeData, app: &App) -> Option<String> {
+    if !anime.categories.is_empty() {
+        Some(format!("Genres: {}", anime.categories.join(", ")))
+    } else if app.genres_loading.contains(&anime.id) {
+        Some(format!("Genres: loading{}", glyphs::current().ellipsis))
+    } else {
+        None
+    }
+}
+
+/// Most streaming links listed before the rest are cut off.
+const MAX_STREAMING_LINES: usize = 5;
+
+/// Rows the "Where to watch" box needs, borders included.
+fn streaming_height(streaming: Option<&Streaming>) -> u16 {
+    let lines = match streaming {
+        Some(Streaming::Loaded(links)) if !links.is_empty() => links.len().min(MAX_STREAMING_LINES),
+        _ => 1,
+    };
+    lines as u16 + 2
+}
+
+fn streaming_lines(streaming: Option<&Streaming>) -> Vec<Spans<'_>> {
+    let message =
+        |text: String, color| vec![Spans::from(Span::styled(text, Style::default().fg(color)))];
+    match streaming {
+        None | Some(Streaming::Loading) => message("Loading...".to_string(), Color::Gray),
+        Some(Streaming::Failed(err)) => message(
+            format!("Couldn't load streaming links: {}", err),
+            Color::Red,
+        ),
+        Some(Streaming::Loaded(links)) if links.is_empty() => {
+            message("No streaming links found".to_string(), Color::Gray)
+        }
+        Some(Streaming::Loaded(links)) => links
+            .iter()
+            .take(MAX_STREAMING_LINES)
+            .map(|(streamer, url)| {
+                Spans::from(vec![
+                    Span::styled(
+                        format!("{}: ", streamer),
+                        Style::default()
+                            .fg(Color::Yellow)
+                            .add_modifier(Modifier::BOLD),
+                    ),
+                    Span::styled(url.as_str(), Style::default().fg(Color::Blue)),
+                ])
+            })
+            .collect(),
+    }
+}
+
+/// The terminal color closest to a `colored` one, from the CLI theme.
+fn tui_color(color: Option<colored::Color>) -> Color {
+    use colored::Color as C;
+    match color {
+        None => Color::Reset,
+        Some(C::Black) => Color::Black,
+        Some(C::Red) => Color::Red,
+        Some(C::Green) => Color::Green,
+        Some(C::Yellow) => Color::Yellow,
+        Some(C::Blue) => Color::Blue,
+        Some(C::Magenta) => Color::Magenta,
+        Some(C::Cyan) => Color::Cyan,
+        Some(C::White) => Color::Gray,
+        Some(C::BrightBlack) => Color::DarkGray,
+        Some(C::BrightRed) => Color::LightRed,
+        Some(C::BrightGreen) => Color::LightGreen,
+        Some(C::BrightYellow) => Color::LightYellow,
+        Some(C::BrightBlue) => Color::LightBlue,
+        Some(C::BrightMagenta) => Color::LightMagenta,
+        Some(C::BrightCyan) => Color::LightCyan,
+        Some(C::BrightWhite) => Color::White,
+        Some(C::TrueColor { r, g, b }) => Color::Rgb(r, g, b),
+    }
+}
+
+/// Colors a rating by its quality band, matching the CLI output.
+fn rating_style(anime: &AnimeData, bands: &RatingBands) -> Style {
+    let Some(rating) = anime.attributes.rating_value() else {
+        return Style::default();
+    };
+    let color = match bands.band(rating as f32) {
+        RatingBand::Low => Color::Red,
+        RatingBand::Mid => Color::Yellow,
+        RatingBand::High => Color::Green,
+    };
+    Style::default().fg(color)
+}
+
+/// Estimates how many rows `text` occupies when word-wrapped to `width`
+/// columns, mirroring the greedy wrapping `Paragraph` does.
+fn wrapped_line_count(text: &str, width: u16) -> u16 {
+    let width = usize::from(width.max(1));
+    let mut lines = 0usize;
+
+    for paragraph in text.lines() {
+        let mut line_width = 0;
+        lines += 1;
+
+        for word in paragraph.split_whitespace() {
+            let word_width = word.width();
+            if line_width > 0 && line_width + 1 + word_width > width {
+                lines += 1;
+                line_width = 0;
+            }
+            if line_width > 0 {
+                line_width += 1;
+            }
+            // Words wider than the area are broken across rows.
+            line_width += word_width;
+            while line_width > width {
+                lines += 1;
+                line_width -= width;
+            }
+        }
+    }
+
+    u16::try_from(lines).unwrap_or(u16::MAX)
+}
+
+/// Case-insensitive subsequence match of `pattern` against `text`. Returns the
+/// char indices of `text` that matched, or `None` if some pattern character
+/// could not be found in order. An empty pattern matches everything.
+fn fuzzy_match(pattern: &str, text: &str) -> Option<Vec<usize>> {
+    let mut matched = Vec::new();
+    let mut chars = text.chars().enumerate();
+
+    for p in pattern.chars().filter(|c| !c.is_whitespace()) {
+        let p = p.to_lowercase().collect::<String>();
+        let (index, _) = chars
+            .by_ref()
+            .find(|(_, c)| c.to_lowercase().collect::<String>() == p)?;
+        matched.push(index);
+    }
+
+    Some(matched)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kitsu_stub::KitsuStub;
+
+    #[test]
+    fn fuzzy_match_finds_a_subsequence_ignoring_case() {
+        assert_eq!(fuzzy_match("cb", "Cowboy Bebop"), Some(vec![0, 3]));
+        assert_eq!(
+            fuzzy_match("BEBOP", "Cowboy Bebop"),
+            Some(vec![3, 8, 9, 10, 11])
+        );
+        assert_eq!(fuzzy_match("bc", "Cowboy Bebop"), None);
+    }
+
+    #[test]
+    fn fuzzy_match_counts_chars_not_bytes() {
+        // "ー" is three bytes; indices must still count one per char.
+        assert_eq!(fuzzy_match("ビバ", "カウボーイビバップ"), Some(vec![5, 6]));
+        assert_eq!(fuzzy_match("é", "Pokémon"), Some(vec![3]));
+    }
+
+    #[test]
+    fn fuzzy_match_ignores_whitespace_in_the_pattern() {
+        assert_eq!(fuzzy_match("", "Naruto"), Some(vec![]));
+        assert_eq!(fuzzy_match(" n t ", "Naruto"), Some(vec![0, 4]));
+    }
+
+    #[test]
+    fn help_text_reads_correctly() {
+        assert!(SEARCH_HELP.contains("Press 'e' to enter search mode"));
+        assert!(NO_RESULTS_HELP.starts_with("No results found."));
+        assert!(NO_FAVORITES_HELP.contains("press 'f'"));
+        for text in [SEARCH_HELP, NO_RESULTS_HELP, NO_FAVORITES_HELP] {
+            assert!(!text.contains("endter"), "{}", text);
+            assert!(!text.contains("  "), "{}", text);
+        }
+    }
+
+    #[test]
+    fn help_overlay_lists_each_key_once() {
+        let mut keys: Vec<&str> = HELP_LINES.iter().map(|(key, _)| *key).collect();
+        keys.sort_unstable();
+        keys.dedup();
+        assert_eq!(keys.len(), HELP_LINES.len());
+        assert!(HELP_LINES.iter().all(|(_, action)| !action.is_empty()));
+    }
+
+    #[test]
+    fn wrapped_line_count_wraps_on_words() {
+        assert_eq!(wrapped_line_count("", 20), 0);
+        assert_eq!(wrapped_line_count("Cowboy Bebop", 20), 1);
+        assert_eq!(wrapped_line_count("Cowboy Bebop", 12), 1);
+        assert_eq!(wrapped_line_count("Cowboy Bebop", 11), 2);
+        // Each paragraph starts a new row.
+        assert_eq!(wrapped_line_count("one\ntwo\n\nthree", 20), 4);
+    }
+
+    #[test]
+    fn wrapped_line_count_measures_columns() {
+        // Each of these is two columns wide.
+        assert_eq!(wrapped_line_count("カウボーイ", 10), 1);
+        assert_eq!(wrapped_line_count("カウボーイ ビバップ", 10), 2);
+        // Too wide for a row: broken across rows.
+        assert_eq!(wrapped_line_count("abcdefghij", 4), 3);
+        assert_eq!(wrapped_line_count("abc", 0), 3);
+    }
+
+    fn app() -> Arc<Mutex<App>> {
+        app_with(HttpClient::new(Duration::from_secs(1), 0).unwrap())
+    }
+
+    fn app_with(client: HttpClient) -> Arc<Mutex<App>> {
+        let (updates, _) = unbounded_channel();
+        Arc::new(Mutex::new(App::new(
+            client,
+            RatingBands::default(),
+            TitleLanguage::default(),
+            Enrichments::default(),
+            updates,
+        )))
+    }
+
+    fn press(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn ctrl_c_quits_from_every_mode() {
+        // Quitting through `handle_key` is what lets the guard restore the
+        // terminal and the session be saved, so no mode may swallow Ctrl-C.
+        let ctrl_c = press(KeyCode::Char('c'), KeyModifiers::CONTROL);
+        let app = app();
+        for mode in [InputMode::Normal, InputMode::Editing, InputMode::Filtering] {
+            app.lock().unwrap().input_mode = mode;
+            assert!(handle_key(&app, ctrl_c));
+        }
+        app.lock().unwrap().show_help = true;
+        assert!(handle_key(&app, ctrl_c));
+    }
+
+    #[tokio::test]
+    async fn plain_c_is_typed_while_editing() {
+        // Typing starts a debounced search, hence the runtime.
+        let app = app();
+        app.lock().unwrap().input_mode = InputMode::Editing;
+        assert!(!handle_key(
+            &app,
+            press(KeyCode::Char('c'), KeyModifiers::NONE)
+        ));
+        assert_eq!(app.lock().unwrap().input, "c");
+    }
+
+    /// Runs a page search against `stub` the way paging does, which leaves
+    /// the search history alone, and waits for it to land.
+    async fn search_stub(stub: &KitsuStub, include_nsfw: bool) -> Arc<Mutex<App>> {
+        let client = HttpClient::new(Duration::from_secs(5), 0)
+            .unwrap()
+            .with_kitsu_api(Some(stub.base_url.clone()));
+        let app = app_with(client.clone());
+        app.lock().unwrap().include_nsfw = include_nsfw;
+        app.lock().unwrap().loading = true;
+        spawn_search(
+            Arc::clone(&app),
+            client,
+            "x".to_string(),
+            1,
+            0,
+            SearchTrigger::Page,
+        );
+        while app.lock().unwrap().loading {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        app
+    }
+
+    #[tokio::test]
+    async fn adult_results_are_hidden_with_a_status() {
+        let page = r#"{
+            "data": [
+                {"id": "1", "type": "anime", "attributes": {"canonicalTitle": "Safe", "nsfw": false}},
+                {"id": "2", "type": "anime", "attributes": {"canonicalTitle": "Adult", "nsfw": true}}
+            ]
+        }"#;
+        let stub = KitsuStub::start(vec![("anime", page)]).await;
+        let ids = |app: &Arc<Mutex<App>>| -> Vec<String> {
+            let state = app.lock().unwrap();
+            state
+                .search_results
+                .iter()
+                .map(|anime| anime.id.clone())
+                .collect()
+        };
+
+        let hidden = search_stub(&stub, false).await;
+        assert_eq!(ids(&hidden), ["1"]);
+        let status = hidden.lock().unwrap().status.clone().unwrap();
+        assert!(status.starts_with("1 adult result(s) hidden"), "{}", status);
+
+        let shown = search_stub(&stub, true).await;
+        assert_eq!(ids(&shown), ["1", "2"]);
+        assert_eq!(shown.lock().unwrap().status, None);
+    }
+}
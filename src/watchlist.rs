@@ -0,0 +1,369 @@
+//! The user's local watchlist, persisted as JSON in the data directory.
+
+use std::{fs, path::PathBuf};
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+
+use crate::{AnimeAttributes, AnimeData, paths};
+
+const WATCHLIST_FILE: &str = "watchlist.json";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum WatchStatus {
+    #[default]
+    PlanToWatch,
+    Watching,
+    Completed,
+    OnHold,
+    Dropped,
+}
+
+impl WatchStatus {
+    pub fn label(self) -> &'static str {
+        match self {
+            WatchStatus::PlanToWatch => "plan-to-watch",
+            WatchStatus::Watching => "watching",
+            WatchStatus::Completed => "completed",
+            WatchStatus::OnHold => "on-hold",
+            WatchStatus::Dropped => "dropped",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchlistEntry {
+    pub id: String,
+    pub title: String,
+    pub status: WatchStatus,
+    /// Attributes as of the last successful fetch, shown when offline.
+    #[serde(default)]
+    pub attributes: Option<AnimeAttributes>,
+    /// Episodes watched so far, set with `progress`.
+    #[serde(default)]
+    pub progress: u16,
+    /// Starred with 'f' in the TUI, which lists favorites with 'F'.
+    #[serde(default)]
+    pub favorite: bool,
+}
+
+impl WatchlistEntry {
+    /// "5/12", or "5/?" while the episode count is unknown.
+    pub fn progress_label(&self) -> String {
+        match self.episode_count() {
+            Some(total) => format!("{}/{}", self.progress, total),
+            None => format!("{}/?", self.progress),
+        }
+    }
+
+    fn episode_count(&self) -> Option<u16> {
+        self.attributes.as_ref()?.episode_count
+    }
+}
+
+#[derive(Debug)]
+pub struct Watchlist {
+    path: PathBuf,
+    pub entries: Vec<WatchlistEntry>,
+}
+
+/// What `Watchlist::add` did with the entry.
+#[derive(Debug, PartialEq, Eq)]
+pub enum AddOutcome {
+    Added,
+    Updated,
+}
+
+impl Watchlist {
+    /// Loads the watchlist, starting a fresh one if the file is missing. A
+    /// corrupt file is set aside as `watchlist.json.bak` rather than
+    /// overwritten, so nothing is silently lost.
+    pub fn load() -> Result<Watchlist> {
+        Watchlist::open(paths::data_dir()?.join(WATCHLIST_FILE))
+    }
+
+    fn open(path: PathBuf) -> Result<Watchlist> {
+        let entries = match fs::read_to_string(&path) {
+            Ok(contents) => match serde_json::from_str(&contents) {
+                Ok(entries) => entries,
+                Err(err) => {
+                    let backup = path.with_extension("json.bak");
+                    eprintln!(
+                        "{} watchlist at {} is corrupt ({}); moved it to {} and started fresh",
+                        "warning:".yellow().bold(),
+                        path.display(),
+                        err,
+                        backup.display()
+                    );
+                    fs::rename(&path, &backup).with_context(|| {
+                        format!("Failed to move corrupt watchlist to {}", backup.display())
+                    })?;
+                    Vec::new()
+                }
+            },
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => {
+                return Err(err).with_context(|| format!("Failed to read {}", path.display()));
+            }
+        };
+
+        Ok(Watchlist { path, entries })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+
+        let contents = serde_json::to_string_pretty(&self.entries)?;
+        fs::write(&self.path, contents)
+            .with_context(|| format!("Failed to write watchlist to {}", self.path.display()))
+    }
+
+    /// Inserts `entry`, or replaces the existing entry with the same id so
+    /// adding twice never duplicates it. Replacing keeps the progress made
+    /// and whether it is a favorite.
+    pub fn add(&mut self, entry: WatchlistEntry) -> AddOutcome {
+        match self.entries.iter_mut().find(|e| e.id == entry.id) {
+            Some(existing) => {
+                *existing = WatchlistEntry {
+                    progress: existing.progress,
+                    favorite: existing.favorite,
+                    ..entry
+                };
+                AddOutcome::Updated
+            }
+            None => {
+                self.entries.push(entry);
+                AddOutcome::Added
+            }
+        }
+    }
+
+    /// Records `episodes` watched for `id`, clamped to its episode count when
+    /// that is known. Returns the updated entry, or `None` if `id` isn't on
+    /// the watchlist.
+    pub fn set_progress(&mut self, id: &str, episodes: u16) -> Option<&WatchlistEntry> {
+        let entry = self.entries.iter_mut().find(|e| e.id == id)?;
+        entry.progress = match entry.episode_count() {
+            Some(total) => episodes.min(total),
+            None => episodes,
+        };
+        Some(entry)
+    }
+
+    /// Flips whether `anime` is a favorite, first adding it as plan-to-watch
+    /// if it isn't on the watchlist. Returns whether it is a favorite now.
+    pub fn toggle_favorite(&mut self, anime: &AnimeData) -> bool {
+        match self.entries.iter_mut().find(|e| e.id == anime.id) {
+            Some(entry) => {
+                entry.favorite = !entry.favorite;
+                entry.favorite
+            }
+            None => {
+                self.entries.push(WatchlistEntry {
+                    id: anime.id.clone(),
+                    title: anime.attributes.canonical_title.clone(),
+                    status: WatchStatus::default(),
+                    attributes: Some(anime.attributes.clone()),
+                    progress: 0,
+                    favorite: true,
+                });
+                true
+            }
+        }
+    }
+
+    /// Removes the entry with `id`, returning it if it was present.
+    pub fn remove(&mut self, id: &str) -> Option<WatchlistEntry> {
+        let index = self.entries.iter().position(|e| e.id == id)?;
+        Some(self.entries.remove(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A watchlist file of its own under the temp directory, removed first.
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "sabikani-watchlist-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        dir.join(WATCHLIST_FILE)
+    }
+
+    fn entry(id: &str, status: WatchStatus) -> WatchlistEntry {
+        WatchlistEntry {
+            id: id.to_string(),
+            title: format!("Anime {}", id),
+            status,
+            attributes: None,
+            progress: 0,
+            favorite: false,
+        }
+    }
+
+    fn empty() -> Watchlist {
+        Watchlist {
+            path: PathBuf::from("unused.json"),
+            entries: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn adding_twice_keeps_one_entry() {
+        let mut watchlist = empty();
+        assert_eq!(
+            watchlist.add(entry("1", WatchStatus::PlanToWatch)),
+            AddOutcome::Added
+        );
+        assert_eq!(
+            watchlist.add(entry("1", WatchStatus::Watching)),
+            AddOutcome::Updated
+        );
+        assert_eq!(watchlist.entries.len(), 1);
+        assert_eq!(watchlist.entries[0].status, WatchStatus::Watching);
+    }
+
+    #[test]
+    fn removing_twice_removes_once() {
+        let mut watchlist = empty();
+        watchlist.add(entry("1", WatchStatus::PlanToWatch));
+        watchlist.add(entry("2", WatchStatus::PlanToWatch));
+
+        assert_eq!(watchlist.remove("1").map(|e| e.id), Some("1".to_string()));
+        assert!(watchlist.remove("1").is_none());
+        assert_eq!(watchlist.entries.len(), 1);
+        assert_eq!(watchlist.entries[0].id, "2");
+    }
+
+    #[test]
+    fn missing_file_starts_empty() {
+        let watchlist = Watchlist::open(scratch("missing")).unwrap();
+        assert!(watchlist.entries.is_empty());
+    }
+
+    #[test]
+    fn corrupt_file_is_set_aside() {
+        let path = scratch("corrupt");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "{ not json").unwrap();
+
+        let watchlist = Watchlist::open(path.clone()).unwrap();
+        assert!(watchlist.entries.is_empty());
+        assert!(!path.exists());
+        let backup = path.with_extension("json.bak");
+        assert_eq!(fs::read_to_string(backup).unwrap(), "{ not json");
+    }
+
+    fn with_episodes(id: &str, episodes: Option<u16>) -> WatchlistEntry {
+        WatchlistEntry {
+            attributes: Some(AnimeAttributes {
+                episode_count: episodes,
+                ..Default::default()
+            }),
+            ..entry(id, WatchStatus::Watching)
+        }
+    }
+
+    #[test]
+    fn progress_is_clamped_to_the_episode_count() {
+        let mut watchlist = empty();
+        watchlist.add(with_episodes("1", Some(12)));
+        watchlist.add(with_episodes("2", None));
+
+        assert_eq!(
+            watchlist.set_progress("1", 5).unwrap().progress_label(),
+            "5/12"
+        );
+        assert_eq!(
+            watchlist.set_progress("1", 40).unwrap().progress_label(),
+            "12/12"
+        );
+        assert_eq!(
+            watchlist.set_progress("2", 40).unwrap().progress_label(),
+            "40/?"
+        );
+        assert!(watchlist.set_progress("3", 1).is_none());
+    }
+
+    #[test]
+    fn progress_survives_saving_and_re_adding() {
+        let path = scratch("progress");
+        let mut watchlist = Watchlist::open(path.clone()).unwrap();
+        watchlist.add(with_episodes("1", Some(26)));
+        watchlist.set_progress("1", 9);
+        watchlist.save().unwrap();
+
+        let mut reloaded = Watchlist::open(path).unwrap();
+        assert_eq!(reloaded.entries[0].progress, 9);
+        // Adding it again, e.g. to change the status, keeps the progress.
+        reloaded.add(with_episodes("1", Some(26)));
+        assert_eq!(reloaded.entries[0].progress, 9);
+    }
+
+    #[test]
+    fn entries_saved_before_progress_existed_start_at_zero() {
+        let entry: WatchlistEntry =
+            serde_json::from_str(r#"{"id": "1", "title": "Trigun", "status": "watching"}"#)
+                .unwrap();
+        assert_eq!(entry.progress, 0);
+        assert!(!entry.favorite);
+    }
+
+    fn anime(id: &str) -> AnimeData {
+        AnimeData {
+            id: id.to_string(),
+            attributes: AnimeAttributes {
+                canonical_title: format!("Anime {}", id),
+                ..Default::default()
+            },
+            categories: Vec::new(),
+            streamers: Vec::new(),
+            source: None,
+        }
+    }
+
+    #[test]
+    fn favoriting_adds_the_entry_and_toggles_back() {
+        let mut watchlist = empty();
+        assert!(watchlist.toggle_favorite(&anime("1")));
+        assert_eq!(watchlist.entries.len(), 1);
+        assert_eq!(watchlist.entries[0].status, WatchStatus::PlanToWatch);
+        assert_eq!(watchlist.entries[0].title, "Anime 1");
+
+        assert!(!watchlist.toggle_favorite(&anime("1")));
+        assert_eq!(watchlist.entries.len(), 1);
+        assert!(!watchlist.entries[0].favorite);
+    }
+
+    #[test]
+    fn favorites_survive_saving_and_re_adding() {
+        let path = scratch("favorites");
+        let mut watchlist = Watchlist::open(path.clone()).unwrap();
+        watchlist.add(entry("1", WatchStatus::Watching));
+        watchlist.add(entry("2", WatchStatus::Watching));
+        watchlist.toggle_favorite(&anime("2"));
+        watchlist.save().unwrap();
+
+        let mut reloaded = Watchlist::open(path).unwrap();
+        let favorites: Vec<&str> = reloaded
+            .entries
+            .iter()
+            .filter(|entry| entry.favorite)
+            .map(|entry| entry.id.as_str())
+            .collect();
+        assert_eq!(favorites, ["2"]);
+
+        reloaded.add(entry("2", WatchStatus::Completed));
+        assert!(reloaded.entries[1].favorite);
+    }
+}
@@ -0,0 +1,640 @@
+//! `sabikani search` end to end, against a stub Kitsu API.
+
+#[path = "support/kitsu_stub.rs"]
+mod kitsu_stub;
+
+use std::{
+    path::{Path, PathBuf},
+    process::Output,
+};
+
+use kitsu_stub::KitsuStub;
+use serde_json::Value;
+
+const SEARCH_PAGE: &str = include_str!("fixtures/search_page.json");
+const LAST_PAGE: &str = include_str!("fixtures/search_last_page.json");
+
+/// Runs the binary against `stub` with a config, cache and history of its
+/// own under `home`.
+async fn sabikani(stub: &KitsuStub, home: &str, args: &[&str]) -> Output {
+    command(stub, home, args).output().await.unwrap()
+}
+
+/// The command `sabikani` runs, for tests that need to add to it first.
+fn command(stub: &KitsuStub, home: &str, args: &[&str]) -> tokio::process::Command {
+    command_in(stub, &scratch_dir(home), args)
+}
+
+/// Like `command`, keeping whatever earlier runs left under `home`.
+fn command_in(stub: &KitsuStub, home: &Path, args: &[&str]) -> tokio::process::Command {
+    let mut command = tokio::process::Command::new(env!("CARGO_BIN_EXE_sabikani"));
+    command
+        .args(args)
+        .env("SABIKANI_BASE_URL", &stub.base_url)
+        .env("HOME", home)
+        .env("XDG_CONFIG_HOME", home.join("config"))
+        .env("XDG_DATA_HOME", home.join("data"))
+        .env("XDG_CACHE_HOME", home.join("cache"))
+        .env("NO_COLOR", "1")
+        .env_remove("SABIKANI_TOKEN");
+    command
+}
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("sabikani-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn stdout(output: &Output) -> String {
+    assert!(
+        output.status.success(),
+        "sabikani failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[tokio::test]
+async fn json_keeps_null_fields() {
+    let stub = KitsuStub::start(vec![("filter%5Btext%5D=bebop", SEARCH_PAGE)]).await;
+    let output = sabikani(
+        &stub,
+        "json",
+        &["search", "bebop", "--limit", "2", "--format", "json"],
+    )
+    .await;
+
+    let results: Value = serde_json::from_str(&stdout(&output)).unwrap();
+    let results = results.as_array().unwrap();
+    assert_eq!(results.len(), 2);
+
+    let filled = &results[0]["attributes"];
+    assert_eq!(filled["canonicalTitle"], "Cowboy Bebop");
+    assert_eq!(filled["averageRating"], "82.1");
+    assert_eq!(filled["episodeCount"], 26);
+    assert_eq!(filled["titles"]["en_jp"], "Cowboy Bebop");
+
+    let sparse = &results[1]["attributes"];
+    assert_eq!(sparse["canonicalTitle"], "Cowboy Bebop: Yose Atsume Blues");
+    assert!(sparse["averageRating"].is_null());
+    assert!(sparse["episodeCount"].is_null());
+    assert!(sparse["posterImage"].is_null());
+    assert!(sparse["titles"]["en"].is_null());
+
+    let requests = stub.requests();
+    assert_eq!(requests.len(), 1);
+    assert!(requests[0].starts_with("GET /api/edge/anime?"));
+}
+
+#[tokio::test]
+async fn footer_follows_the_next_link() {
+    let stub = KitsuStub::start(vec![
+        ("page%5Boffset%5D=0", SEARCH_PAGE),
+        ("page%5Boffset%5D=2", LAST_PAGE),
+    ])
+    .await;
+
+    let first = stdout(&sabikani(&stub, "first", &["search", "bebop", "--limit", "2"]).await);
+    assert!(first.contains("Showing 1-2 of 3 results"), "{}", first);
+    assert!(first.contains("use --page 2 for more"), "{}", first);
+    // Unrated and unknown fields are shown as such rather than dropped.
+    assert!(
+        first.contains("Cowboy Bebop: Yose Atsume Blues"),
+        "{}",
+        first
+    );
+
+    let last = stdout(
+        &sabikani(
+            &stub,
+            "last",
+            &["search", "bebop", "--limit", "2", "--page", "2"],
+        )
+        .await,
+    );
+    assert!(last.contains("Showing 3-3 of 3 results."), "{}", last);
+    assert!(!last.contains("--page 3"), "{}", last);
+}
+
+#[tokio::test]
+async fn quiet_prints_only_ids() {
+    let stub = KitsuStub::start(vec![("filter%5Btext%5D=bebop", SEARCH_PAGE)]).await;
+    let output = sabikani(&stub, "quiet", &["search", "bebop", "--limit", "2", "-q"]).await;
+    assert_eq!(stdout(&output), "1\n4231\n");
+
+    // Asking for both is a mistake, not a choice between them.
+    let output = sabikani(
+        &stub,
+        "quiet-json",
+        &["search", "bebop", "-q", "--format", "json"],
+    )
+    .await;
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("cannot be used with"),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[tokio::test]
+async fn empty_queries_send_nothing() {
+    let stub = KitsuStub::start(vec![("anime", SEARCH_PAGE)]).await;
+    for query in ["", "   ", "\t"] {
+        let output = sabikani(&stub, "empty", &["search", query]).await;
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("The search query is empty"), "{}", stderr);
+    }
+    assert!(stub.requests().is_empty());
+}
+
+#[tokio::test]
+async fn queries_are_trimmed() {
+    let stub = KitsuStub::start(vec![("anime", SEARCH_PAGE)]).await;
+    stdout(&sabikani(&stub, "trimmed", &["search", "  bebop \n", "-q"]).await);
+
+    let requests = stub.requests();
+    assert!(
+        requests[0].starts_with("GET /api/edge/anime?filter%5Btext%5D=bebop&"),
+        "{}",
+        requests[0]
+    );
+}
+
+#[tokio::test]
+async fn limits_are_checked() {
+    let stub = KitsuStub::start(vec![("anime", SEARCH_PAGE)]).await;
+
+    let output = sabikani(&stub, "zero", &["search", "bebop", "--limit", "0"]).await;
+    assert!(!output.status.success());
+
+    let output = sabikani(
+        &stub,
+        "large",
+        &["search", "bebop", "--limit", "201", "--dry-run"],
+    )
+    .await;
+    let requests = stdout(&output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("--limit 201 will take 11 requests to Kitsu"),
+        "{}",
+        stderr
+    );
+    assert_eq!(requests.matches("GET ").count(), 11);
+    assert!(requests.contains("page%5Blimit%5D=1&page%5Boffset%5D=200"));
+    assert!(stub.requests().is_empty());
+}
+
+#[tokio::test]
+async fn output_writes_json_to_the_file() {
+    let stub = KitsuStub::start(vec![("filter%5Btext%5D=bebop", SEARCH_PAGE)]).await;
+    let path = scratch_dir("output-dir").join("nested/results.json");
+    let output = sabikani(
+        &stub,
+        "output",
+        &[
+            "--output",
+            path.to_str().unwrap(),
+            "search",
+            "bebop",
+            "--limit",
+            "2",
+            "--format",
+            "json",
+        ],
+    )
+    .await;
+    assert_eq!(stdout(&output), "");
+
+    let written: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    let titles: Vec<&str> = written
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|anime| anime["attributes"]["canonicalTitle"].as_str().unwrap())
+        .collect();
+    assert_eq!(titles, ["Cowboy Bebop", "Cowboy Bebop: Yose Atsume Blues"]);
+}
+
+#[tokio::test]
+async fn the_token_is_sent_only_when_configured() {
+    let stub = KitsuStub::start(vec![("anime", SEARCH_PAGE)]).await;
+    stdout(&sabikani(&stub, "anonymous", &["search", "bebop", "-q"]).await);
+    let output = command(&stub, "token", &["search", "bebop", "-q"])
+        .env("SABIKANI_TOKEN", " s3cret-token \n")
+        .output()
+        .await
+        .unwrap();
+    stdout(&output);
+
+    let requests = stub.requests();
+    assert_eq!(requests.len(), 2);
+    assert!(!requests[0].contains("authorization:"), "{}", requests[0]);
+    assert!(
+        requests[1].contains("authorization: Bearer s3cret-token\r\n"),
+        "{}",
+        requests[1]
+    );
+
+    // --dry-run shows that the header is sent, but not the token.
+    let output = command(&stub, "token-dry-run", &["search", "bebop", "--dry-run"])
+        .env("SABIKANI_TOKEN", "s3cret-token")
+        .output()
+        .await
+        .unwrap();
+    let described = stdout(&output);
+    assert!(
+        described.contains("authorization: Bearer REDACTED"),
+        "{}",
+        described
+    );
+    assert!(!described.contains("s3cret"), "{}", described);
+    assert_eq!(stub.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn saved_queries_run_as_searches() {
+    let stub = KitsuStub::start(vec![("filter%5Btext%5D=bebop", SEARCH_PAGE)]).await;
+    let home = scratch_dir("queries");
+    let run = |args: &'static [&'static str]| {
+        let mut command = command_in(&stub, &home, args);
+        async move { command.output().await.unwrap() }
+    };
+
+    let saved = stdout(&run(&["query", "save", "bebop", "bebop", "--limit", "2"]).await);
+    assert!(saved.starts_with("Saved bebop"), "{}", saved);
+    let updated = stdout(&run(&["query", "save", "bebop", "bebop", "--limit", "1"]).await);
+    assert!(updated.starts_with("Updated bebop"), "{}", updated);
+
+    stdout(&run(&["query", "run", "bebop", "-q"]).await);
+    // Flags after the name override the saved ones.
+    stdout(&run(&["query", "run", "bebop", "--limit", "2", "-q"]).await);
+
+    let missing = run(&["query", "run", "nope"]).await;
+    assert!(!missing.status.success());
+    let stderr = String::from_utf8_lossy(&missing.stderr);
+    assert!(stderr.contains("no saved query named `nope`"), "{}", stderr);
+
+    let requests = stub.requests();
+    assert_eq!(requests.len(), 2);
+    assert!(
+        requests[0].contains("page%5Blimit%5D=1&"),
+        "{}",
+        requests[0]
+    );
+    assert!(
+        requests[1].contains("page%5Blimit%5D=2&"),
+        "{}",
+        requests[1]
+    );
+}
+
+#[tokio::test]
+async fn nsfw_results_are_hidden_unless_asked_for() {
+    let page = r#"{
+        "data": [
+            {"id": "1", "type": "anime", "attributes": {"canonicalTitle": "Safe", "nsfw": false}},
+            {"id": "2", "type": "anime", "attributes": {"canonicalTitle": "Adult", "nsfw": true}}
+        ],
+        "meta": {"count": 2}
+    }"#;
+    let stub = KitsuStub::start(vec![("anime", page)]).await;
+
+    let hidden = stdout(&sabikani(&stub, "nsfw", &["search", "x"]).await);
+    assert!(!hidden.contains("Adult"), "{}", hidden);
+    assert!(hidden.contains("1 adult result(s) hidden"), "{}", hidden);
+    assert_eq!(
+        stdout(&sabikani(&stub, "nsfw-quiet", &["search", "x", "-q"]).await),
+        "1\n"
+    );
+
+    let shown = stdout(&sabikani(&stub, "nsfw-shown", &["search", "x", "--include-nsfw"]).await);
+    assert!(shown.contains("Adult"), "{}", shown);
+    assert!(!shown.contains("hidden"), "{}", shown);
+
+    let trending = stdout(&sabikani(&stub, "nsfw-trending", &["trending"]).await);
+    assert!(!trending.contains("Adult"), "{}", trending);
+    assert!(
+        trending.contains("1 adult result(s) hidden"),
+        "{}",
+        trending
+    );
+    let random = stdout(&sabikani(&stub, "nsfw-random", &["random", "--seed", "1"]).await);
+    assert!(random.contains("Safe"), "{}", random);
+}
+
+#[tokio::test]
+async fn air_date_ranges_are_checked() {
+    let stub = KitsuStub::start(vec![("anime", SEARCH_PAGE)]).await;
+    let output = sabikani(
+        &stub,
+        "backwards",
+        &[
+            "search",
+            "bebop",
+            "--since",
+            "2000-01-01",
+            "--until",
+            "1999-12-31",
+        ],
+    )
+    .await;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--since is after --until"), "{}", stderr);
+
+    let output = sabikani(
+        &stub,
+        "bad-date",
+        &["search", "bebop", "--since", "1998-02-30"],
+    )
+    .await;
+    assert!(!output.status.success());
+    assert!(stub.requests().is_empty());
+
+    // Cowboy Bebop started on 1998-04-03, the last day of the range.
+    let shown = stdout(
+        &sabikani(
+            &stub,
+            "range",
+            &[
+                "search",
+                "bebop",
+                "--since",
+                "1998-01-01",
+                "--until",
+                "1998-04-03",
+                "-q",
+            ],
+        )
+        .await,
+    );
+    assert_eq!(shown, "1\n");
+}
+
+#[tokio::test]
+async fn forced_hyperlinks_wrap_titles_in_osc_8() {
+    let stub = KitsuStub::start(vec![("anime", SEARCH_PAGE)]).await;
+    let linked = stdout(
+        &sabikani(
+            &stub,
+            "links",
+            &["search", "bebop", "--limit", "2", "--hyperlinks", "always"],
+        )
+        .await,
+    );
+    assert!(
+        linked.contains("\x1b]8;;https://kitsu.io/anime/1\x1b\\Cowboy Bebop\x1b]8;;\x1b\\"),
+        "{:?}",
+        linked
+    );
+
+    // Output that isn't a terminal gets none unless forced.
+    let plain = stdout(&sabikani(&stub, "no-links", &["search", "bebop", "--limit", "2"]).await);
+    assert!(!plain.contains("\x1b]8;;"), "{:?}", plain);
+}
+
+// Elsewhere the config file isn't under XDG_CONFIG_HOME.
+#[cfg(all(unix, not(target_os = "macos")))]
+#[tokio::test]
+async fn config_base_url_is_used_for_requests() {
+    let stub = KitsuStub::start(vec![("anime", SEARCH_PAGE)]).await;
+    let home = scratch_dir("base-url");
+    let config_dir = home.join("config/sabikani");
+    std::fs::create_dir_all(&config_dir).unwrap();
+    std::fs::write(
+        config_dir.join("config.toml"),
+        format!("[kitsu]\nbase_url = \"{}/\"\n", stub.base_url),
+    )
+    .unwrap();
+
+    let output = command_in(&stub, &home, &["search", "bebop", "-q"])
+        .env_remove("SABIKANI_BASE_URL")
+        .output()
+        .await
+        .unwrap();
+    assert_eq!(stdout(&output), "1\n4231\n");
+    let requests = stub.requests();
+    assert!(
+        requests[0].starts_with("GET /api/edge/anime?"),
+        "{}",
+        requests[0]
+    );
+
+    // The environment overrides the config, and is checked too.
+    let output = command_in(&stub, &home, &["search", "bebop", "-q"])
+        .env("SABIKANI_BASE_URL", "not a url")
+        .output()
+        .await
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("SABIKANI_BASE_URL is not usable"),
+        "{}",
+        stderr
+    );
+    assert_eq!(stub.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn table_titles_give_way_to_the_width() {
+    let stub = KitsuStub::start(vec![("anime", SEARCH_PAGE)]).await;
+    let table = stdout(
+        &sabikani(
+            &stub,
+            "table",
+            &[
+                "search",
+                "bebop",
+                "--limit",
+                "2",
+                "--format",
+                "table",
+                "--wrap-width",
+                "50",
+            ],
+        )
+        .await,
+    );
+
+    let rows: Vec<&str> = table
+        .lines()
+        .skip_while(|line| !line.starts_with('#'))
+        .take(4)
+        .collect();
+    assert!(rows[0].starts_with("#  Title"), "{}", table);
+    for row in &rows {
+        assert!(row.chars().count() <= 50, "{:?} is too wide", row);
+    }
+    assert!(rows[2].starts_with("1  Cowboy Bebop "), "{}", table);
+    // The longer title is cut, the columns after it kept.
+    assert!(rows[3].starts_with("2  Cowboy Bebop…  N/A"), "{}", table);
+}
+
+#[tokio::test]
+async fn requests_identify_themselves() {
+    let stub = KitsuStub::start(vec![("anime", SEARCH_PAGE)]).await;
+    stdout(&sabikani(&stub, "user-agent", &["search", "bebop", "-q"]).await);
+
+    let request = &stub.requests()[0];
+    let user_agent = format!("user-agent: sabikani/{}\r\n", env!("CARGO_PKG_VERSION"));
+    assert!(request.contains(&user_agent), "{}", request);
+    assert_eq!(
+        request.matches("accept: application/vnd.api+json").count(),
+        1,
+        "{}",
+        request
+    );
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+#[tokio::test]
+async fn config_headers_are_sent_to_kitsu() {
+    let stub = KitsuStub::start(vec![("anime", SEARCH_PAGE)]).await;
+    let home = scratch_dir("headers");
+    let config_dir = home.join("config/sabikani");
+    std::fs::create_dir_all(&config_dir).unwrap();
+    std::fs::write(
+        config_dir.join("config.toml"),
+        "[headers]\nX-Api-Gateway = \"abc123\"\nUser-Agent = \"my-proxy-client/2\"\n",
+    )
+    .unwrap();
+
+    let output = command_in(&stub, &home, &["search", "bebop", "-q"])
+        .output()
+        .await
+        .unwrap();
+    stdout(&output);
+
+    let request = &stub.requests()[0];
+    assert!(request.contains("x-api-gateway: abc123\r\n"), "{}", request);
+    // A configured User-Agent replaces the default rather than joining it.
+    assert!(
+        request.contains("user-agent: my-proxy-client/2\r\n"),
+        "{}",
+        request
+    );
+    assert!(!request.contains("sabikani/"), "{}", request);
+}
+
+#[tokio::test]
+async fn airing_browses_by_popularity_without_a_query() {
+    let stub = KitsuStub::start(vec![("anime", SEARCH_PAGE)]).await;
+    let described = stdout(&sabikani(&stub, "airing", &["search", "--airing", "--dry-run"]).await);
+    assert!(
+        described.contains("filter%5Bstatus%5D=current"),
+        "{}",
+        described
+    );
+    assert!(described.contains("sort=popularityRank"), "{}", described);
+
+    // An explicit sort wins over the popularity default.
+    let described = stdout(
+        &sabikani(
+            &stub,
+            "airing-sorted",
+            &["search", "--airing", "--sort", "rating", "--dry-run"],
+        )
+        .await,
+    );
+    assert!(described.contains("sort=-averageRating"), "{}", described);
+    assert!(stub.requests().is_empty());
+}
+
+#[tokio::test]
+async fn backwards_episode_ranges_are_rejected() {
+    let stub = KitsuStub::start(vec![("anime", SEARCH_PAGE)]).await;
+    let output = sabikani(
+        &stub,
+        "episodes",
+        &[
+            "search",
+            "bebop",
+            "--min-episodes",
+            "24",
+            "--max-episodes",
+            "12",
+        ],
+    )
+    .await;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("--min-episodes is above --max-episodes"),
+        "{}",
+        stderr
+    );
+    assert!(stub.requests().is_empty());
+}
+
+#[tokio::test]
+async fn bare_pick_uses_the_last_listing() {
+    let stub = KitsuStub::start(vec![("anime", SEARCH_PAGE)]).await;
+    let home = scratch_dir("pick");
+    let run = |args: &'static [&'static str]| {
+        let mut command = command_in(&stub, &home, args);
+        async move { command.output().await.unwrap() }
+    };
+
+    let nothing = run(&["search", "--pick", "1"]).await;
+    assert!(!nothing.status.success());
+    let stderr = String::from_utf8_lossy(&nothing.stderr);
+    assert!(stderr.contains("no listing to pick from"), "{}", stderr);
+
+    // Browses aren't in the search history, but their listing is what a
+    // pick follows up on.
+    stdout(
+        &run(&[
+            "search", "--year", "1998", "--sort", "-rating", "--limit", "2",
+        ])
+        .await,
+    );
+    assert_eq!(
+        stdout(&run(&["search", "--pick", "2", "-q"]).await),
+        "4231\n"
+    );
+    let details = stdout(&run(&["search", "--pick", "1"]).await);
+    assert!(details.contains("Cowboy Bebop"), "{}", details);
+    assert!(!details.contains("Searching for"), "{}", details);
+    assert!(!details.contains("Browsing"), "{}", details);
+    assert_eq!(stub.requests().len(), 1);
+
+    let searched = stdout(&run(&["search", "bebop", "--pick", "1"]).await);
+    assert!(!searched.contains("Searching for"), "{}", searched);
+}
+
+#[tokio::test]
+async fn episode_pages_stay_on_the_configured_api() {
+    // Kitsu's `next` link names its own host; following it would leave the
+    // stub, and the request would fail or reach somewhere else.
+    let first = r#"{
+        "data": [
+            {"id": "11", "attributes": {"number": 1, "canonicalTitle": "Asteroid Blues"}},
+            {"id": "12", "attributes": {"number": 2, "canonicalTitle": "Stray Dog Strut"}}
+        ],
+        "links": {"next": "http://kitsu.invalid/api/edge/anime/1/episodes?page%5Boffset%5D=2"}
+    }"#;
+    let last = r#"{
+        "data": [{"id": "13", "attributes": {"number": 3, "canonicalTitle": "Honky Tonk Women"}}],
+        "links": {}
+    }"#;
+    let stub = KitsuStub::start(vec![
+        ("episodes?page%5Blimit%5D=20&page%5Boffset%5D=0", first),
+        ("episodes?page%5Blimit%5D=20&page%5Boffset%5D=2", last),
+        (
+            "anime/1",
+            r#"{"data": {"id": "1", "attributes": {"canonicalTitle": "Cowboy Bebop"}}}"#,
+        ),
+    ])
+    .await;
+
+    let listed = stdout(&sabikani(&stub, "episode-pages", &["episodes", "1"]).await);
+    assert!(listed.contains("Asteroid Blues"), "{}", listed);
+    assert!(listed.contains("Honky Tonk Women"), "{}", listed);
+    assert_eq!(stub.requests().len(), 3);
+}
@@ -0,0 +1,139 @@
+//! A stand-in for the Kitsu API: a local HTTP server that answers each
+//! request with the first fixture whose key appears in the request target,
+//! or with a fixed sequence of replies. Shared by the integration tests and
+//! the crate's own unit tests, each of which uses only part of it.
+
+#![allow(dead_code)]
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
+
+/// One response the stub sends.
+#[derive(Debug, Clone)]
+pub struct Reply {
+    status: u16,
+    headers: Vec<(&'static str, String)>,
+    body: String,
+    delay: Duration,
+}
+
+impl Reply {
+    pub fn json(status: u16, body: &str) -> Reply {
+        Reply {
+            status,
+            headers: Vec::new(),
+            body: body.to_string(),
+            delay: Duration::ZERO,
+        }
+    }
+
+    pub fn header(mut self, name: &'static str, value: impl Into<String>) -> Reply {
+        self.headers.push((name, value.into()));
+        self
+    }
+
+    /// Holds the reply back for `delay`, without keeping other requests
+    /// waiting.
+    pub fn after(mut self, delay: Duration) -> Reply {
+        self.delay = delay;
+        self
+    }
+
+    fn to_http(&self) -> String {
+        let mut response = format!(
+            "HTTP/1.1 {} Stub\r\nContent-Type: application/vnd.api+json\r\n\
+             Content-Length: {}\r\nConnection: close\r\n",
+            self.status,
+            self.body.len()
+        );
+        for (name, value) in &self.headers {
+            response.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        response.push_str("\r\n");
+        response.push_str(&self.body);
+        response
+    }
+}
+
+pub struct KitsuStub {
+    /// The API root to point the client at, in place of `KITSU_API_URL`.
+    pub base_url: String,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl KitsuStub {
+    /// Starts serving `routes`, pairs of a request-target substring and the
+    /// JSON to answer with, on a free local port. Anything matching none of
+    /// them gets a 404.
+    pub async fn start(routes: Vec<(&'static str, impl Into<String>)>) -> KitsuStub {
+        KitsuStub::routes(
+            routes
+                .into_iter()
+                .map(|(key, body)| (key, Reply::json(200, &body.into())))
+                .collect(),
+        )
+        .await
+    }
+
+    /// Like `start`, with a whole reply for each route.
+    pub async fn routes(routes: Vec<(&'static str, Reply)>) -> KitsuStub {
+        KitsuStub::serve(move |target, _| {
+            match routes.iter().find(|(key, _)| target.contains(key)) {
+                Some((_, reply)) => reply.clone(),
+                None => Reply::json(404, r#"{"errors":[{"title":"Not Found"}]}"#),
+            }
+        })
+        .await
+    }
+
+    /// Answers the requests with `replies` in order, repeating the last one
+    /// once they run out.
+    pub async fn replies(replies: Vec<Reply>) -> KitsuStub {
+        KitsuStub::serve(move |_, count| replies[count.min(replies.len() - 1)].clone()).await
+    }
+
+    /// Answers each request with `respond(target, requests before it)`.
+    async fn serve(respond: impl Fn(&str, usize) -> Reply + Send + 'static) -> KitsuStub {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}/api/edge", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = requests.clone();
+
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut head = Vec::new();
+                let mut buf = [0; 4096];
+                while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+                    match stream.read(&mut buf).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => head.extend_from_slice(&buf[..n]),
+                    }
+                }
+                let head = String::from_utf8_lossy(&head).into_owned();
+                let target = head.split_whitespace().nth(1).unwrap_or_default();
+
+                let count = seen.lock().unwrap().len();
+                let reply = respond(target, count);
+                seen.lock().unwrap().push(head);
+                tokio::spawn(async move {
+                    tokio::time::sleep(reply.delay).await;
+                    let _ = stream.write_all(reply.to_http().as_bytes()).await;
+                });
+            }
+        });
+
+        KitsuStub { base_url, requests }
+    }
+
+    /// The request line and headers of every request so far, in order.
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}